@@ -0,0 +1,97 @@
+use crate::near::batch::parse_concurrency;
+use crate::near::rpc::client::Client;
+use crate::near::types::NearEnv;
+use crate::oct::audit::{fetch_stake_audits, print_stake_audit};
+use crate::oct::scan::{active_anchors_of_registry, parse_list, AppchainScanResult, ALL};
+use crate::CliResult;
+use color_eyre::owo_colors::OwoColorize;
+use dialoguer::Input;
+
+#[derive(Debug, Clone, interactive_clap_derive::InteractiveClap)]
+#[interactive_clap(context = ())]
+pub struct AuditRegistry {
+    pub registry_account: String,
+    #[interactive_clap(long)]
+    pub appchain: String,
+    #[interactive_clap(long)]
+    pub concurrency: String,
+}
+
+impl AuditRegistry {
+    pub fn input_registry_account(_context: &()) -> color_eyre::eyre::Result<String> {
+        Ok(Input::new()
+            .with_prompt("What is the registry account?")
+            .interact_text()?)
+    }
+
+    pub fn input_appchain(_context: &()) -> color_eyre::eyre::Result<String> {
+        Ok(Input::new()
+            .with_prompt("Which appchains (comma separated ids, or 'all')?")
+            .default(ALL.to_string())
+            .interact_text()?)
+    }
+
+    pub fn input_concurrency(_context: &()) -> color_eyre::eyre::Result<String> {
+        Ok(Input::new()
+            .with_prompt("How many requests to send at the same time?")
+            .default("8".to_string())
+            .interact_text()?)
+    }
+
+    pub async fn process(self, _connection_config: NearEnv, client: Client) -> CliResult {
+        let concurrency = parse_concurrency(&self.concurrency)?;
+        let anchors = active_anchors_of_registry(
+            &client,
+            self.registry_account.parse()?,
+            &parse_list(&self.appchain),
+            concurrency,
+        )
+        .await
+        .map_err(|err| color_eyre::Report::msg(err.to_string()))?;
+        let results = fetch_stake_audits(&client, anchors, concurrency)
+            .await
+            .map_err(|err| color_eyre::Report::msg(err.to_string()))?;
+
+        let mut failed = 0;
+        let mut violations = 0;
+        for result in &results {
+            match result {
+                Ok(audit) => {
+                    print_stake_audit(audit);
+                    violations += audit.violations.len();
+                }
+                Err(AppchainScanResult::Skipped {
+                    anchor_account_id,
+                    appchain_state,
+                    ..
+                }) => println!(
+                    "The state of {} is {:?}, skip audit!\n",
+                    anchor_account_id.blue(),
+                    appchain_state.yellow()
+                ),
+                Err(AppchainScanResult::Failed { appchain_id, error }) => {
+                    failed += 1;
+                    println!(
+                        "{}\n",
+                        format!("Failed to audit {}: {}", appchain_id, error).red()
+                    );
+                }
+                Err(AppchainScanResult::Scanned(_)) => {}
+            }
+        }
+        if failed > 0 {
+            return Err(color_eyre::Report::msg(format!(
+                "Failed to audit {} of {} appchains",
+                failed,
+                results.len()
+            )));
+        }
+        if violations > 0 {
+            return Err(color_eyre::Report::msg(format!(
+                "Found {} violations of the protocol settings",
+                violations
+            )));
+        }
+        Ok(())
+    }
+}
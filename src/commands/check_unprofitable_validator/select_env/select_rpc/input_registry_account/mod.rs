@@ -1,20 +1,42 @@
+use crate::near::batch::parse_concurrency;
 use crate::near::rpc::client::Client;
 use crate::near::types::NearEnv;
-use crate::oct::contracts::anchor::AnchorContract;
-use crate::oct::contracts::registry::RegistryContract;
+use crate::oct::report::{
+    parse_report_path, save_csv, unprofitable_html, unprofitable_records, NO_REPORT,
+};
+use crate::oct::scan::{
+    parse_era, parse_list, parse_validator_ids, scan_registry, AnchorScan, AppchainScanResult,
+    ScanFilter, UnbondRisk, ALL, AUTO_ERA, LATEST_ERA,
+};
 use crate::CliResult;
-use appchain_registry::types::AppchainState;
 use color_eyre::owo_colors::OwoColorize;
 use dialoguer::Input;
 use itertools::Itertools;
 use near_primitives::types::AccountId;
 use prettytable::{row, table, Cell, Row, Table};
-use std::collections::{HashMap, HashSet};
 
 #[derive(Debug, Clone, interactive_clap_derive::InteractiveClap)]
 #[interactive_clap(context = ())]
 pub struct InputRegistryAccount {
     pub registry_account: String,
+    /// Comma separated appchain ids, eg: fusotao,barnacle, or 'all'
+    #[interactive_clap(long)]
+    pub appchain: String,
+    #[interactive_clap(long)]
+    pub from_era: String,
+    #[interactive_clap(long)]
+    pub to_era: String,
+    /// Comma separated validator accounts, or 'all'
+    #[interactive_clap(long)]
+    pub validator: String,
+    #[interactive_clap(long)]
+    pub concurrency: String,
+    #[interactive_clap(long)]
+    pub fail_if_at_risk: String,
+    #[interactive_clap(long)]
+    pub csv: String,
+    #[interactive_clap(long)]
+    pub html: String,
 }
 
 impl InputRegistryAccount {
@@ -24,127 +46,189 @@ impl InputRegistryAccount {
             .interact_text()?)
     }
 
+    pub fn input_appchain(_context: &()) -> color_eyre::eyre::Result<String> {
+        Ok(Input::new()
+            .with_prompt("Which appchains (comma separated ids, or 'all')?")
+            .default(ALL.to_string())
+            .interact_text()?)
+    }
+
+    pub fn input_from_era(_context: &()) -> color_eyre::eyre::Result<String> {
+        Ok(Input::new()
+            .with_prompt("From which era ('auto' is the oldest era counted for auto-unbonding)?")
+            .default(AUTO_ERA.to_string())
+            .interact_text()?)
+    }
+
+    pub fn input_to_era(_context: &()) -> color_eyre::eyre::Result<String> {
+        Ok(Input::new()
+            .with_prompt("To which era?")
+            .default(LATEST_ERA.to_string())
+            .interact_text()?)
+    }
+
+    pub fn input_validator(_context: &()) -> color_eyre::eyre::Result<String> {
+        Ok(Input::new()
+            .with_prompt("Which validators (comma separated, or 'all')?")
+            .default(ALL.to_string())
+            .interact_text()?)
+    }
+
+    pub fn input_concurrency(_context: &()) -> color_eyre::eyre::Result<String> {
+        Ok(Input::new()
+            .with_prompt("How many requests to send at the same time?")
+            .default("8".to_string())
+            .interact_text()?)
+    }
+
+    pub fn input_fail_if_at_risk(_context: &()) -> color_eyre::eyre::Result<String> {
+        Ok(Input::new()
+            .with_prompt(
+                "Fail when a validator is one unprofitable era or less from auto-unbond?[y/n]",
+            )
+            .default("n".to_string())
+            .interact_text()?)
+    }
+
+    pub fn input_csv(_context: &()) -> color_eyre::eyre::Result<String> {
+        Ok(Input::new()
+            .with_prompt("Which file to export the check to as csv (or 'none')?")
+            .default(NO_REPORT.to_string())
+            .interact_text()?)
+    }
+
+    pub fn input_html(_context: &()) -> color_eyre::eyre::Result<String> {
+        Ok(Input::new()
+            .with_prompt("Which file to export the check to as html (or 'none')?")
+            .default(NO_REPORT.to_string())
+            .interact_text()?)
+    }
+
     pub async fn process(self, connection_config: NearEnv, client: Client) -> CliResult {
-        let registry_contract = RegistryContract::new(self.registry_account.parse()?, &client);
-        let appchain_ids = registry_contract
-            .get_appchain_ids()
-            .await
-            .map_err(|err| {
-                color_eyre::Report::msg(format!(
-                    "Failed to get appchain ids,contract_id:{}, error: {}",
-                    self.registry_account.clone(),
-                    err
-                ))
-            })
-            .unwrap();
-
-        for appchain_id in appchain_ids {
-            let anchor_contract_id: AccountId =
-                format!("{}.{}", appchain_id, self.registry_account).parse()?;
-
-            let anchor = AnchorContract {
-                account_id: anchor_contract_id.clone(),
-                client: &client,
-            };
-
-            let appchain_state = registry_contract
-                .get_appchain_status_of(appchain_id.clone())
-                .await
-                .map_err(|err| {
-                    color_eyre::Report::msg(format!(
-                        "Failed to get anchor({}) state, error: {}",
-                        anchor_contract_id, err
-                    ))
-                })?
-                .appchain_state;
-
-            if !(matches!(appchain_state, AppchainState::Active)) {
-                println!(
+        let concurrency = parse_concurrency(&self.concurrency)?;
+        let filter = ScanFilter {
+            appchain_ids: parse_list(&self.appchain),
+            from_era: parse_era(&self.from_era, AUTO_ERA)?,
+            to_era: parse_era(&self.to_era, LATEST_ERA)?,
+            validator_ids: parse_validator_ids(&self.validator)?,
+        };
+        if let (Some(from_era), Some(to_era)) = (filter.from_era, filter.to_era) {
+            if from_era > to_era {
+                return Err(color_eyre::Report::msg(format!(
+                    "The from era {} is after the to era {}",
+                    from_era, to_era
+                )));
+            }
+        }
+        let registry_account_id: AccountId = self.registry_account.parse()?;
+        let archival_client = Client::new(connection_config.archival_rpc_url().as_str());
+        let results = scan_registry(
+            &client,
+            Some(&archival_client),
+            registry_account_id.clone(),
+            &filter,
+            concurrency,
+        )
+        .await
+        .map_err(|err| color_eyre::Report::msg(err.to_string()))?;
+
+        let fail_if_at_risk = self.fail_if_at_risk.eq("y") || self.fail_if_at_risk.eq("Y");
+        let mut failed = 0;
+        let mut at_risk = 0;
+        for result in &results {
+            match result {
+                AppchainScanResult::Scanned(scan) => {
+                    print_auto_unbound_info_table(scan);
+                    if !scan.missing_eras.is_empty() {
+                        println!(
+                            "{}",
+                            format!(
+                                "No validator set info of eras {} in {}, they are left out.",
+                                scan.missing_eras.iter().join(", "),
+                                scan.anchor_account_id
+                            )
+                            .yellow()
+                        );
+                    }
+                    let risks = scan.unbond_risks();
+                    print_unbond_risk_table(scan, &risks);
+                    at_risk += risks.iter().filter(|e| e.is_at_risk()).count();
+                }
+                AppchainScanResult::Skipped {
+                    anchor_account_id,
+                    appchain_state,
+                    ..
+                } => println!(
                     "The state of {} is {:?}, skip check!\n",
-                    anchor_contract_id.blue(),
+                    anchor_account_id.blue(),
                     appchain_state.yellow()
-                );
-                continue;
+                ),
+                AppchainScanResult::Failed { appchain_id, error } => {
+                    failed += 1;
+                    println!(
+                        "{}\n",
+                        format!("Failed to check {}: {}", appchain_id, error).red()
+                    );
+                }
             }
-
-            let anchor_status = anchor.get_anchor_status().await.map_err(|err| {
+        }
+        let scans = results
+            .iter()
+            .filter_map(|e| match e {
+                AppchainScanResult::Scanned(scan) => Some(scan),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        if let Some(path) = parse_report_path(&self.csv) {
+            save_csv(&path, &unprofitable_records(&scans)).map_err(|err| {
                 color_eyre::Report::msg(format!(
-                    "Failed to get anchor({}) status, error: {}",
-                    anchor_contract_id, err
+                    "Failed to save csv report to {}, error: {}",
+                    path.display(),
+                    err
                 ))
             })?;
-            let era_now = anchor_status.index_range_of_validator_set_history.end_index;
-
-            let setting = anchor.get_protocol_settings().await.map_err(|err| {
-                color_eyre::Report::msg(format!("Failed to get protocol settings, error: {}", err))
-            })?;
-
-            let start_check_era =
-                if (setting.maximum_allowed_unprofitable_era_count as u64) > era_now.0 {
-                    0
-                } else {
-                    era_now.0 - (setting.maximum_allowed_unprofitable_era_count as u64)
-                };
-
-            let mut era_with_unprofitable_validator_ids = HashMap::new();
-            let mut unprofitable_account_ids = HashSet::new();
-
-            for era in start_check_era..(era_now.0 + 1) {
-                let info = anchor
-                    .get_validator_set_info_of(era)
-                    .await
-                    .map_err(|err| {
-                        color_eyre::Report::msg(format!(
-                            "Failed to get validator set info, error: {}",
-                            err
-                        ))
-                    })?
-                    .expect(
-                        format!("Error: Get empty validator set info of era({})", era).as_str(),
-                    );
-
-                info.unprofitable_validator_ids.iter().for_each(|e| {
-                    unprofitable_account_ids.insert(AccountId::try_from(e.to_string()).unwrap());
-                });
-
-                era_with_unprofitable_validator_ids.insert(
-                    era,
-                    info.unprofitable_validator_ids
-                        .iter()
-                        .map(|id| AccountId::try_from(id.to_string()).unwrap())
-                        .collect(),
-                );
-            }
-            print_auto_unbound_info_table(
-                anchor_contract_id,
-                unprofitable_account_ids,
-                era_with_unprofitable_validator_ids,
+            println!("Saved csv report to {}", path.display());
+        }
+        if let Some(path) = parse_report_path(&self.html) {
+            let html = unprofitable_html(
+                &scans,
+                &format!("Unprofitable validators of {}", registry_account_id),
+                &chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
             );
+            std::fs::write(&path, html).map_err(|err| {
+                color_eyre::Report::msg(format!(
+                    "Failed to save html report to {}, error: {}",
+                    path.display(),
+                    err
+                ))
+            })?;
+            println!("Saved html report to {}", path.display());
+        }
+        if failed > 0 {
+            return Err(color_eyre::Report::msg(format!(
+                "Failed to check {} of {} appchains",
+                failed,
+                results.len()
+            )));
+        }
+        if fail_if_at_risk && at_risk > 0 {
+            return Err(color_eyre::Report::msg(format!(
+                "{} validators are at risk of being auto-unbonded",
+                at_risk
+            )));
         }
-
         Ok(())
     }
 }
 
-fn print_auto_unbound_info_table(
-    anchor_contract_id: AccountId,
-    unprofitable_account_ids: HashSet<AccountId>,
-    era_with_unprofitable_validator_ids: HashMap<u64, Vec<AccountId>>,
-) {
+fn print_auto_unbound_info_table(scan: &AnchorScan) {
     let mut table = Table::new();
-    let eras = era_with_unprofitable_validator_ids
-        .iter()
-        .map(|(era, _)| *era)
-        .sorted()
-        .collect_vec();
-
-    assert!(
-        eras.len() > 0,
-        "Failed to print_auto_unbound_info_table, the era len should > 0."
-    );
+    let matrix = scan.unprofitable_matrix();
+    let eras = &matrix.eras;
 
-    if unprofitable_account_ids.is_empty() {
-        // head.add_cell(Cell::new(format!("No unprofitable validator in allowed time range.").green().to_string().as_str()));
+    if eras.is_empty() {
+        table.add_row(row!["No era to check.".yellow()]);
+    } else if matrix.rows.is_empty() {
         table.add_row(row![format!(
             "No unprofitable validator from era_{} to era_{}.",
             eras[0],
@@ -157,14 +241,10 @@ fn print_auto_unbound_info_table(
         eras.iter()
             .for_each(|era| head.add_cell(Cell::new(era.to_string().as_str())));
         table.add_row(head);
-        for account_id in unprofitable_account_ids {
+        for (account_id, unprofitable) in &matrix.rows {
             let mut row = row![account_id];
-            for era in &eras {
-                let is_unprofitable = era_with_unprofitable_validator_ids
-                    .get(era)
-                    .expect("Failed to get user's information unprofitable")
-                    .contains(&account_id);
-                let cell_content = if is_unprofitable {
+            for unprofitable in unprofitable {
+                let cell_content = if *unprofitable {
                     format!("×").red().to_string()
                 } else {
                     format!("√").green().to_string()
@@ -178,7 +258,7 @@ fn print_auto_unbound_info_table(
     let title = Row::new(vec![Cell::new(
         format!(
             "{} unprofitable_validator_ids table",
-            anchor_contract_id.blue()
+            scan.anchor_account_id.blue()
         )
         .as_str(),
     )
@@ -188,6 +268,43 @@ fn print_auto_unbound_info_table(
     table.printstd();
 }
 
+/// Prints the validators unprofitable in the last era with when they will be auto-unbonded.
+fn print_unbond_risk_table(scan: &AnchorScan, risks: &[UnbondRisk]) {
+    if risks.is_empty() {
+        return;
+    }
+    let mut table = Table::new();
+    table.set_titles(Row::new(vec![Cell::new(
+        format!(
+            "{} auto-unbond risk, at most {} unprofitable eras in a row are allowed",
+            scan.anchor_account_id.blue(),
+            scan.maximum_allowed_unprofitable_era_count
+        )
+        .as_str(),
+    )
+    .style_spec("H4")]));
+    table.add_row(row![
+        "validator",
+        "unprofitable streak",
+        "eras left",
+        "predicted unbond era"
+    ]);
+    for risk in risks {
+        let eras_left = match risk.eras_left {
+            0 => "due".red().to_string(),
+            1 => risk.eras_left.yellow().to_string(),
+            _ => risk.eras_left.to_string(),
+        };
+        table.add_row(row![
+            risk.validator_id,
+            risk.streak,
+            eras_left,
+            risk.predicted_unbond_era
+        ]);
+    }
+    table.printstd();
+}
+
 struct AutoBondInfo {
     anchor_contract_name: AccountId,
     current_era: u64,
@@ -205,7 +322,13 @@ fn test() {
     )]));
     table.printstd();
 
-    let state = AppchainState::Active;
-    println!("{}", matches!(state, AppchainState::Active));
-    println!("{}", matches!(state, AppchainState::Broken));
+    let state = appchain_registry::types::AppchainState::Active;
+    println!(
+        "{}",
+        matches!(state, appchain_registry::types::AppchainState::Active)
+    );
+    println!(
+        "{}",
+        matches!(state, appchain_registry::types::AppchainState::Broken)
+    );
 }
@@ -1,13 +1,65 @@
-use crate::near::contracts::clean_state::CleanStateContract;
-use crate::near::rpc::client::Client;
+use crate::commands::snapshot_command::confirm_incomplete_snapshot;
+use crate::near::batch::{
+    parse_concurrency, print_account_outcomes, run_for_signers, AccountOutcome, AccountStatus,
+};
+use crate::near::contracts::clean_state::{
+    parse_clean_gas_budget, CleanKey, CleanStateContract, STATE_CLEANUP_WASM,
+};
+use crate::near::rpc::client::{Client, DEFAULT_DEPLOY_AND_INIT_GAS};
+use crate::near::rpc::result::CallExecutionDetails;
+use crate::near::snapshot::{find_latest_snapshot, ContractSnapshot};
+use crate::near::state_key::{print_key_groups, KeyFilter};
+use crate::near::storage::{print_storage_reclaim_table, storage_amount_per_byte, StorageReclaim};
 use crate::near::types::NearEnv;
+use crate::near::util::get_default_snapshot_dir_path;
+use crate::near::wasm::{pre_check_deploy, WasmInspection};
 use crate::CliResult;
+use color_eyre::owo_colors::OwoColorize;
 use dialoguer::Input;
+use indicatif::{MultiProgress, ProgressBar};
+use near_crypto::InMemorySigner;
+use near_primitives::hash::CryptoHash;
+use near_primitives::types::{AccountId, Balance, Gas};
+use near_primitives::views::AccountView;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+pub const KEEP_CLEAN_CONTRACT: &str = "keep";
+pub const ORIGINAL_CODE: &str = "original";
+pub const NO_INIT_METHOD: &str = "none";
+
+/// What the account runs once its state is cleaned.
+#[derive(Debug, Clone)]
+enum AfterClean {
+    /// Leave the clean state contract deployed.
+    Keep,
+    /// Redeploy the code saved in the snapshot taken before cleaning.
+    Original,
+    /// Deploy another wasm.
+    Wasm(Vec<u8>),
+}
 
 #[derive(Debug, Clone, interactive_clap_derive::InteractiveClap)]
 #[interactive_clap(context = ())]
 pub struct CleanState {
     confirm: String,
+    #[interactive_clap(long)]
+    pub concurrency: String,
+    #[interactive_clap(long)]
+    pub continue_on_error: String,
+    #[interactive_clap(long)]
+    pub gas_budget: String,
+    #[interactive_clap(long)]
+    pub prefix: String,
+    #[interactive_clap(long)]
+    pub exclude_prefix: String,
+    #[interactive_clap(long)]
+    pub after_clean: String,
+    #[interactive_clap(long)]
+    pub init_method: String,
+    #[interactive_clap(long)]
+    pub init_args: String,
 }
 
 impl CleanState {
@@ -17,6 +69,115 @@ impl CleanState {
             .interact_text()?)
     }
 
+    pub fn input_concurrency(_context: &()) -> color_eyre::eyre::Result<String> {
+        Ok(Input::new()
+            .with_prompt("How many accounts to clean at the same time?")
+            .default("1".to_string())
+            .interact_text()?)
+    }
+
+    pub fn input_continue_on_error(_context: &()) -> color_eyre::eyre::Result<String> {
+        Ok(Input::new()
+            .with_prompt("Continue with the other accounts when one fails?[y/n]")
+            .default("n".to_string())
+            .interact_text()?)
+    }
+
+    pub fn input_gas_budget(_context: &()) -> color_eyre::eyre::Result<String> {
+        Ok(Input::new()
+            .with_prompt("How much gas may the keys removed by one clean transaction use?")
+            .default("200 TGas".to_string())
+            .interact_text()?)
+    }
+
+    pub fn input_prefix(_context: &()) -> color_eyre::eyre::Result<String> {
+        Ok(Input::new()
+            .with_prompt(
+                "Only clean keys with these prefixes (hex:, base64: or utf8:, comma separated)?",
+            )
+            .default("all".to_string())
+            .interact_text()?)
+    }
+
+    pub fn input_exclude_prefix(_context: &()) -> color_eyre::eyre::Result<String> {
+        Ok(Input::new()
+            .with_prompt("Keep keys with these prefixes (hex:, base64: or utf8:, comma separated)?")
+            .default("none".to_string())
+            .interact_text()?)
+    }
+
+    pub fn input_after_clean(_context: &()) -> color_eyre::eyre::Result<String> {
+        Ok(Input::new()
+            .with_prompt("Deploy the 'original' code, a wasm path, or 'keep' the clean state contract after cleaning?")
+            .default(ORIGINAL_CODE.to_string())
+            .interact_text()?)
+    }
+
+    pub fn input_init_method(_context: &()) -> color_eyre::eyre::Result<String> {
+        Ok(Input::new()
+            .with_prompt("Which method to call with the deploy after cleaning, or 'none'?")
+            .default(NO_INIT_METHOD.to_string())
+            .interact_text()?)
+    }
+
+    pub fn input_init_args(_context: &()) -> color_eyre::eyre::Result<String> {
+        Ok(Input::new()
+            .with_prompt("What are the args of the init method?")
+            .default("{}".to_string())
+            .interact_text()?)
+    }
+
+    /// Reads `--after-clean`, checking a custom wasm and the init method before anything
+    /// is cleaned.
+    async fn after_clean(&self, client: &Client) -> color_eyre::eyre::Result<AfterClean> {
+        let init_method = if self.init_method.eq(NO_INIT_METHOD) {
+            None
+        } else {
+            Some(self.init_method.as_str())
+        };
+        if init_method.is_some() {
+            serde_json::from_str::<serde_json::Value>(&self.init_args).map_err(|err| {
+                color_eyre::Report::msg(format!(
+                    "Init args are not valid JSON ({}): {}",
+                    err, self.init_args
+                ))
+            })?;
+        }
+        match self.after_clean.as_str() {
+            KEEP_CLEAN_CONTRACT => {
+                if init_method.is_some() {
+                    return Err(color_eyre::Report::msg(
+                        "An init method needs --after-clean original or a wasm path",
+                    ));
+                }
+                Ok(AfterClean::Keep)
+            }
+            ORIGINAL_CODE => Ok(AfterClean::Original),
+            wasm_path => {
+                let code = std::fs::read(Path::new(wasm_path)).map_err(|err| {
+                    color_eyre::Report::msg(format!(
+                        "Failed to read wasm file {}, error: {}",
+                        wasm_path, err
+                    ))
+                })?;
+                let checked = match init_method {
+                    Some(init_method) => pre_check_deploy(client, &code, init_method)
+                        .await
+                        .map(|_| ()),
+                    None => WasmInspection::inspect(&code)
+                        .and_then(|inspection| inspection.check_imports()),
+                };
+                checked.map_err(|err| {
+                    color_eyre::Report::msg(format!(
+                        "Wasm {} can not be deployed after cleaning: {}",
+                        wasm_path, err
+                    ))
+                })?;
+                Ok(AfterClean::Wasm(code))
+            }
+        }
+    }
+
     pub async fn process(
         self,
         connection_config: NearEnv,
@@ -24,48 +185,551 @@ impl CleanState {
         client: Client,
     ) -> CliResult {
         if self.confirm.eq("y") || self.confirm.eq("Y") {
-            for account in account_list {
-                println!("\n---Start clean {} states", account.account_id);
+            let concurrency = parse_concurrency(&self.concurrency)?;
+            let continue_on_error =
+                self.continue_on_error.eq("y") || self.continue_on_error.eq("Y");
+            let gas_budget = parse_clean_gas_budget(&self.gas_budget)
+                .map_err(|err| color_eyre::Report::msg(err.to_string()))?;
+            let filter = KeyFilter::parse(&self.prefix, &self.exclude_prefix)
+                .map_err(|err| color_eyre::Report::msg(err.to_string()))?;
+            let after_clean = self.after_clean(&client).await?;
+            if matches!(after_clean, AfterClean::Keep) && !filter.is_empty() {
+                return Err(color_eyre::Report::msg(
+                    "A clean up by prefix can not keep the clean state contract, use --after-clean original",
+                ));
+            }
+            let snapshot_dir = get_default_snapshot_dir_path(&connection_config);
 
-                let clean_contract = CleanStateContract {
-                    account_id: account.account_id.clone(),
-                    client: &client,
-                };
+            let (account_list, selections) = if filter.is_empty() {
+                (account_list, None)
+            } else {
+                let selections = select_keys(&client, &account_list, &filter).await?;
+                let account_list = account_list
+                    .into_iter()
+                    .filter(|e| selections.contains_key(&e.account_id))
+                    .collect::<Vec<_>>();
+                (account_list, Some(selections))
+            };
+            if account_list.is_empty() {
+                println!("Cancel clean state!");
+                return Ok(());
+            }
+
+            let context = &CleanContext {
+                client: &client,
+                snapshot_dir: snapshot_dir.as_path(),
+                gas_budget,
+                selections,
+                after_clean,
+                init_call: (!self.init_method.eq(NO_INIT_METHOD))
+                    .then(|| (self.init_method.clone(), self.init_args.clone())),
+                filter,
+                storage_amount_per_byte: storage_amount_per_byte(&client).await,
+                reclaims: Mutex::new(vec![]),
+            };
+            let multi_progress = &MultiProgress::new();
+            let outcomes = run_for_signers(
+                account_list,
+                concurrency,
+                continue_on_error,
+                |account| async move {
+                    let mut outcome = AccountOutcome::new(account.account_id.clone());
+                    let progress = multi_progress
+                        .add(ProgressBar::new(0).with_prefix(account.account_id.to_string()));
+                    if let Err(err) = context
+                        .clean_account(&account, &progress, &mut outcome)
+                        .await
+                    {
+                        outcome.fail(err);
+                    }
+                    outcome
+                },
+            )
+            .await;
+            if matches!(context.after_clean, AfterClean::Keep) {
+                let kept = outcomes
+                    .iter()
+                    .filter(|e| !e.is_failure() && e.status != AccountStatus::NotStarted)
+                    .map(|e| e.account_id.to_string())
+                    .collect::<Vec<_>>();
+                if !kept.is_empty() {
+                    println!(
+                        "{} {}",
+                        "These accounts still run the clean state contract:".yellow(),
+                        kept.join(", ")
+                    );
+                }
+            }
+            let mut reclaims = context
+                .reclaims
+                .lock()
+                .expect("reclaims are not poisoned")
+                .clone();
+            if !reclaims.is_empty() {
+                reclaims.sort_by_key(|reclaim| {
+                    outcomes
+                        .iter()
+                        .position(|e| e.account_id == reclaim.account_id)
+                });
+                print_storage_reclaim_table(&reclaims);
+            }
+            print_account_outcomes(&outcomes)
+        } else {
+            println!("Cancel clean state!");
+            Ok(())
+        }
+    }
+}
+
+/// Lists and previews the keys selected by `filter` on every account, keeping the accounts
+/// whose deletion list is confirmed.
+async fn select_keys(
+    client: &Client,
+    account_list: &[InMemorySigner],
+    filter: &KeyFilter,
+) -> color_eyre::eyre::Result<HashMap<AccountId, Vec<CleanKey>>> {
+    let mut selections = HashMap::new();
+    for account in account_list {
+        let clean_contract = CleanStateContract {
+            account_id: account.account_id.clone(),
+            client,
+        };
+        let progress = ProgressBar::new(0).with_prefix(account.account_id.to_string());
+        let keys = clean_contract
+            .list_selected_keys(filter, &progress)
+            .await
+            .map_err(|error| {
+                color_eyre::Report::msg(format!(
+                    "Failed to list account({}) states, error: {}",
+                    account.account_id, error
+                ))
+            })?;
+        progress.finish_and_clear();
+
+        if keys.is_empty() {
+            println!("No keys of {} are selected, skip it", account.account_id);
+            continue;
+        }
+        println!("Keys of {} to delete:", account.account_id);
+        print_key_groups(
+            &keys
+                .iter()
+                .map(|e| (e.key.clone(), e.size))
+                .collect::<Vec<_>>(),
+            filter,
+        );
+        let confirm: String = Input::new()
+            .with_prompt(format!(
+                "Delete these {} keys of {}?[y/n]",
+                keys.len(),
+                account.account_id
+            ))
+            .interact_text()?;
+        if confirm.eq("y") || confirm.eq("Y") {
+            selections.insert(account.account_id.clone(), keys);
+        } else {
+            println!("Skip clean state of {}", account.account_id);
+        }
+    }
+    Ok(selections)
+}
+
+struct CleanContext<'a> {
+    client: &'a Client,
+    snapshot_dir: &'a Path,
+    gas_budget: Gas,
+    /// The confirmed keys of every account, or `None` to clean all keys.
+    selections: Option<HashMap<AccountId, Vec<CleanKey>>>,
+    after_clean: AfterClean,
+    /// Method and args called with the deploy after cleaning.
+    init_call: Option<(String, String)>,
+    filter: KeyFilter,
+    storage_amount_per_byte: Balance,
+    reclaims: Mutex<Vec<StorageReclaim>>,
+}
+
+impl<'a> CleanContext<'a> {
+    async fn clean_account(
+        &self,
+        account: &InMemorySigner,
+        progress: &ProgressBar,
+        outcome: &mut AccountOutcome,
+    ) -> CliResult {
+        println!("\n---Start clean {} states", account.account_id);
+        let clean_contract = CleanStateContract {
+            account_id: account.account_id.clone(),
+            client: self.client,
+        };
+        match &self.selections {
+            None => {
+                self.clean_all_keys(&clean_contract, account, progress, outcome)
+                    .await?
+            }
+            Some(selections) => {
+                let keys = selections
+                    .get(&account.account_id)
+                    .map(|e| e.as_slice())
+                    .unwrap_or_default();
+                self.clean_selected_keys(&clean_contract, account, keys, progress, outcome)
+                    .await?
+            }
+        }
+        println!("---End clean {} states\n", account.account_id);
+        Ok(())
+    }
+
+    async fn clean_all_keys(
+        &self,
+        clean_contract: &CleanStateContract<'_>,
+        account: &InMemorySigner,
+        progress: &ProgressBar,
+        outcome: &mut AccountOutcome,
+    ) -> CliResult {
+        let resumed = self
+            .is_running_clean_contract(clean_contract, account)
+            .await?;
+        let before = self.view_account(account).await?;
+
+        let snapshot = if resumed {
+            // The snapshot was saved when the clean up started, the state left now is partial.
+            println!(
+                "Account({}) already runs the clean state contract, resume cleaning",
+                account.account_id
+            );
+            match self.after_clean {
+                AfterClean::Original => Some(self.load_snapshot_before_clean(account)?),
+                _ => None,
+            }
+        } else {
+            let snapshot = self.take_snapshot(account).await?;
+            self.deploy_clean_contract(clean_contract, account, outcome)
+                .await?;
+            Some(snapshot)
+        };
+
+        let keys = clean_contract
+            .list_keys(&[], progress)
+            .await
+            .map_err(|error| {
+                color_eyre::Report::msg(format!(
+                    "Failed to list account({}) states, error: {}",
+                    account.account_id, error
+                ))
+            })?;
+        let clean_outcomes = clean_contract
+            .clean_keys(account, &keys, self.gas_budget, progress)
+            .await
+            .map_err(|error| {
+                color_eyre::Report::msg(format!(
+                    "Failed to clean account({}) states, run it again to resume, error: {}",
+                    account.account_id, error
+                ))
+            })?;
+        for clean_outcome in &clean_outcomes {
+            outcome.record_transaction(clean_outcome);
+        }
+
+        let (remaining, _) = self
+            .client
+            .view_state_paged(account.account_id.clone(), &[], None, |_, _| {})
+            .await
+            .map_err(|error| {
+                color_eyre::Report::msg(format!(
+                    "Failed to view account({}) states, error: {}",
+                    account.account_id, error
+                ))
+            })?;
+        if !remaining.is_empty() {
+            // Keep the clean state contract, so running it again resumes the clean up.
+            self.record_reclaim(account, &before, keys.len(), remaining.len())
+                .await?;
+            return Err(color_eyre::Report::msg(format!(
+                "Account({}) has {} keys left after clean up, run it again to resume",
+                account.account_id,
+                remaining.len()
+            )));
+        }
+
+        self.deploy_after_clean(account, snapshot.as_ref(), outcome)
+            .await?;
+        self.record_reclaim(account, &before, keys.len(), 0).await
+    }
+
+    /// Removes only `keys`, then deploys the code chosen by `--after-clean`.
+    async fn clean_selected_keys(
+        &self,
+        clean_contract: &CleanStateContract<'_>,
+        account: &InMemorySigner,
+        keys: &[CleanKey],
+        progress: &ProgressBar,
+        outcome: &mut AccountOutcome,
+    ) -> CliResult {
+        if self
+            .is_running_clean_contract(clean_contract, account)
+            .await?
+        {
+            return Err(color_eyre::Report::msg(format!(
+                "Account({}) runs the clean state contract, finish it with a full clean-state or restore a snapshot first",
+                account.account_id
+            )));
+        }
+
+        let before = self.view_account(account).await?;
+        let snapshot = self.take_snapshot(account).await?;
+        self.deploy_clean_contract(clean_contract, account, outcome)
+            .await?;
+        let clean_result = clean_contract
+            .clean_keys(account, keys, self.gas_budget, progress)
+            .await;
+        if let Ok(clean_outcomes) = &clean_result {
+            for clean_outcome in clean_outcomes {
+                outcome.record_transaction(clean_outcome);
+            }
+        }
 
-                clean_contract.deploy(&account).await.map_err(|error| {
+        let remaining = clean_contract
+            .list_selected_keys(&self.filter, &ProgressBar::hidden())
+            .await;
+
+        // Deploy even if the clean up failed, the account must not keep the clean contract.
+        self.deploy_after_clean(account, Some(&snapshot), outcome)
+            .await?;
+        clean_result.map_err(|error| {
+            color_eyre::Report::msg(format!(
+                "Failed to clean account({}) states, error: {}",
+                account.account_id, error
+            ))
+        })?;
+        let remaining = remaining.map_err(|error| {
+            color_eyre::Report::msg(format!(
+                "Failed to list account({}) states, error: {}",
+                account.account_id, error
+            ))
+        })?;
+        self.record_reclaim(account, &before, keys.len(), remaining.len())
+            .await?;
+        if !remaining.is_empty() {
+            return Err(color_eyre::Report::msg(format!(
+                "Account({}) has {} selected keys left after clean up",
+                account.account_id,
+                remaining.len()
+            )));
+        }
+        Ok(())
+    }
+
+    async fn view_account(
+        &self,
+        account: &InMemorySigner,
+    ) -> color_eyre::eyre::Result<AccountView> {
+        self.client
+            .view_account(account.account_id.clone(), None)
+            .await
+            .map_err(|error| {
+                color_eyre::Report::msg(format!(
+                    "Failed to view account({}), error: {}",
+                    account.account_id, error
+                ))
+            })
+    }
+
+    /// Compares the account with `before` for the storage report.
+    async fn record_reclaim(
+        &self,
+        account: &InMemorySigner,
+        before: &AccountView,
+        keys_listed: usize,
+        keys_left: usize,
+    ) -> CliResult {
+        let after = self.view_account(account).await?;
+        self.reclaims
+            .lock()
+            .expect("reclaims are not poisoned")
+            .push(StorageReclaim {
+                account_id: account.account_id.clone(),
+                storage_usage_before: before.storage_usage,
+                storage_usage_after: after.storage_usage,
+                keys_removed: keys_listed.saturating_sub(keys_left),
+                keys_left,
+                code_hash: after.code_hash,
+                storage_amount_per_byte: self.storage_amount_per_byte,
+            });
+        Ok(())
+    }
+
+    async fn is_running_clean_contract(
+        &self,
+        clean_contract: &CleanStateContract<'_>,
+        account: &InMemorySigner,
+    ) -> color_eyre::eyre::Result<bool> {
+        clean_contract.is_deployed().await.map_err(|error| {
+            color_eyre::Report::msg(format!(
+                "Failed to view code of account({}), error: {}",
+                account.account_id, error
+            ))
+        })
+    }
+
+    async fn take_snapshot(
+        &self,
+        account: &InMemorySigner,
+    ) -> color_eyre::eyre::Result<(ContractSnapshot, PathBuf)> {
+        let snapshot = ContractSnapshot::take(self.client, account.account_id.clone())
+            .await
+            .map_err(|error| {
+                color_eyre::Report::msg(format!(
+                    "Failed to take snapshot of account({}), error: {}",
+                    account.account_id, error
+                ))
+            })?;
+        let snapshot_path = snapshot.save(self.snapshot_dir).map_err(|error| {
+            color_eyre::Report::msg(format!(
+                "Failed to save snapshot of account({}), error: {}",
+                account.account_id, error
+            ))
+        })?;
+        println!("Saved snapshot to {}", snapshot_path.display());
+        confirm_incomplete_snapshot(&snapshot, &snapshot_path)?;
+        Ok((snapshot, snapshot_path))
+    }
+
+    /// Loads the snapshot taken when an interrupted clean up started.
+    fn load_snapshot_before_clean(
+        &self,
+        account: &InMemorySigner,
+    ) -> color_eyre::eyre::Result<(ContractSnapshot, PathBuf)> {
+        let snapshot_path = find_latest_snapshot(self.snapshot_dir, &account.account_id)
+            .map_err(|error| {
+                color_eyre::Report::msg(format!(
+                    "Failed to find snapshot of account({}), error: {}",
+                    account.account_id, error
+                ))
+            })?
+            .ok_or_else(|| {
+                color_eyre::Report::msg(format!(
+                    "No snapshot of account({}) in {} to redeploy the original code from",
+                    account.account_id,
+                    self.snapshot_dir.display()
+                ))
+            })?;
+        let snapshot = ContractSnapshot::load(&snapshot_path).map_err(|error| {
+            color_eyre::Report::msg(format!(
+                "Failed to load snapshot {}, error: {}",
+                snapshot_path.display(),
+                error
+            ))
+        })?;
+        if snapshot.code_hash == CryptoHash::hash_bytes(STATE_CLEANUP_WASM) {
+            return Err(color_eyre::Report::msg(format!(
+                "The latest snapshot {} already has the clean state contract, deploy the original code with --after-clean <wasm path>",
+                snapshot_path.display()
+            )));
+        }
+        println!(
+            "Original code of {} is taken from {}",
+            account.account_id,
+            snapshot_path.display()
+        );
+        Ok((snapshot, snapshot_path))
+    }
+
+    async fn deploy_clean_contract(
+        &self,
+        clean_contract: &CleanStateContract<'_>,
+        account: &InMemorySigner,
+        outcome: &mut AccountOutcome,
+    ) -> CliResult {
+        let deploy_outcome = clean_contract.deploy(account).await.map_err(|error| {
+            color_eyre::Report::msg(format!(
+                "Failed to deploy clean state contract for account({}), error: {}",
+                account.account_id, error
+            ))
+        })?;
+        outcome.record_transaction(&deploy_outcome);
+        Ok(())
+    }
+
+    /// Deploys the code chosen by `--after-clean`, with the init call if there is one.
+    async fn deploy_after_clean(
+        &self,
+        account: &InMemorySigner,
+        snapshot: Option<&(ContractSnapshot, PathBuf)>,
+        outcome: &mut AccountOutcome,
+    ) -> CliResult {
+        let code = match (&self.after_clean, snapshot) {
+            (AfterClean::Keep, _) => {
+                println!(
+                    "{}",
+                    format!(
+                        "Account({}) keeps running the clean state contract",
+                        account.account_id
+                    )
+                    .yellow()
+                );
+                return Ok(());
+            }
+            (AfterClean::Wasm(code), _) => code.clone(),
+            (AfterClean::Original, Some((snapshot, snapshot_path))) => {
+                let code = snapshot.code_bytes().map_err(|error| {
                     color_eyre::Report::msg(format!(
-                        "Failed to deploy clean state contract for account({}), error: {}",
-                        account.account_id, error
+                        "Failed to decode code in snapshot {}, error: {}",
+                        snapshot_path.display(),
+                        error
                     ))
                 })?;
+                if code.is_empty() {
+                    println!(
+                        "{}",
+                        format!(
+                            "Account({}) had no code before cleaning, it keeps running the clean state contract",
+                            account.account_id
+                        )
+                        .yellow()
+                    );
+                    return Ok(());
+                }
+                code
+            }
+            (AfterClean::Original, None) => {
+                return Err(color_eyre::Report::msg(format!(
+                    "No snapshot of account({}) to redeploy the original code from",
+                    account.account_id
+                )))
+            }
+        };
 
-                clean_contract
-                    .clean_up_all(&account)
+        let code_hash = CryptoHash::hash_bytes(&code);
+        let deploy_outcome = match &self.init_call {
+            Some((method_name, args)) => {
+                self.client
+                    .deploy_and_init(
+                        account,
+                        code,
+                        method_name.clone(),
+                        args.clone().into_bytes(),
+                        DEFAULT_DEPLOY_AND_INIT_GAS,
+                    )
                     .await
-                    .map_err(|error| {
-                        color_eyre::Report::msg(format!(
-                            "Failed to clean account({}) states, error: {}",
-                            account.account_id, error
-                        ))
-                    })?;
-
-                let result = client
-                    .view_state(account.account_id.clone(), None, None)
-                    .await
-                    .map_err(|error| {
-                        color_eyre::Report::msg(format!(
-                            "Failed to view account({}) states, error: {}",
-                            account.account_id, error
-                        ))
-                    })?;
-                println!("Show account state after clean up: {:?}", result);
-
-                println!("---End clean {} states\n", account.account_id);
             }
-        } else {
-            println!("Cancel clean state!");
+            None => self.client.deploy(account, code).await,
         }
-
+        .map_err(|error| {
+            color_eyre::Report::msg(format!(
+                "Failed to deploy code after cleaning account({}), it still runs the clean state contract, error: {}",
+                account.account_id, error
+            ))
+        })?;
+        outcome.record_transaction(&deploy_outcome);
+        if CallExecutionDetails::from(deploy_outcome).is_failure() {
+            return Err(color_eyre::Report::msg(format!(
+                "The deploy after cleaning account({}) failed, it still runs the clean state contract",
+                account.account_id
+            )));
+        }
+        println!(
+            "Deployed code {} to {} after cleaning",
+            code_hash, account.account_id
+        );
         Ok(())
     }
 }
@@ -6,6 +6,7 @@ pub mod clean;
 pub mod custom_directory;
 pub mod default_directory;
 pub mod manual_select_accounts;
+pub mod registry_anchors;
 
 #[derive(Debug, Clone, EnumDiscriminants, interactive_clap_derive::InteractiveClap)]
 #[strum_discriminants(derive(EnumMessage, EnumIter))]
@@ -20,6 +21,10 @@ pub enum SelectAccounts {
     DefaultDirectory(self::default_directory::DefaultDirectory),
     #[strum_discriminants(strum(message = "Select all accounts located in custom directory."))]
     CustomDirectory(self::custom_directory::CustomDirectory),
+    #[strum_discriminants(strum(
+        message = "Select anchors of all appchains in a registry, filtered by appchain state and code hash"
+    ))]
+    RegistryAnchors(self::registry_anchors::RegistryAnchors),
 }
 
 impl SelectAccounts {
@@ -36,6 +41,9 @@ impl SelectAccounts {
             SelectAccounts::CustomDirectory(custom_directory) => {
                 custom_directory.process(connection_config, client).await
             }
+            SelectAccounts::RegistryAnchors(registry_anchors) => {
+                registry_anchors.process(connection_config, client).await
+            }
         }
     }
 }
@@ -0,0 +1,80 @@
+use crate::near::rpc::client::Client;
+use crate::near::types::NearEnv;
+use crate::near::util::{get_accounts_from_path, get_default_near_account_dir_path};
+use crate::oct::anchor_selector::{match_credentials, select_anchors_of_registry};
+use crate::CliResult;
+use dialoguer::Input;
+use itertools::Itertools;
+
+#[derive(Debug, Clone, interactive_clap_derive::InteractiveClap)]
+#[interactive_clap(context = ())]
+pub struct RegistryAnchors {
+    #[interactive_clap(long)]
+    pub registry_account: String,
+    #[interactive_clap(long)]
+    pub appchain_state: String,
+    #[interactive_clap(long)]
+    pub code_hash: String,
+    #[interactive_clap(named_arg)]
+    pub clean_state: super::clean::CleanState,
+}
+
+impl RegistryAnchors {
+    pub fn input_registry_account(_context: &()) -> color_eyre::eyre::Result<String> {
+        Ok(Input::new()
+            .with_prompt("What is the registry account?")
+            .interact_text()?)
+    }
+
+    pub fn input_appchain_state(_context: &()) -> color_eyre::eyre::Result<String> {
+        Ok(Input::new()
+            .with_prompt(
+                "Filter anchors by appchain states split by ','(eg: Active,Frozen), or 'all'",
+            )
+            .default("all".to_string())
+            .interact_text()?)
+    }
+
+    pub fn input_code_hash(_context: &()) -> color_eyre::eyre::Result<String> {
+        Ok(Input::new()
+            .with_prompt("Filter anchors by current code hash, or 'any'")
+            .default("any".to_string())
+            .interact_text()?)
+    }
+
+    pub async fn process(self, connection_config: NearEnv, client: Client) -> CliResult {
+        let anchor_ids = select_anchors_of_registry(
+            &client,
+            self.registry_account.parse()?,
+            &self.appchain_state,
+            &self.code_hash,
+        )
+        .await
+        .map_err(|err| {
+            color_eyre::Report::msg(format!(
+                "Failed to select anchors of registry {}, error: {}",
+                self.registry_account, err
+            ))
+        })?;
+
+        let buf = get_default_near_account_dir_path(&connection_config);
+        let (signers, missing) =
+            match_credentials(anchor_ids, get_accounts_from_path(buf.as_path())?);
+        if !missing.is_empty() {
+            println!(
+                "Skip these anchors, their credentials are not found in {}:\n[{}]",
+                buf.display(),
+                missing.iter().join("\n")
+            );
+        }
+
+        println!("Use these account to clean state:");
+        println!(
+            "[{}]",
+            signers.iter().map(|e| e.account_id.to_string()).join("\n")
+        );
+        self.clean_state
+            .process(connection_config, signers, client)
+            .await
+    }
+}
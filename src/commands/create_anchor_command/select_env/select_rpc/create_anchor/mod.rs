@@ -0,0 +1,223 @@
+use crate::near::rpc::client::{is_unknown_account_error, Client};
+use crate::near::rpc::result::CallExecutionDetails;
+use crate::near::types::{NearAccountWithKey, NearBalance, NearEnv};
+use crate::near::util::{
+    get_accounts_from_path, get_default_near_account_dir_path, print_transaction_status,
+};
+use crate::near::wasm::pre_check_deploy;
+use crate::oct::contracts::anchor::AnchorContract;
+use crate::oct::contracts::registry::RegistryContract;
+use crate::CliResult;
+use dialoguer::Input;
+use near_crypto::{InMemorySigner, KeyType, SecretKey};
+use near_primitives::types::AccountId;
+use std::path::Path;
+use std::str::FromStr;
+
+pub const DEFAULT_ANCHOR_ACCOUNT: &str = "default";
+
+#[derive(Debug, Clone, interactive_clap_derive::InteractiveClap)]
+#[interactive_clap(context = ())]
+pub struct CreateAnchor {
+    #[interactive_clap(long)]
+    pub appchain_id: String,
+    #[interactive_clap(long)]
+    pub registry_account: String,
+    #[interactive_clap(long)]
+    pub anchor_account: String,
+    #[interactive_clap(long)]
+    pub initial_balance: String,
+    #[interactive_clap(long)]
+    pub wasm_path: String,
+    confirm: String,
+}
+
+impl CreateAnchor {
+    pub fn input_appchain_id(_context: &()) -> color_eyre::eyre::Result<String> {
+        Ok(Input::new()
+            .with_prompt("What is the appchain id?")
+            .interact_text()?)
+    }
+
+    pub fn input_registry_account(_context: &()) -> color_eyre::eyre::Result<String> {
+        Ok(Input::new()
+            .with_prompt("What is the registry account?")
+            .interact_text()?)
+    }
+
+    pub fn input_anchor_account(_context: &()) -> color_eyre::eyre::Result<String> {
+        Ok(Input::new()
+            .with_prompt("What is the anchor account, or 'default' for <appchain_id>.<registry>?")
+            .default(DEFAULT_ANCHOR_ACCOUNT.to_string())
+            .interact_text()?)
+    }
+
+    pub fn input_initial_balance(_context: &()) -> color_eyre::eyre::Result<String> {
+        Ok(Input::new()
+            .with_prompt("How much NEAR to fund the anchor account with?")
+            .interact_text()?)
+    }
+
+    pub fn input_wasm_path(_context: &()) -> color_eyre::eyre::Result<String> {
+        Ok(Input::new()
+            .with_prompt("What is the anchor wasm path?")
+            .interact_text()?)
+    }
+
+    fn input_confirm(_context: &()) -> color_eyre::eyre::Result<String> {
+        Ok(Input::new()
+            .with_prompt("Please confirm you want to create the anchor?[y/n]")
+            .interact_text()?)
+    }
+
+    pub async fn process(self, connection_config: NearEnv, client: Client) -> CliResult {
+        let registry_account_id: AccountId = self.registry_account.parse()?;
+        let anchor_account_id: AccountId = if self
+            .anchor_account
+            .eq_ignore_ascii_case(DEFAULT_ANCHOR_ACCOUNT)
+        {
+            RegistryContract::new(registry_account_id.clone(), &client)
+                .anchor_account_id_of(&self.appchain_id)
+                .map_err(|err| color_eyre::Report::msg(err.to_string()))?
+        } else {
+            self.anchor_account.parse()?
+        };
+        let initial_balance =
+            NearBalance::from_str(&self.initial_balance).map_err(color_eyre::Report::msg)?;
+        let oct_token = connection_config.oct_token_account_id();
+
+        // Only the parent account can create a sub-account.
+        let parent_account_id = anchor_account_id
+            .as_str()
+            .split_once('.')
+            .map(|(_, parent)| parent.to_string())
+            .ok_or_else(|| {
+                color_eyre::Report::msg(format!(
+                    "{} is a top-level account, it can not be created by this command",
+                    anchor_account_id
+                ))
+            })?;
+        let credentials_dir = get_default_near_account_dir_path(&connection_config);
+        let creator: InMemorySigner = get_accounts_from_path(credentials_dir.as_path())?
+            .into_iter()
+            .find(|e| e.account_id.as_str() == parent_account_id)
+            .ok_or_else(|| {
+                color_eyre::Report::msg(format!(
+                    "Failed to find account {} in your system.",
+                    parent_account_id
+                ))
+            })?
+            .into();
+
+        match client.view_account(anchor_account_id.clone(), None).await {
+            Ok(_) => {
+                return Err(color_eyre::Report::msg(format!(
+                    "Account {} already exists",
+                    anchor_account_id
+                )))
+            }
+            Err(err) if is_unknown_account_error(&err) => {}
+            Err(err) => {
+                return Err(color_eyre::Report::msg(format!(
+                    "Failed to check whether account {} exists, error: {}",
+                    anchor_account_id, err
+                )))
+            }
+        }
+        let code = std::fs::read(Path::new(self.wasm_path.as_str())).map_err(|err| {
+            color_eyre::Report::msg(format!(
+                "Failed to read wasm file {}, error: {}",
+                self.wasm_path, err
+            ))
+        })?;
+        pre_check_deploy(&client, &code, "new")
+            .await
+            .map_err(|err| {
+                color_eyre::Report::msg(format!(
+                    "Wasm pre-check of {} failed, error: {}",
+                    self.wasm_path, err
+                ))
+            })?;
+
+        println!(
+            "Create anchor {} by {} with {}, deploy {} and init with appchain id {}, registry {}, oct token {}",
+            anchor_account_id,
+            creator.account_id,
+            initial_balance,
+            self.wasm_path,
+            self.appchain_id,
+            registry_account_id,
+            oct_token
+        );
+        if !(self.confirm.eq("y") || self.confirm.eq("Y")) {
+            println!("Cancel create anchor!");
+            return Ok(());
+        }
+
+        let secret_key = SecretKey::from_random(KeyType::ED25519);
+        let new_account = NearAccountWithKey {
+            account_id: anchor_account_id.clone(),
+            public_key: secret_key.public_key(),
+            private_key: secret_key,
+        };
+        // The key is saved before the account is created so it can not get lost.
+        let credentials_path = new_account.save(credentials_dir.as_path()).map_err(|err| {
+            color_eyre::Report::msg(format!(
+                "Failed to save credentials of {}, error: {}",
+                anchor_account_id, err
+            ))
+        })?;
+        println!("Saved credentials to {}", credentials_path.display());
+
+        let outcome = client
+            .create_account_and_deploy(
+                &creator,
+                &anchor_account_id,
+                new_account.public_key.clone(),
+                initial_balance.to_yoctonear(),
+                code,
+            )
+            .await
+            .map_err(|err| {
+                color_eyre::Report::msg(format!(
+                    "Failed to create account {}, error: {}",
+                    anchor_account_id, err
+                ))
+            })?;
+        let is_failure = CallExecutionDetails::from(outcome.clone()).is_failure();
+        print_transaction_status(outcome, connection_config.clone());
+        if is_failure {
+            let _ = std::fs::remove_file(&credentials_path);
+            return Err(color_eyre::Report::msg(format!(
+                "Failed to create account {}, the saved credentials are removed",
+                anchor_account_id
+            )));
+        }
+
+        let anchor_signer: InMemorySigner = new_account.into();
+        let outcome = AnchorContract::new(anchor_account_id.clone(), &client)
+            .init(
+                &anchor_signer,
+                &self.appchain_id,
+                &registry_account_id,
+                &oct_token,
+            )
+            .await
+            .map_err(|err| {
+                color_eyre::Report::msg(format!(
+                    "Failed to init anchor {}, error: {}",
+                    anchor_account_id, err
+                ))
+            })?;
+        let is_failure = CallExecutionDetails::from(outcome.clone()).is_failure();
+        print_transaction_status(outcome, connection_config);
+        if is_failure {
+            return Err(color_eyre::Report::msg(format!(
+                "Account {} is created but `new` failed, fix the issue and init it again",
+                anchor_account_id
+            )));
+        }
+        println!("Anchor {} is created.", anchor_account_id);
+        Ok(())
+    }
+}
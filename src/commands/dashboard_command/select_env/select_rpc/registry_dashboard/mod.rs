@@ -0,0 +1,53 @@
+use crate::near::batch::parse_concurrency;
+use crate::near::rpc::client::Client;
+use crate::near::types::NearEnv;
+use crate::oct::dashboard::run_dashboard;
+use crate::oct::monitor::parse_duration;
+use crate::CliResult;
+use dialoguer::Input;
+use near_primitives::types::AccountId;
+
+#[derive(Debug, Clone, interactive_clap_derive::InteractiveClap)]
+#[interactive_clap(context = ())]
+pub struct RegistryDashboard {
+    pub registry_account: String,
+    #[interactive_clap(long)]
+    pub interval: String,
+    #[interactive_clap(long)]
+    pub concurrency: String,
+}
+
+impl RegistryDashboard {
+    pub fn input_registry_account(_context: &()) -> color_eyre::eyre::Result<String> {
+        Ok(Input::new()
+            .with_prompt("What is the registry account?")
+            .interact_text()?)
+    }
+
+    pub fn input_interval(_context: &()) -> color_eyre::eyre::Result<String> {
+        Ok(Input::new()
+            .with_prompt("How often to refresh the dashboard (eg: 30s, 1m)?")
+            .default("30s".to_string())
+            .interact_text()?)
+    }
+
+    pub fn input_concurrency(_context: &()) -> color_eyre::eyre::Result<String> {
+        Ok(Input::new()
+            .with_prompt("How many requests to send at the same time?")
+            .default("8".to_string())
+            .interact_text()?)
+    }
+
+    pub async fn process(self, _connection_config: NearEnv, client: Client) -> CliResult {
+        let concurrency = parse_concurrency(&self.concurrency)?;
+        let interval = parse_duration(&self.interval)
+            .map_err(|err| color_eyre::Report::msg(err.to_string()))?;
+        if interval.as_secs() == 0 {
+            return Err(color_eyre::Report::msg("The interval must be at least 1s"));
+        }
+        let registry_account_id: AccountId = self.registry_account.parse()?;
+        run_dashboard(&client, &registry_account_id, interval, concurrency)
+            .await
+            .map_err(|err| color_eyre::Report::msg(format!("Dashboard failed, error: {}", err)))
+    }
+}
@@ -0,0 +1,119 @@
+use crate::near::batch::parse_concurrency;
+use crate::near::rpc::client::Client;
+use crate::near::types::NearEnv;
+use crate::oct::portfolio::{fetch_delegations, print_delegator_portfolio};
+use crate::oct::scan::{active_anchors_of_registry, AppchainScanResult};
+use crate::CliResult;
+use color_eyre::owo_colors::OwoColorize;
+use dialoguer::Input;
+use near_primitives::types::AccountId;
+
+#[derive(Debug, Clone, interactive_clap_derive::InteractiveClap)]
+#[interactive_clap(context = ())]
+pub struct DelegatorPortfolio {
+    pub registry_account: String,
+    #[interactive_clap(long)]
+    pub delegator: String,
+    #[interactive_clap(long)]
+    pub concurrency: String,
+}
+
+impl DelegatorPortfolio {
+    pub fn input_registry_account(_context: &()) -> color_eyre::eyre::Result<String> {
+        Ok(Input::new()
+            .with_prompt("What is the registry account?")
+            .interact_text()?)
+    }
+
+    pub fn input_delegator(_context: &()) -> color_eyre::eyre::Result<String> {
+        Ok(Input::new()
+            .with_prompt("Which delegator account?")
+            .interact_text()?)
+    }
+
+    pub fn input_concurrency(_context: &()) -> color_eyre::eyre::Result<String> {
+        Ok(Input::new()
+            .with_prompt("How many requests to send at the same time?")
+            .default("8".to_string())
+            .interact_text()?)
+    }
+
+    pub async fn process(self, _connection_config: NearEnv, client: Client) -> CliResult {
+        let concurrency = parse_concurrency(&self.concurrency)?;
+        let delegator_id: AccountId = self.delegator.parse().map_err(|err| {
+            color_eyre::Report::msg(format!(
+                "Invalid delegator account id {}, error: {}",
+                self.delegator, err
+            ))
+        })?;
+        let anchors =
+            active_anchors_of_registry(&client, self.registry_account.parse()?, &[], concurrency)
+                .await
+                .map_err(|err| color_eyre::Report::msg(err.to_string()))?;
+        let results = fetch_delegations(&client, &delegator_id, anchors, concurrency)
+            .await
+            .map_err(|err| color_eyre::Report::msg(err.to_string()))?;
+
+        let mut portfolio = vec![];
+        let mut failed = 0;
+        for result in results.iter() {
+            if let Ok(delegations) = result {
+                if delegations.searched_from_era > delegations.start_era {
+                    println!(
+                        "{}",
+                        format!(
+                            "{} keeps validator sets from era {}, delegations to validators which left before are not found, their rewards from era {} may be missing.",
+                            delegations.anchor.anchor_account_id,
+                            delegations.searched_from_era,
+                            delegations.start_era
+                        )
+                        .yellow()
+                    );
+                }
+            }
+            match result {
+                Ok(delegations) if !delegations.delegations.is_empty() => {
+                    portfolio.push(delegations.clone())
+                }
+                Ok(_) => {}
+                Err(AppchainScanResult::Skipped {
+                    anchor_account_id,
+                    appchain_state,
+                    ..
+                }) => println!(
+                    "The state of {} is {:?}, skip delegations!",
+                    anchor_account_id.blue(),
+                    appchain_state.yellow()
+                ),
+                Err(AppchainScanResult::Failed { appchain_id, error }) => {
+                    failed += 1;
+                    println!(
+                        "{}",
+                        format!("Failed to read delegations in {}: {}", appchain_id, error).red()
+                    );
+                }
+                Err(AppchainScanResult::Scanned(_)) => {}
+            }
+        }
+        if portfolio.is_empty() {
+            println!(
+                "{}",
+                format!(
+                    "No delegation of {} with rewards which have not expired.",
+                    delegator_id
+                )
+                .yellow()
+            );
+        } else {
+            print_delegator_portfolio(&delegator_id, &portfolio);
+        }
+        if failed > 0 {
+            return Err(color_eyre::Report::msg(format!(
+                "Failed to read delegations in {} of {} appchains",
+                failed,
+                results.len()
+            )));
+        }
+        Ok(())
+    }
+}
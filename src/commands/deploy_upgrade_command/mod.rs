@@ -1,3 +1,4 @@
+pub mod plan;
 pub mod select_env;
 
 #[derive(Debug, Clone, interactive_clap_derive::InteractiveClap)]
@@ -0,0 +1,36 @@
+use strum::{EnumDiscriminants, EnumIter, EnumMessage};
+
+pub mod plan_file;
+pub mod run;
+
+#[derive(Debug, Clone, interactive_clap_derive::InteractiveClap)]
+#[interactive_clap(context = ())]
+pub struct Plan {
+    #[interactive_clap(subcommand)]
+    action: PlanAction,
+}
+
+impl Plan {
+    pub async fn process(self) -> crate::CliResult {
+        self.action.process().await
+    }
+}
+
+#[derive(Debug, Clone, EnumDiscriminants, interactive_clap_derive::InteractiveClap)]
+#[strum_discriminants(derive(EnumMessage, EnumIter))]
+#[interactive_clap(context = ())]
+///Choose plan action
+pub enum PlanAction {
+    #[strum_discriminants(strum(
+        message = "Run an upgrade plan, resume it if it was interrupted"
+    ))]
+    Run(self::run::Run),
+}
+
+impl PlanAction {
+    pub async fn process(self) -> crate::CliResult {
+        match self {
+            PlanAction::Run(run) => run.process().await,
+        }
+    }
+}
@@ -0,0 +1,189 @@
+use crate::near::args_template::ArgsTemplate;
+use crate::near::gas::NearGas;
+use crate::near::rpc::client::DEFAULT_DEPLOY_AND_INIT_GAS;
+use crate::near::rpc::rpc_provider::RpcProvider;
+use crate::near::types::NearEnv;
+use crate::near::view_check::ViewCheck;
+use near_primitives::hash::CryptoHash;
+use near_primitives::types::{AccountId, Gas};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// An ordered list of upgrade steps, loaded from a TOML file.
+#[derive(Debug, Clone, Deserialize)]
+pub struct UpgradePlan {
+    pub network: String,
+    #[serde(default = "default_rpc")]
+    pub rpc: String,
+    pub steps: Vec<PlanStep>,
+}
+
+fn default_rpc() -> String {
+    "block-pi".to_string()
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PlanStep {
+    pub name: String,
+    pub accounts: Vec<AccountId>,
+    pub wasm_path: String,
+    /// Hex encoded sha256 of the wasm file.
+    pub sha256: String,
+    pub migrate_method: String,
+    /// Migration args, placeholders such as `{{account_id}}` are rendered for every account.
+    #[serde(default = "default_args")]
+    pub args: String,
+    /// Read the args from this file instead of `args`.
+    pub args_file: Option<String>,
+    pub gas: Option<String>,
+    #[serde(default)]
+    pub pre_checks: Vec<ViewCheck>,
+    #[serde(default)]
+    pub post_checks: Vec<ViewCheck>,
+}
+
+fn default_args() -> String {
+    "{}".to_string()
+}
+
+impl UpgradePlan {
+    pub fn load(path: &Path) -> anyhow::Result<(Self, CryptoHash)> {
+        let content = std::fs::read(path)?;
+        let plan: Self = toml::from_slice(&content)?;
+        plan.near_env()?;
+        plan.rpc_provider()?;
+        for step in &plan.steps {
+            step.gas()?;
+            step.args_template()?;
+        }
+        Ok((plan, CryptoHash::hash_bytes(&content)))
+    }
+
+    pub fn near_env(&self) -> anyhow::Result<NearEnv> {
+        match self.network.as_str() {
+            "testnet" => Ok(NearEnv::Testnet),
+            "mainnet" => Ok(NearEnv::Mainnet),
+            network => anyhow::bail!("Unknown network '{}' in plan file", network),
+        }
+    }
+
+    pub fn rpc_provider(&self) -> anyhow::Result<RpcProvider> {
+        match self.rpc.as_str() {
+            "near-official" => Ok(RpcProvider::NearOfficial),
+            "block-pi" => Ok(RpcProvider::BlockPi),
+            rpc => anyhow::bail!("Unknown rpc '{}' in plan file", rpc),
+        }
+    }
+}
+
+impl PlanStep {
+    pub fn gas(&self) -> anyhow::Result<Gas> {
+        match &self.gas {
+            Some(gas) => Ok(NearGas::from_str(gas)
+                .map_err(|err| anyhow::anyhow!("Invalid gas of step '{}': {}", self.name, err))?
+                .inner),
+            None => Ok(DEFAULT_DEPLOY_AND_INIT_GAS),
+        }
+    }
+
+    pub fn args_template(&self) -> anyhow::Result<ArgsTemplate> {
+        match &self.args_file {
+            Some(args_file) => ArgsTemplate::from_file(Path::new(args_file)),
+            None => ArgsTemplate::parse(&self.args),
+        }
+        .map_err(|err| anyhow::anyhow!("Invalid args of step '{}': {}", self.name, err))
+    }
+
+    /// Reads the wasm of this step and makes sure it matches the expected sha256.
+    pub fn read_wasm(&self) -> anyhow::Result<Vec<u8>> {
+        let code = std::fs::read(&self.wasm_path)?;
+        let sha256 = hex::encode(CryptoHash::hash_bytes(&code).0);
+        if !sha256.eq_ignore_ascii_case(&self.sha256) {
+            anyhow::bail!(
+                "The sha256 of {} is {}, but step '{}' expects {}",
+                self.wasm_path,
+                sha256,
+                self.name,
+                self.sha256
+            );
+        }
+        Ok(code)
+    }
+}
+
+/// Progress of a plan run, saved next to the plan file so an interrupted run can resume.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct PlanState {
+    pub plan_hash: Option<CryptoHash>,
+    /// Step index -> accounts which have been upgraded in that step.
+    pub completed_accounts: BTreeMap<usize, BTreeSet<AccountId>>,
+}
+
+impl PlanState {
+    pub fn path_of(plan_path: &Path) -> PathBuf {
+        let mut path = plan_path.as_os_str().to_owned();
+        path.push(".state.json");
+        PathBuf::from(path)
+    }
+
+    pub fn load_or_default(path: &Path) -> anyhow::Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        Ok(std::fs::write(path, serde_json::to_string_pretty(self)?)?)
+    }
+
+    pub fn is_completed(&self, step_index: usize, account_id: &AccountId) -> bool {
+        matches!(
+            self.completed_accounts.get(&step_index),
+            Some(accounts) if accounts.contains(account_id)
+        )
+    }
+
+    pub fn mark_completed(&mut self, step_index: usize, account_id: AccountId) {
+        self.completed_accounts
+            .entry(step_index)
+            .or_default()
+            .insert(account_id);
+    }
+}
+
+#[test]
+fn test_parse_plan() {
+    let plan: UpgradePlan = toml::from_str(
+        r#"
+network = "testnet"
+
+[[steps]]
+name = "anchor v2.1.0"
+accounts = ["anchorxsb.testnet"]
+wasm_path = "res/appchain_anchor_v2.1.0.wasm"
+sha256 = "00"
+migrate_method = "migrate_state"
+gas = "250 TGas"
+
+[[steps.post_checks]]
+method = "get_anchor_version"
+expected = "v2.1.0"
+"#,
+    )
+    .unwrap();
+
+    assert_eq!(plan.rpc, "block-pi");
+    assert_eq!(plan.steps[0].args, "{}");
+    assert_eq!(
+        plan.steps[0].gas().unwrap(),
+        250 * crate::near::constants::ONE_TERA_GAS
+    );
+    assert_eq!(
+        plan.steps[0].post_checks[0].expected,
+        serde_json::json!("v2.1.0")
+    );
+}
@@ -0,0 +1,225 @@
+use super::plan_file::{PlanState, PlanStep, UpgradePlan};
+use crate::near::args_template::ArgsTemplate;
+use crate::near::rpc::client::Client;
+use crate::near::rpc::result::CallExecutionDetails;
+use crate::near::snapshot::take_and_save_snapshot;
+use crate::near::storage::{gas_reserve, storage_amount_per_byte, StorageCost};
+use crate::near::types::NearEnv;
+use crate::near::util::{
+    get_accounts_from_path, get_default_near_account_dir_path, get_default_snapshot_dir_path,
+    print_transaction_status,
+};
+use crate::near::wasm::pre_check_deploy;
+use crate::CliResult;
+use dialoguer::Input;
+use near_crypto::InMemorySigner;
+use near_primitives::hash::CryptoHash;
+use near_primitives::types::{AccountId, Gas};
+use std::collections::HashMap;
+use std::path::Path;
+
+#[derive(Debug, Clone, interactive_clap_derive::InteractiveClap)]
+#[interactive_clap(context = ())]
+pub struct Run {
+    #[interactive_clap(long)]
+    pub plan_file: String,
+}
+
+impl Run {
+    pub fn input_plan_file(_context: &()) -> color_eyre::eyre::Result<String> {
+        Ok(Input::new()
+            .with_prompt("What is the upgrade plan file path?")
+            .interact_text()?)
+    }
+
+    pub async fn process(self) -> CliResult {
+        let plan_path = Path::new(self.plan_file.as_str());
+        let (plan, plan_hash) = UpgradePlan::load(plan_path).map_err(|err| {
+            color_eyre::Report::msg(format!(
+                "Failed to load plan file {}, error: {}",
+                self.plan_file, err
+            ))
+        })?;
+        let connection_config = plan
+            .near_env()
+            .map_err(|err| color_eyre::Report::msg(err.to_string()))?;
+        let client = Client::new(
+            plan.rpc_provider()
+                .map_err(|err| color_eyre::Report::msg(err.to_string()))?
+                .get_rpc_by_env(&connection_config),
+        );
+
+        let state_path = PlanState::path_of(plan_path);
+        let mut state = PlanState::load_or_default(&state_path).map_err(|err| {
+            color_eyre::Report::msg(format!(
+                "Failed to load plan state file {}, error: {}",
+                state_path.display(),
+                err
+            ))
+        })?;
+        match state.plan_hash {
+            Some(hash) if hash != plan_hash => {
+                return Err(color_eyre::Report::msg(format!(
+                    "The plan file has changed since the last run, remove {} to start over.",
+                    state_path.display()
+                )));
+            }
+            Some(_) => println!("Resume plan from {}", state_path.display()),
+            None => state.plan_hash = Some(plan_hash),
+        }
+
+        let signers: HashMap<AccountId, InMemorySigner> = get_accounts_from_path(
+            get_default_near_account_dir_path(&connection_config).as_path(),
+        )?
+        .into_iter()
+        .map(|e| (e.account_id.clone(), e.into()))
+        .collect();
+
+        for (index, step) in plan.steps.iter().enumerate() {
+            if step
+                .accounts
+                .iter()
+                .all(|account_id| state.is_completed(index, account_id))
+            {
+                println!("---Step {} '{}' is already completed\n", index, step.name);
+                continue;
+            }
+            println!("---Start step {} '{}'", index, step.name);
+            let code = step.read_wasm().map_err(|err| {
+                color_eyre::Report::msg(format!("Step {} '{}': {}", index, step.name, err))
+            })?;
+            pre_check_deploy(&client, &code, &step.migrate_method)
+                .await
+                .map_err(|err| {
+                    color_eyre::Report::msg(format!(
+                        "Step {} '{}': wasm pre-check failed, error: {}",
+                        index, step.name, err
+                    ))
+                })?;
+            let args_template = step
+                .args_template()
+                .map_err(|err| color_eyre::Report::msg(err.to_string()))?;
+            let gas = step
+                .gas()
+                .map_err(|err| color_eyre::Report::msg(err.to_string()))?;
+
+            for account_id in &step.accounts {
+                if state.is_completed(index, account_id) {
+                    println!("{} is already upgraded in this step", account_id);
+                    continue;
+                }
+                let signer = signers.get(account_id).ok_or_else(|| {
+                    color_eyre::Report::msg(format!(
+                        "Failed to find account {} in your system.",
+                        account_id
+                    ))
+                })?;
+                run_step_for_account(
+                    &client,
+                    connection_config.clone(),
+                    step,
+                    signer,
+                    &code,
+                    &args_template,
+                    gas,
+                )
+                .await
+                .map_err(|err| {
+                    color_eyre::Report::msg(format!(
+                        "Step {} '{}' failed on {}, error: {}\nFix the issue and run the plan again to resume.",
+                        index, step.name, account_id, err
+                    ))
+                })?;
+
+                state.mark_completed(index, account_id.clone());
+                state.save(&state_path).map_err(|err| {
+                    color_eyre::Report::msg(format!(
+                        "Failed to save plan state file {}, error: {}",
+                        state_path.display(),
+                        err
+                    ))
+                })?;
+            }
+            println!("---End step {} '{}'\n", index, step.name);
+        }
+        println!("All {} steps of the plan are completed.", plan.steps.len());
+        Ok(())
+    }
+}
+
+async fn run_step_for_account(
+    client: &Client,
+    connection_config: NearEnv,
+    step: &PlanStep,
+    signer: &InMemorySigner,
+    code: &[u8],
+    args_template: &ArgsTemplate,
+    gas: Gas,
+) -> anyhow::Result<()> {
+    let target_code_hash = CryptoHash::hash_bytes(code);
+    let current_code_hash = client.view_code_hash(signer.account_id.clone()).await?;
+
+    // An account already running the target code was upgraded by an interrupted run,
+    // so only the post-checks are left for it.
+    if current_code_hash == Some(target_code_hash) {
+        println!(
+            "{} is already running {}, skip deploy",
+            signer.account_id, target_code_hash
+        );
+    } else {
+        for check in &step.pre_checks {
+            check
+                .run(client, &signer.account_id)
+                .await
+                .map_err(|err| anyhow::anyhow!("Pre-check failed: {}", err))?;
+        }
+
+        StorageCost::fetch(
+            client,
+            signer.account_id.clone(),
+            code.len() as u64,
+            storage_amount_per_byte(client).await,
+            gas_reserve(client, gas).await?,
+        )
+        .await?
+        .check()?;
+
+        let (snapshot, snapshot_path) = take_and_save_snapshot(
+            client,
+            signer.account_id.clone(),
+            &get_default_snapshot_dir_path(&connection_config),
+        )
+        .await?;
+        println!("Saved snapshot to {}", snapshot_path.display());
+        // A plan runs unattended, so an incomplete snapshot stops the step.
+        snapshot.ensure_complete()?;
+
+        let args = args_template.render(client, &signer.account_id).await?;
+        println!(
+            "Deploy {} to {}, migrate method {}, args: {}",
+            step.wasm_path, signer.account_id, step.migrate_method, args
+        );
+        let outcome = client
+            .deploy_and_init(
+                signer,
+                code.to_vec(),
+                step.migrate_method.clone(),
+                args.into_bytes(),
+                gas,
+            )
+            .await?;
+        let is_failure = CallExecutionDetails::from(outcome.clone()).is_failure();
+        print_transaction_status(outcome, connection_config);
+        if is_failure {
+            anyhow::bail!("The deploy transaction failed");
+        }
+    }
+
+    for check in &step.post_checks {
+        check
+            .run(client, &signer.account_id)
+            .await
+            .map_err(|err| anyhow::anyhow!("Post-check failed: {}", err))?;
+    }
+    Ok(())
+}
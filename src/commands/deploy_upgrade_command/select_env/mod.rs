@@ -12,6 +12,8 @@ pub enum SelectEnv {
     Testnet(self::env::Env),
     #[strum_discriminants(strum(message = "Mainnet"))]
     Mainnet(self::env::Env),
+    #[strum_discriminants(strum(message = "Upgrade plan file(the network is set in the plan)"))]
+    Plan(super::plan::Plan),
 }
 
 impl SelectEnv {
@@ -19,6 +21,7 @@ impl SelectEnv {
         Ok(match self {
             SelectEnv::Testnet(env) => env.process(NearEnv::Testnet).await?,
             SelectEnv::Mainnet(env) => env.process(NearEnv::Mainnet).await?,
+            SelectEnv::Plan(plan) => plan.process().await?,
         })
     }
 }
@@ -1,10 +1,56 @@
-use crate::near::rpc::client::Client;
+use crate::commands::snapshot_command::confirm_incomplete_snapshot;
+use crate::near::args_template::ArgsTemplate;
+use crate::near::batch::{
+    parse_concurrency, print_account_outcomes, run_for_signers, AccountOutcome, AccountStatus,
+};
+use crate::near::rpc::client::{Client, DEFAULT_DEPLOY_AND_INIT_GAS};
+use crate::near::rpc::result::CallExecutionDetails;
+use crate::near::snapshot::take_and_save_snapshot;
+use crate::near::storage::{
+    gas_reserve, print_storage_cost_table, storage_amount_per_byte, StorageCost,
+};
 use crate::near::types::NearEnv;
-use crate::near::util::print_transaction_status;
-use crate::oct::contracts::anchor::AnchorContract;
+use crate::near::util::{get_default_snapshot_dir_path, print_transaction_status};
+use crate::near::view_check::ViewChecks;
+use crate::near::wasm::pre_check_deploy;
 use crate::CliResult;
+use color_eyre::owo_colors::OwoColorize;
 use dialoguer::Input;
-use std::path::Path;
+use itertools::Itertools;
+use near_crypto::InMemorySigner;
+use near_primitives::hash::CryptoHash;
+use near_primitives::types::AccountId;
+use prettytable::{row, Table};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+pub const NO_ARGS_FILE: &str = "none";
+pub const NO_CHECKS_FILE: &str = "none";
+
+/// What to do when the migration of an account fails or a post-upgrade check fails.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RollbackPolicy {
+    Auto,
+    Prompt,
+    Never,
+}
+
+impl FromStr for RollbackPolicy {
+    type Err = color_eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "auto" => Ok(RollbackPolicy::Auto),
+            "prompt" => Ok(RollbackPolicy::Prompt),
+            "never" => Ok(RollbackPolicy::Never),
+            _ => Err(color_eyre::Report::msg(format!(
+                "Invalid rollback policy '{}', expected auto, prompt or never",
+                s
+            ))),
+        }
+    }
+}
 
 #[derive(Debug, Clone, interactive_clap_derive::InteractiveClap)]
 // #[interactive_clap(context = super::super::super::ServerContext)]
@@ -13,6 +59,16 @@ pub struct Upgrade {
     pub wasm_path: String,
     pub migrate_method_name: String,
     pub args: String,
+    #[interactive_clap(long)]
+    pub args_file: String,
+    #[interactive_clap(long)]
+    pub checks_file: String,
+    #[interactive_clap(long)]
+    pub rollback: String,
+    #[interactive_clap(long)]
+    pub concurrency: String,
+    #[interactive_clap(long)]
+    pub continue_on_error: String,
 }
 
 impl Upgrade {
@@ -34,6 +90,41 @@ impl Upgrade {
             .interact_text()?)
     }
 
+    pub fn input_args_file(_context: &()) -> color_eyre::eyre::Result<String> {
+        Ok(Input::new()
+            .with_prompt("What is the args file path(it replaces the args above), or 'none'?")
+            .default(NO_ARGS_FILE.to_string())
+            .interact_text()?)
+    }
+
+    pub fn input_checks_file(_context: &()) -> color_eyre::eyre::Result<String> {
+        Ok(Input::new()
+            .with_prompt("What is the post-upgrade checks file path, or 'none'?")
+            .default(NO_CHECKS_FILE.to_string())
+            .interact_text()?)
+    }
+
+    pub fn input_rollback(_context: &()) -> color_eyre::eyre::Result<String> {
+        Ok(Input::new()
+            .with_prompt("Rollback when the migration or a check fails: auto, prompt or never?")
+            .default("prompt".to_string())
+            .interact_text()?)
+    }
+
+    pub fn input_concurrency(_context: &()) -> color_eyre::eyre::Result<String> {
+        Ok(Input::new()
+            .with_prompt("How many accounts to upgrade at the same time?")
+            .default("1".to_string())
+            .interact_text()?)
+    }
+
+    pub fn input_continue_on_error(_context: &()) -> color_eyre::eyre::Result<String> {
+        Ok(Input::new()
+            .with_prompt("Continue with the other accounts when one fails?[y/n]")
+            .default("n".to_string())
+            .interact_text()?)
+    }
+
     pub async fn process(
         self,
         connection_config: NearEnv,
@@ -42,31 +133,400 @@ impl Upgrade {
     ) -> CliResult {
         let code =
             std::fs::read(&Path::new(self.wasm_path.as_str())).expect("Failed to read wasm file");
+        let target_code_hash = CryptoHash::hash_bytes(&code);
+        let rollback_policy = RollbackPolicy::from_str(&self.rollback)?;
+        let concurrency = parse_concurrency(&self.concurrency)?;
+        if concurrency > 1 && rollback_policy == RollbackPolicy::Prompt {
+            return Err(color_eyre::Report::msg(
+                "--rollback prompt can not be used with --concurrency greater than 1, use auto or never",
+            ));
+        }
+        let continue_on_error = self.continue_on_error.eq("y") || self.continue_on_error.eq("Y");
+        let args_template = if self.args_file.eq_ignore_ascii_case(NO_ARGS_FILE) {
+            ArgsTemplate::parse(&self.args)
+        } else {
+            ArgsTemplate::from_file(Path::new(self.args_file.as_str()))
+        }
+        .map_err(|err| color_eyre::Report::msg(format!("Invalid migrate args, error: {}", err)))?;
+        let checks = if self.checks_file.eq_ignore_ascii_case(NO_CHECKS_FILE) {
+            ViewChecks::default()
+        } else {
+            ViewChecks::load(Path::new(self.checks_file.as_str())).map_err(|err| {
+                color_eyre::Report::msg(format!(
+                    "Failed to load checks file {}, error: {}",
+                    self.checks_file, err
+                ))
+            })?
+        };
 
         let client = connection_config.init_client();
+        pre_check_deploy(&client, &code, &self.migrate_method_name)
+            .await
+            .map_err(|err| {
+                color_eyre::Report::msg(format!(
+                    "Wasm pre-check of {} failed, error: {}",
+                    self.wasm_path, err
+                ))
+            })?;
+        let snapshot_dir = get_default_snapshot_dir_path(&connection_config);
+
+        let mut current_code_hashes = vec![];
+        for signer in &account_list {
+            let current_code_hash = client
+                .view_code_hash(signer.account_id.clone())
+                .await
+                .map_err(|err| {
+                    color_eyre::Report::msg(format!(
+                        "Failed to view code of {}, error: {}",
+                        signer.account_id, err
+                    ))
+                })?;
+            current_code_hashes.push(current_code_hash);
+        }
+        print_code_hash_table(&account_list, &current_code_hashes, &target_code_hash);
 
-        for signer in account_list {
+        let distinct_code_hashes = current_code_hashes.iter().unique().count();
+        if distinct_code_hashes > 1 {
             println!(
-                "---Start {} deploy, wasm is {} , migrate method {}, args: {}",
-                signer.account_id, self.wasm_path, self.migrate_method_name, self.args
-            );
-            let outcome = client
-                .deploy_and_init(
-                    &signer,
-                    code.clone(),
-                    self.migrate_method_name.clone(),
-                    self.args.clone().into_bytes(),
+                "{}",
+                format!(
+                    "Warning: the selected accounts are running {} different versions of code.",
+                    distinct_code_hashes
                 )
+                .yellow()
+            );
+        }
+
+        let mut args_of_accounts: HashMap<AccountId, String> = HashMap::new();
+        for (signer, current_code_hash) in account_list.iter().zip(&current_code_hashes) {
+            if current_code_hash == &Some(target_code_hash) {
+                continue;
+            }
+            let args = args_template
+                .render(&client, &signer.account_id)
+                .await
+                .map_err(|err| {
+                    color_eyre::Report::msg(format!(
+                        "Failed to render migrate args of {}, error: {}",
+                        signer.account_id, err
+                    ))
+                })?;
+            args_of_accounts.insert(signer.account_id.clone(), args);
+        }
+
+        let storage_amount_per_byte = storage_amount_per_byte(&client).await;
+        let deploy_gas_reserve = gas_reserve(&client, DEFAULT_DEPLOY_AND_INIT_GAS)
+            .await
+            .map_err(|err| {
+                color_eyre::Report::msg(format!("Failed to read the gas price, error: {}", err))
+            })?;
+        let mut storage_costs = vec![];
+        for (signer, current_code_hash) in account_list.iter().zip(&current_code_hashes) {
+            if current_code_hash == &Some(target_code_hash) {
+                continue;
+            }
+            let storage_cost = StorageCost::fetch(
+                &client,
+                signer.account_id.clone(),
+                code.len() as u64,
+                storage_amount_per_byte,
+                deploy_gas_reserve,
+            )
+            .await
+            .map_err(|err| {
+                color_eyre::Report::msg(format!(
+                    "Failed to calculate storage cost of {}, error: {}",
+                    signer.account_id, err
+                ))
+            })?;
+            storage_costs.push(storage_cost);
+        }
+        if !storage_costs.is_empty() {
+            print_storage_cost_table(&storage_costs);
+        }
+        let shortfalls: Vec<String> = storage_costs
+            .iter()
+            .filter_map(|storage_cost| storage_cost.check().err())
+            .map(|err| err.to_string())
+            .collect();
+        if !shortfalls.is_empty() {
+            return Err(color_eyre::Report::msg(format!(
+                "Refuse to deploy, not enough balance for storage and gas:\n{}",
+                shortfalls.join("\n")
+            )));
+        }
+
+        let current_code_hashes: HashMap<AccountId, Option<CryptoHash>> = account_list
+            .iter()
+            .map(|signer| signer.account_id.clone())
+            .zip(current_code_hashes)
+            .collect();
+        let context = UpgradeContext {
+            client: &client,
+            connection_config,
+            code: &code,
+            target_code_hash,
+            upgrade: &self,
+            args_of_accounts: &args_of_accounts,
+            checks: &checks,
+            rollback_policy,
+            snapshot_dir: &snapshot_dir,
+        };
+        let outcomes = run_for_signers(account_list, concurrency, continue_on_error, |signer| {
+            let current_code_hash = current_code_hashes[&signer.account_id];
+            let context = &context;
+            async move { context.upgrade_account(signer, current_code_hash).await }
+        })
+        .await;
+        print_account_outcomes(&outcomes)
+    }
+}
+
+struct UpgradeContext<'a> {
+    client: &'a Client,
+    connection_config: NearEnv,
+    code: &'a [u8],
+    target_code_hash: CryptoHash,
+    upgrade: &'a Upgrade,
+    args_of_accounts: &'a HashMap<AccountId, String>,
+    checks: &'a ViewChecks,
+    rollback_policy: RollbackPolicy,
+    snapshot_dir: &'a Path,
+}
+
+impl<'a> UpgradeContext<'a> {
+    async fn upgrade_account(
+        &self,
+        signer: InMemorySigner,
+        current_code_hash: Option<CryptoHash>,
+    ) -> AccountOutcome {
+        let mut outcome = AccountOutcome::new(signer.account_id.clone());
+        if current_code_hash == Some(self.target_code_hash) {
+            println!(
+                "---Skip {} deploy, the code is already {}\n",
+                signer.account_id, self.target_code_hash
+            );
+            outcome.status = AccountStatus::Skipped;
+            return outcome;
+        }
+        if let Err(err) = self
+            .try_upgrade_account(&signer, current_code_hash, &mut outcome)
+            .await
+        {
+            let rolled_back = outcome.status == AccountStatus::RolledBack;
+            outcome.fail(err);
+            if rolled_back {
+                outcome.status = AccountStatus::RolledBack;
+            }
+        }
+        outcome
+    }
+
+    async fn try_upgrade_account(
+        &self,
+        signer: &InMemorySigner,
+        current_code_hash: Option<CryptoHash>,
+        outcome: &mut AccountOutcome,
+    ) -> CliResult {
+        let client = self.client;
+        let args = &self.args_of_accounts[&signer.account_id];
+        println!(
+            "---Start {} deploy, wasm is {} , migrate method {}, args: {}",
+            signer.account_id, self.upgrade.wasm_path, self.upgrade.migrate_method_name, args
+        );
+        let (snapshot, snapshot_path) =
+            take_and_save_snapshot(client, signer.account_id.clone(), self.snapshot_dir)
                 .await
                 .map_err(|err| {
                     color_eyre::Report::msg(format!(
-                        "Failed to deploy anchor with {}, error: {}",
+                        "Failed to take snapshot of {}, error: {}",
                         signer.account_id, err
                     ))
                 })?;
-            print_transaction_status(outcome, connection_config.clone());
-            println!("---End {} deploy\n", signer.account_id);
+        println!("Saved snapshot to {}", snapshot_path.display());
+        confirm_incomplete_snapshot(&snapshot, &snapshot_path)?;
+        // Captured separately from the snapshot so rollback only depends on the rpc.
+        let previous_code = match current_code_hash {
+            Some(_) => Some(
+                client
+                    .view_code(signer.account_id.clone(), None)
+                    .await
+                    .map_err(|err| {
+                        color_eyre::Report::msg(format!(
+                            "Failed to view code of {}, error: {}",
+                            signer.account_id, err
+                        ))
+                    })?
+                    .code,
+            ),
+            None => None,
+        };
+
+        let deploy_outcome = client
+            .deploy_and_init(
+                signer,
+                self.code.to_vec(),
+                self.upgrade.migrate_method_name.clone(),
+                args.clone().into_bytes(),
+                DEFAULT_DEPLOY_AND_INIT_GAS,
+            )
+            .await
+            .map_err(|err| {
+                color_eyre::Report::msg(format!(
+                    "Failed to deploy anchor with {}, error: {}",
+                    signer.account_id, err
+                ))
+            })?;
+        outcome.record_transaction(&deploy_outcome);
+        let is_failure = CallExecutionDetails::from(deploy_outcome.clone()).is_failure();
+        print_transaction_status(deploy_outcome, self.connection_config.clone());
+
+        let failure = if is_failure {
+            Some("the migration receipt failed".to_string())
+        } else {
+            self.checks
+                .run(client, &signer.account_id)
+                .await
+                .err()
+                .map(|err| format!("post-upgrade check failed: {}", err))
+        };
+        if let Some(failure) = failure {
+            println!(
+                "{}",
+                format!("Upgrade of {} failed, {}", signer.account_id, failure).red()
+            );
+            self.handle_failed_upgrade(
+                signer,
+                previous_code,
+                current_code_hash,
+                snapshot_path,
+                outcome,
+            )
+            .await?;
+            return Err(color_eyre::Report::msg(failure));
+        }
+        if !self.checks.checks.is_empty() {
+            println!(
+                "All {} post-upgrade checks passed on {}",
+                self.checks.checks.len(),
+                signer.account_id
+            );
+        }
+        println!("---End {} deploy\n", signer.account_id);
+        Ok(())
+    }
+
+    /// Redeploys the code captured before the upgrade if the policy allows it.
+    ///
+    /// Only the code is rolled back, a migrated state can be restored from `snapshot_path`.
+    async fn handle_failed_upgrade(
+        &self,
+        signer: &InMemorySigner,
+        previous_code: Option<Vec<u8>>,
+        previous_code_hash: Option<CryptoHash>,
+        snapshot_path: PathBuf,
+        outcome: &mut AccountOutcome,
+    ) -> CliResult {
+        let previous_code = match previous_code {
+            Some(previous_code) => previous_code,
+            None => {
+                println!(
+                    "{} had no contract before the upgrade, nothing to roll back.",
+                    signer.account_id
+                );
+                return Ok(());
+            }
+        };
+        let current_code_hash = self
+            .client
+            .view_code_hash(signer.account_id.clone())
+            .await
+            .map_err(|err| {
+                color_eyre::Report::msg(format!(
+                    "Failed to view code of {}, error: {}",
+                    signer.account_id, err
+                ))
+            })?;
+        // A failed function call reverts the deploy action of the same transaction.
+        if current_code_hash == previous_code_hash {
+            println!(
+                "{} is still running the previous code, nothing to roll back.",
+                signer.account_id
+            );
+            return Ok(());
         }
+
+        let rollback = match self.rollback_policy {
+            RollbackPolicy::Auto => true,
+            RollbackPolicy::Never => false,
+            RollbackPolicy::Prompt => {
+                let confirm: String = Input::new()
+                    .with_prompt(format!(
+                        "Redeploy the previous code to {}?[y/n]",
+                        signer.account_id
+                    ))
+                    .interact_text()?;
+                confirm.eq("y") || confirm.eq("Y")
+            }
+        };
+        if !rollback {
+            println!(
+                "Skip rollback of {}, the snapshot before the upgrade is {}",
+                signer.account_id,
+                snapshot_path.display()
+            );
+            return Ok(());
+        }
+
+        println!("---Rollback {} to the previous code", signer.account_id);
+        let rollback_outcome = self
+            .client
+            .deploy(signer, previous_code)
+            .await
+            .map_err(|err| {
+                color_eyre::Report::msg(format!(
+                    "Failed to roll back {}, error: {}",
+                    signer.account_id, err
+                ))
+            })?;
+        outcome.record_transaction(&rollback_outcome);
+        print_transaction_status(rollback_outcome, self.connection_config.clone());
+        outcome.status = AccountStatus::RolledBack;
+        println!(
+            "Rolled back the code of {}, restore the state from {} if the migration changed it.",
+            signer.account_id,
+            snapshot_path.display()
+        );
         Ok(())
     }
 }
+
+fn print_code_hash_table(
+    account_list: &[near_crypto::InMemorySigner],
+    current_code_hashes: &[Option<CryptoHash>],
+    target_code_hash: &CryptoHash,
+) {
+    let mut table = Table::new();
+    table.set_titles(row![
+        "account",
+        "current code hash",
+        "target code hash",
+        "action"
+    ]);
+    for (signer, current_code_hash) in account_list.iter().zip(current_code_hashes) {
+        let action = if current_code_hash.as_ref() == Some(target_code_hash) {
+            "skip(identical)".green().to_string()
+        } else {
+            "upgrade".yellow().to_string()
+        };
+        table.add_row(row![
+            signer.account_id,
+            current_code_hash
+                .map(|hash| hash.to_string())
+                .unwrap_or_else(|| "<no contract>".to_string()),
+            target_code_hash,
+            action
+        ]);
+    }
+    table.printstd();
+}
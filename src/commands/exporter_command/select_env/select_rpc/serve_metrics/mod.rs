@@ -0,0 +1,154 @@
+use crate::near::batch::parse_concurrency;
+use crate::near::rpc::client::Client;
+use crate::near::types::NearEnv;
+use crate::oct::metrics::{bind_metrics_server, collect_metrics, render_metrics};
+use crate::oct::monitor::parse_duration;
+use crate::CliResult;
+use color_eyre::owo_colors::OwoColorize;
+use dialoguer::Input;
+use near_primitives::types::AccountId;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+pub const NO_FUND_ACCOUNTS: &str = "none";
+
+#[derive(Debug, Clone, interactive_clap_derive::InteractiveClap)]
+#[interactive_clap(context = ())]
+pub struct ServeMetrics {
+    pub registry_account: String,
+    #[interactive_clap(long)]
+    pub listen: String,
+    #[interactive_clap(long)]
+    pub interval: String,
+    #[interactive_clap(long)]
+    pub fund_accounts: String,
+    #[interactive_clap(long)]
+    pub concurrency: String,
+}
+
+impl ServeMetrics {
+    pub fn input_registry_account(_context: &()) -> color_eyre::eyre::Result<String> {
+        Ok(Input::new()
+            .with_prompt("What is the registry account?")
+            .interact_text()?)
+    }
+
+    pub fn input_listen(_context: &()) -> color_eyre::eyre::Result<String> {
+        Ok(Input::new()
+            .with_prompt("Which address to serve /metrics on?")
+            .default("127.0.0.1:9898".to_string())
+            .interact_text()?)
+    }
+
+    pub fn input_interval(_context: &()) -> color_eyre::eyre::Result<String> {
+        Ok(Input::new()
+            .with_prompt("How often to refresh the metrics (eg: 30s, 1m)?")
+            .default("1m".to_string())
+            .interact_text()?)
+    }
+
+    pub fn input_fund_accounts(_context: &()) -> color_eyre::eyre::Result<String> {
+        Ok(Input::new()
+            .with_prompt(
+                "Which accounts to export the OCT balance of (comma separated, or 'none')?",
+            )
+            .default(NO_FUND_ACCOUNTS.to_string())
+            .interact_text()?)
+    }
+
+    pub fn input_concurrency(_context: &()) -> color_eyre::eyre::Result<String> {
+        Ok(Input::new()
+            .with_prompt("How many requests to send at the same time?")
+            .default("8".to_string())
+            .interact_text()?)
+    }
+
+    fn fund_accounts(&self) -> color_eyre::eyre::Result<Vec<AccountId>> {
+        if self
+            .fund_accounts
+            .trim()
+            .eq_ignore_ascii_case(NO_FUND_ACCOUNTS)
+        {
+            return Ok(vec![]);
+        }
+        self.fund_accounts
+            .split(',')
+            .map(|e| {
+                e.trim().parse().map_err(|err| {
+                    color_eyre::Report::msg(format!("Invalid fund account {}, error: {}", e, err))
+                })
+            })
+            .collect()
+    }
+
+    pub async fn process(self, connection_config: NearEnv, client: Client) -> CliResult {
+        let concurrency = parse_concurrency(&self.concurrency)?;
+        let interval = parse_duration(&self.interval)
+            .map_err(|err| color_eyre::Report::msg(err.to_string()))?;
+        if interval.as_secs() == 0 {
+            return Err(color_eyre::Report::msg("The interval must be at least 1s"));
+        }
+        let listen: SocketAddr = self.listen.parse().map_err(|err| {
+            color_eyre::Report::msg(format!(
+                "Invalid listen address {}, error: {}",
+                self.listen, err
+            ))
+        })?;
+        let fund_accounts = self.fund_accounts()?;
+        let registry_account_id: AccountId = self.registry_account.parse()?;
+        let oct_token_account_id = connection_config.oct_token_account_id();
+
+        let metrics = Arc::new(RwLock::new(None));
+        let (addr, server) = bind_metrics_server(listen, metrics.clone(), async {
+            let _ = tokio::signal::ctrl_c().await;
+        })
+        .map_err(|err| {
+            color_eyre::Report::msg(format!("Failed to listen on {}, error: {}", listen, err))
+        })?;
+        println!(
+            "Serving metrics of {} on http://{}/metrics, refreshed every {}s. Press Ctrl-C to stop.",
+            registry_account_id.blue(),
+            addr,
+            interval.as_secs()
+        );
+
+        let refresh = async {
+            loop {
+                match collect_metrics(
+                    &client,
+                    registry_account_id.clone(),
+                    &oct_token_account_id,
+                    &fund_accounts,
+                    concurrency,
+                )
+                .await
+                {
+                    Ok(collected) => {
+                        for appchain in &collected.appchains {
+                            if let Some(error) = &appchain.error {
+                                println!(
+                                    "{}",
+                                    format!("Failed to read {}: {}", appchain.appchain_id, error)
+                                        .red()
+                                );
+                            }
+                        }
+                        *metrics.write().await = Some(render_metrics(&collected));
+                    }
+                    // Keep serving the last metrics, their refresh timestamp shows they are old.
+                    Err(err) => println!("{}", format!("Failed to refresh metrics: {}", err).red()),
+                }
+                tokio::time::sleep(interval).await;
+            }
+        };
+        tokio::select! {
+            result = server => result.map_err(|err| {
+                color_eyre::Report::msg(format!("Metrics server failed, error: {}", err))
+            })?,
+            _ = refresh => {}
+        }
+        println!("Exporter stopped.");
+        Ok(())
+    }
+}
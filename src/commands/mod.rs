@@ -1,9 +1,20 @@
 use strum::{EnumDiscriminants, EnumIter, EnumMessage};
 
+pub mod audit_stake_command;
 pub mod check_unprofitable_validator;
 pub mod clean_state_command;
+pub mod create_anchor_command;
+pub mod dashboard_command;
 pub mod delegation_airdrop;
+pub mod delegator_rewards_command;
 pub mod deploy_upgrade_command;
+pub mod exporter_command;
+pub mod monitor_command;
+pub mod rewards_command;
+pub mod snapshot_command;
+pub mod state_command;
+pub mod storage_cost_command;
+pub mod wasm_command;
 
 #[derive(Debug, Clone, EnumDiscriminants, interactive_clap_derive::InteractiveClap)]
 #[strum_discriminants(derive(EnumMessage, EnumIter))]
@@ -16,8 +27,30 @@ pub enum TopLevelCommand {
     CleanState(self::clean_state_command::CleanStateCommand),
     #[strum_discriminants(strum(message = "Check unprofitable validator"))]
     CheckUnprofitableValidator(self::check_unprofitable_validator::CheckUnprofitableValidator),
+    #[strum_discriminants(strum(message = "Monitor appchains and post alerts to webhooks"))]
+    Monitor(self::monitor_command::MonitorCommand),
+    #[strum_discriminants(strum(message = "Open a live dashboard of appchains"))]
+    Dashboard(self::dashboard_command::DashboardCommand),
+    #[strum_discriminants(strum(message = "Serve Prometheus metrics of appchains"))]
+    Exporter(self::exporter_command::ExporterCommand),
+    #[strum_discriminants(strum(message = "Show validator reward history"))]
+    Rewards(self::rewards_command::RewardsCommand),
+    #[strum_discriminants(strum(message = "Show rewards of a delegator across appchains"))]
+    DelegatorRewards(self::delegator_rewards_command::DelegatorRewardsCommand),
+    #[strum_discriminants(strum(message = "Audit stake distribution against protocol settings"))]
+    AuditStake(self::audit_stake_command::AuditStakeCommand),
     #[strum_discriminants(strum(message = "Perform delegation airdrop"))]
     DelegationAirdrop(self::delegation_airdrop::DelegationAirdrop),
+    #[strum_discriminants(strum(message = "Create a new appchain anchor"))]
+    CreateAnchor(self::create_anchor_command::CreateAnchorCommand),
+    #[strum_discriminants(strum(message = "Restore contract snapshot"))]
+    Snapshot(self::snapshot_command::SnapshotCommand),
+    #[strum_discriminants(strum(message = "Dump or compare contract state"))]
+    State(self::state_command::StateCommand),
+    #[strum_discriminants(strum(message = "Calculate storage cost of deploying a wasm"))]
+    StorageCost(self::storage_cost_command::StorageCostCommand),
+    #[strum_discriminants(strum(message = "Inspect wasm"))]
+    Wasm(self::wasm_command::WasmCommand),
 }
 
 impl TopLevelCommand {
@@ -30,9 +63,26 @@ impl TopLevelCommand {
             TopLevelCommand::CheckUnprofitableValidator(check_unprofitable_validator) => {
                 check_unprofitable_validator.process().await
             }
+            TopLevelCommand::Monitor(monitor_command) => monitor_command.process().await,
+            TopLevelCommand::Dashboard(dashboard_command) => dashboard_command.process().await,
+            TopLevelCommand::Exporter(exporter_command) => exporter_command.process().await,
+            TopLevelCommand::Rewards(rewards_command) => rewards_command.process().await,
+            TopLevelCommand::DelegatorRewards(delegator_rewards_command) => {
+                delegator_rewards_command.process().await
+            }
+            TopLevelCommand::AuditStake(audit_stake_command) => audit_stake_command.process().await,
             TopLevelCommand::DelegationAirdrop(delegation_airdrip) => {
                 delegation_airdrip.process().await
             }
+            TopLevelCommand::CreateAnchor(create_anchor_command) => {
+                create_anchor_command.process().await
+            }
+            TopLevelCommand::Snapshot(snapshot_command) => snapshot_command.process().await,
+            TopLevelCommand::State(state_command) => state_command.process().await,
+            TopLevelCommand::StorageCost(storage_cost_command) => {
+                storage_cost_command.process().await
+            }
+            TopLevelCommand::Wasm(wasm_command) => wasm_command.process().await,
         }
     }
 }
@@ -0,0 +1,135 @@
+use crate::near::batch::parse_concurrency;
+use crate::near::rpc::client::Client;
+use crate::near::types::NearEnv;
+use crate::oct::monitor::{observe_registry, parse_duration, post_event, MonitorState};
+use crate::CliResult;
+use color_eyre::owo_colors::OwoColorize;
+use dialoguer::Input;
+use near_primitives::types::AccountId;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const NO_WEBHOOK: &str = "none";
+
+#[derive(Debug, Clone, interactive_clap_derive::InteractiveClap)]
+#[interactive_clap(context = ())]
+pub struct MonitorRegistry {
+    pub registry_account: String,
+    #[interactive_clap(long)]
+    pub interval: String,
+    #[interactive_clap(long)]
+    pub max_era_age: String,
+    #[interactive_clap(long)]
+    pub webhook: String,
+    #[interactive_clap(long)]
+    pub concurrency: String,
+}
+
+impl MonitorRegistry {
+    pub fn input_registry_account(_context: &()) -> color_eyre::eyre::Result<String> {
+        Ok(Input::new()
+            .with_prompt("What is the registry account?")
+            .interact_text()?)
+    }
+
+    pub fn input_interval(_context: &()) -> color_eyre::eyre::Result<String> {
+        Ok(Input::new()
+            .with_prompt("How often to scan the registry (eg: 30s, 5m, 1h)?")
+            .default("5m".to_string())
+            .interact_text()?)
+    }
+
+    pub fn input_max_era_age(_context: &()) -> color_eyre::eyre::Result<String> {
+        Ok(Input::new()
+            .with_prompt("Alert when an era has not advanced for how long?")
+            .default("25h".to_string())
+            .interact_text()?)
+    }
+
+    pub fn input_webhook(_context: &()) -> color_eyre::eyre::Result<String> {
+        Ok(Input::new()
+            .with_prompt("Which webhook urls to post alerts to (comma separated, or 'none')?")
+            .default(NO_WEBHOOK.to_string())
+            .interact_text()?)
+    }
+
+    pub fn input_concurrency(_context: &()) -> color_eyre::eyre::Result<String> {
+        Ok(Input::new()
+            .with_prompt("How many requests to send at the same time?")
+            .default("8".to_string())
+            .interact_text()?)
+    }
+
+    fn webhooks(&self) -> color_eyre::eyre::Result<Vec<url::Url>> {
+        if self.webhook.trim().eq_ignore_ascii_case(NO_WEBHOOK) {
+            return Ok(vec![]);
+        }
+        self.webhook
+            .split(',')
+            .map(|e| {
+                e.trim().parse().map_err(|err| {
+                    color_eyre::Report::msg(format!("Invalid webhook url {}, error: {}", e, err))
+                })
+            })
+            .collect()
+    }
+
+    pub async fn process(self, _connection_config: NearEnv, client: Client) -> CliResult {
+        let concurrency = parse_concurrency(&self.concurrency)?;
+        let interval = parse_duration(&self.interval)
+            .map_err(|err| color_eyre::Report::msg(err.to_string()))?;
+        if interval.as_secs() == 0 {
+            return Err(color_eyre::Report::msg("The interval must be at least 1s"));
+        }
+        let max_era_age = parse_duration(&self.max_era_age)
+            .map_err(|err| color_eyre::Report::msg(err.to_string()))?;
+        let webhooks = self.webhooks()?;
+        let registry_account_id: AccountId = self.registry_account.parse()?;
+
+        let http = reqwest::Client::new();
+        let mut state = MonitorState::default();
+        println!(
+            "Monitoring {} every {}s, alerts go to {} webhooks. Press Ctrl-C to stop.",
+            registry_account_id.blue(),
+            interval.as_secs(),
+            webhooks.len()
+        );
+        loop {
+            let time = chrono::Local::now().format("%Y-%m-%d %H:%M:%S");
+            match observe_registry(&client, registry_account_id.clone(), concurrency).await {
+                Ok(observations) => {
+                    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+                    let events = state.observe(&observations, now, max_era_age);
+                    if events.is_empty() {
+                        println!(
+                            "[{}] {} appchains checked, nothing new.",
+                            time,
+                            observations.len()
+                        );
+                    }
+                    for event in &events {
+                        println!("[{}] {}", time, event.to_string().yellow());
+                        for (webhook, error) in
+                            post_event(&http, &webhooks, &registry_account_id, event).await
+                        {
+                            println!(
+                                "{}",
+                                format!("Failed to post alert to {}: {}", webhook, error).red()
+                            );
+                        }
+                    }
+                }
+                Err(err) => println!(
+                    "{}",
+                    format!("[{}] Failed to scan {}: {}", time, registry_account_id, err).red()
+                ),
+            }
+            tokio::select! {
+                _ = tokio::time::sleep(interval) => {}
+                _ = tokio::signal::ctrl_c() => {
+                    println!("Monitor stopped.");
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
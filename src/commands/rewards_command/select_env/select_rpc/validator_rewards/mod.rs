@@ -0,0 +1,177 @@
+use crate::near::batch::parse_concurrency;
+use crate::near::rpc::client::Client;
+use crate::near::types::NearEnv;
+use crate::oct::report::{parse_report_path, reward_records, rewards_html, save_csv, NO_REPORT};
+use crate::oct::rewards::{fetch_rewards, print_anchor_rewards, RewardQuery};
+use crate::oct::scan::{
+    active_anchors_of_registry, parse_era, parse_list, parse_validator_ids, AppchainScanResult,
+    ALL, AUTO_ERA, LATEST_ERA,
+};
+use crate::CliResult;
+use color_eyre::owo_colors::OwoColorize;
+use dialoguer::Input;
+use near_primitives::types::AccountId;
+
+#[derive(Debug, Clone, interactive_clap_derive::InteractiveClap)]
+#[interactive_clap(context = ())]
+pub struct ValidatorRewards {
+    pub registry_account: String,
+    #[interactive_clap(long)]
+    pub appchain: String,
+    #[interactive_clap(long)]
+    pub from_era: String,
+    #[interactive_clap(long)]
+    pub to_era: String,
+    #[interactive_clap(long)]
+    pub validator: String,
+    #[interactive_clap(long)]
+    pub concurrency: String,
+    #[interactive_clap(long)]
+    pub csv: String,
+    #[interactive_clap(long)]
+    pub html: String,
+}
+
+impl ValidatorRewards {
+    pub fn input_registry_account(_context: &()) -> color_eyre::eyre::Result<String> {
+        Ok(Input::new()
+            .with_prompt("What is the registry account?")
+            .interact_text()?)
+    }
+
+    pub fn input_appchain(_context: &()) -> color_eyre::eyre::Result<String> {
+        Ok(Input::new()
+            .with_prompt("Which appchains (comma separated ids, or 'all')?")
+            .default(ALL.to_string())
+            .interact_text()?)
+    }
+
+    pub fn input_from_era(_context: &()) -> color_eyre::eyre::Result<String> {
+        Ok(Input::new()
+            .with_prompt(
+                "From which era ('auto' is the oldest era whose rewards have not expired)?",
+            )
+            .default(AUTO_ERA.to_string())
+            .interact_text()?)
+    }
+
+    pub fn input_to_era(_context: &()) -> color_eyre::eyre::Result<String> {
+        Ok(Input::new()
+            .with_prompt("To which era?")
+            .default(LATEST_ERA.to_string())
+            .interact_text()?)
+    }
+
+    pub fn input_validator(_context: &()) -> color_eyre::eyre::Result<String> {
+        Ok(Input::new()
+            .with_prompt("Which validators (comma separated, or 'all' of the current era)?")
+            .default(ALL.to_string())
+            .interact_text()?)
+    }
+
+    pub fn input_concurrency(_context: &()) -> color_eyre::eyre::Result<String> {
+        Ok(Input::new()
+            .with_prompt("How many requests to send at the same time?")
+            .default("8".to_string())
+            .interact_text()?)
+    }
+
+    pub fn input_csv(_context: &()) -> color_eyre::eyre::Result<String> {
+        Ok(Input::new()
+            .with_prompt("Which file to export the rewards to as csv (or 'none')?")
+            .default(NO_REPORT.to_string())
+            .interact_text()?)
+    }
+
+    pub fn input_html(_context: &()) -> color_eyre::eyre::Result<String> {
+        Ok(Input::new()
+            .with_prompt("Which file to export the rewards to as html (or 'none')?")
+            .default(NO_REPORT.to_string())
+            .interact_text()?)
+    }
+
+    pub async fn process(self, _connection_config: NearEnv, client: Client) -> CliResult {
+        let registry_account_id: AccountId = self.registry_account.parse()?;
+        let concurrency = parse_concurrency(&self.concurrency)?;
+        let query = RewardQuery {
+            start_era: parse_era(&self.from_era, AUTO_ERA)?,
+            end_era: parse_era(&self.to_era, LATEST_ERA)?,
+            validator_ids: parse_validator_ids(&self.validator)?,
+        };
+        let anchors = active_anchors_of_registry(
+            &client,
+            registry_account_id.clone(),
+            &parse_list(&self.appchain),
+            concurrency,
+        )
+        .await
+        .map_err(|err| color_eyre::Report::msg(err.to_string()))?;
+        let results = fetch_rewards(&client, anchors, &query, concurrency)
+            .await
+            .map_err(|err| color_eyre::Report::msg(err.to_string()))?;
+
+        let mut failed = 0;
+        for result in &results {
+            match result {
+                Ok(rewards) => print_anchor_rewards(rewards),
+                Err(AppchainScanResult::Skipped {
+                    anchor_account_id,
+                    appchain_state,
+                    ..
+                }) => println!(
+                    "The state of {} is {:?}, skip rewards!\n",
+                    anchor_account_id.blue(),
+                    appchain_state.yellow()
+                ),
+                Err(AppchainScanResult::Failed { appchain_id, error }) => {
+                    failed += 1;
+                    println!(
+                        "{}\n",
+                        format!("Failed to read rewards of {}: {}", appchain_id, error).red()
+                    );
+                }
+                Err(AppchainScanResult::Scanned(_)) => {}
+            }
+        }
+
+        let records = reward_records(
+            &results
+                .iter()
+                .filter_map(|e| e.as_ref().ok())
+                .collect::<Vec<_>>(),
+        );
+        if let Some(path) = parse_report_path(&self.csv) {
+            save_csv(&path, &records).map_err(|err| {
+                color_eyre::Report::msg(format!(
+                    "Failed to save csv report to {}, error: {}",
+                    path.display(),
+                    err
+                ))
+            })?;
+            println!("Saved csv report to {}", path.display());
+        }
+        if let Some(path) = parse_report_path(&self.html) {
+            let html = rewards_html(
+                &records,
+                &format!("Validator rewards of {}", registry_account_id),
+                &chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+            );
+            std::fs::write(&path, html).map_err(|err| {
+                color_eyre::Report::msg(format!(
+                    "Failed to save html report to {}, error: {}",
+                    path.display(),
+                    err
+                ))
+            })?;
+            println!("Saved html report to {}", path.display());
+        }
+        if failed > 0 {
+            return Err(color_eyre::Report::msg(format!(
+                "Failed to read rewards of {} of {} appchains",
+                failed,
+                results.len()
+            )));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,60 @@
+use crate::near::snapshot::ContractSnapshot;
+use dialoguer::Input;
+use std::path::Path;
+use strum::{EnumDiscriminants, EnumIter, EnumMessage};
+
+pub mod restore;
+
+#[derive(Debug, Clone, interactive_clap_derive::InteractiveClap)]
+#[interactive_clap(context = ())]
+pub struct SnapshotCommand {
+    #[interactive_clap(subcommand)]
+    action: SnapshotAction,
+}
+
+impl SnapshotCommand {
+    pub async fn process(self) -> crate::CliResult {
+        self.action.process().await
+    }
+}
+
+#[derive(Debug, Clone, EnumDiscriminants, interactive_clap_derive::InteractiveClap)]
+#[strum_discriminants(derive(EnumMessage, EnumIter))]
+#[interactive_clap(context = ())]
+///Choose snapshot action
+pub enum SnapshotAction {
+    #[strum_discriminants(strum(
+        message = "Restore code and state of an account from a snapshot"
+    ))]
+    Restore(self::restore::Restore),
+}
+
+impl SnapshotAction {
+    pub async fn process(self) -> crate::CliResult {
+        match self {
+            SnapshotAction::Restore(restore) => restore.process().await,
+        }
+    }
+}
+
+/// Asks before an account is changed with an incomplete snapshot as the only way back, keys
+/// equal to a split prefix can not be restored from it.
+pub fn confirm_incomplete_snapshot(snapshot: &ContractSnapshot, path: &Path) -> crate::CliResult {
+    if let Err(err) = snapshot.ensure_complete() {
+        println!("{}", err);
+        let confirm: String = Input::new()
+            .with_prompt(format!(
+                "Go on changing {} with the incomplete snapshot {}?[y/n]",
+                snapshot.account_id,
+                path.display()
+            ))
+            .interact_text()?;
+        if !(confirm.eq("y") || confirm.eq("Y")) {
+            return Err(color_eyre::Report::msg(format!(
+                "Stopped before changing {}, its snapshot is incomplete",
+                snapshot.account_id
+            )));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,187 @@
+use crate::near::contracts::clean_state::{CleanStateContract, DEFAULT_CLEAN_GAS_BUDGET};
+use crate::near::contracts::state_restore::StateRestoreContract;
+use crate::near::rpc::client::Client;
+use crate::near::snapshot::ContractSnapshot;
+use crate::near::types::NearEnv;
+use crate::near::util::{
+    get_accounts_from_path, get_default_near_account_dir_path, print_transaction_status,
+};
+use crate::CliResult;
+use dialoguer::Input;
+use indicatif::ProgressBar;
+use near_crypto::InMemorySigner;
+use std::path::Path;
+
+#[derive(Debug, Clone, interactive_clap_derive::InteractiveClap)]
+#[interactive_clap(context = ())]
+pub struct RestoreSnapshot {
+    #[interactive_clap(long)]
+    pub snapshot_file: String,
+    #[interactive_clap(long)]
+    pub helper_wasm_path: String,
+    confirm: String,
+}
+
+impl RestoreSnapshot {
+    pub fn input_snapshot_file(_context: &()) -> color_eyre::eyre::Result<String> {
+        Ok(Input::new()
+            .with_prompt("What is the snapshot file path?")
+            .interact_text()?)
+    }
+
+    pub fn input_helper_wasm_path(_context: &()) -> color_eyre::eyre::Result<String> {
+        Ok(Input::new()
+            .with_prompt(
+                "What is the wasm path of the restore helper contract(it must expose `replace`)?",
+            )
+            .interact_text()?)
+    }
+
+    fn input_confirm(_context: &()) -> color_eyre::eyre::Result<String> {
+        Ok(Input::new()
+            .with_prompt("Please confirm you want to overwrite the account code and state?[y/n]")
+            .interact_text()?)
+    }
+
+    pub async fn process(self, connection_config: NearEnv, client: Client) -> CliResult {
+        let snapshot =
+            ContractSnapshot::load(Path::new(self.snapshot_file.as_str())).map_err(|err| {
+                color_eyre::Report::msg(format!(
+                    "Failed to load snapshot file {}, error: {}",
+                    self.snapshot_file, err
+                ))
+            })?;
+        let helper_wasm =
+            std::fs::read(Path::new(self.helper_wasm_path.as_str())).map_err(|err| {
+                color_eyre::Report::msg(format!(
+                    "Failed to read restore helper wasm file {}, error: {}",
+                    self.helper_wasm_path, err
+                ))
+            })?;
+
+        let signer: InMemorySigner = get_accounts_from_path(
+            get_default_near_account_dir_path(&connection_config).as_path(),
+        )?
+        .into_iter()
+        .find(|e| e.account_id == snapshot.account_id)
+        .ok_or_else(|| {
+            color_eyre::Report::msg(format!(
+                "Failed to find account {} in your system.",
+                snapshot.account_id
+            ))
+        })?
+        .into();
+
+        println!(
+            "Snapshot of {} taken at block {} ({}): code hash {}, {} state entries",
+            snapshot.account_id,
+            snapshot.block_height,
+            snapshot.created_at,
+            snapshot.code_hash,
+            snapshot.state.len()
+        );
+        if !(self.confirm.eq("y") || self.confirm.eq("Y")) {
+            println!("Cancel restore snapshot!");
+            return Ok(());
+        }
+        if let Err(err) = snapshot.ensure_complete() {
+            println!("{}", err);
+            let confirm: String = Input::new()
+                .with_prompt("Restore without these keys?[y/n]")
+                .interact_text()?;
+            if !(confirm.eq("y") || confirm.eq("Y")) {
+                println!("Cancel restore snapshot!");
+                return Ok(());
+            }
+        }
+
+        let clean_contract = CleanStateContract {
+            account_id: signer.account_id.clone(),
+            client: &client,
+        };
+        let resumed = clean_contract.is_deployed().await.map_err(|error| {
+            color_eyre::Report::msg(format!(
+                "Failed to view code of account({}), error: {}",
+                signer.account_id, error
+            ))
+        })?;
+        if !resumed {
+            clean_contract.deploy(&signer).await.map_err(|error| {
+                color_eyre::Report::msg(format!(
+                    "Failed to deploy clean state contract for account({}), error: {}",
+                    signer.account_id, error
+                ))
+            })?;
+        }
+        let progress = ProgressBar::new(0).with_prefix(signer.account_id.to_string());
+        clean_contract
+            .clean_up_all(&signer, DEFAULT_CLEAN_GAS_BUDGET, &progress)
+            .await
+            .map_err(|error| {
+                color_eyre::Report::msg(format!(
+                    "Failed to clean account({}) states, error: {}",
+                    signer.account_id, error
+                ))
+            })?;
+
+        let restore_contract = StateRestoreContract {
+            account_id: signer.account_id.clone(),
+            client: &client,
+        };
+        restore_contract
+            .deploy(&signer, helper_wasm)
+            .await
+            .map_err(|error| {
+                color_eyre::Report::msg(format!(
+                    "Failed to deploy restore helper contract for account({}), error: {}",
+                    signer.account_id, error
+                ))
+            })?;
+        restore_contract
+            .replace_all(&signer, &snapshot.state)
+            .await
+            .map_err(|error| {
+                color_eyre::Report::msg(format!(
+                    "Failed to restore account({}) states, error: {}",
+                    signer.account_id, error
+                ))
+            })?;
+
+        let code = snapshot.code_bytes().map_err(|error| {
+            color_eyre::Report::msg(format!("Invalid code in snapshot, error: {}", error))
+        })?;
+        if code.is_empty() {
+            println!(
+                "The snapshot has no contract code, the restore helper contract is left deployed on {}.",
+                signer.account_id
+            );
+        } else {
+            let outcome = client.deploy(&signer, code).await.map_err(|error| {
+                color_eyre::Report::msg(format!(
+                    "Failed to redeploy original code to account({}), error: {}",
+                    signer.account_id, error
+                ))
+            })?;
+            print_transaction_status(outcome, connection_config.clone());
+        }
+
+        let code_hash = client
+            .view_code_hash(signer.account_id.clone())
+            .await
+            .map_err(|error| {
+                color_eyre::Report::msg(format!(
+                    "Failed to view account({}) code, error: {}",
+                    signer.account_id, error
+                ))
+            })?;
+        println!(
+            "Restored {}: code hash is {}, expected {}",
+            signer.account_id,
+            code_hash
+                .map(|hash| hash.to_string())
+                .unwrap_or_else(|| "<no contract>".to_string()),
+            snapshot.code_hash
+        );
+        Ok(())
+    }
+}
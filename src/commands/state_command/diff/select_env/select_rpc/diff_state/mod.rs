@@ -0,0 +1,100 @@
+use crate::commands::state_command::dump::select_env::select_rpc::dump_state::{
+    parse_block_height, LATEST_BLOCK,
+};
+use crate::near::rpc::client::Client;
+use crate::near::state_dump::{print_state_changes, StateDump};
+use crate::near::types::NearEnv;
+use crate::CliResult;
+use dialoguer::Input;
+use near_primitives::types::AccountId;
+use std::path::Path;
+
+pub const ACCOUNT_OF_DUMPS: &str = "none";
+
+#[derive(Debug, Clone, interactive_clap_derive::InteractiveClap)]
+#[interactive_clap(context = ())]
+pub struct DiffState {
+    #[interactive_clap(long)]
+    pub account_id: String,
+    #[interactive_clap(long)]
+    pub from: String,
+    #[interactive_clap(long)]
+    pub to: String,
+}
+
+impl DiffState {
+    pub fn input_account_id(_context: &()) -> color_eyre::eyre::Result<String> {
+        Ok(Input::new()
+            .with_prompt(
+                "Which contract to compare at block heights, or 'none' to compare dump files?",
+            )
+            .default(ACCOUNT_OF_DUMPS.to_string())
+            .interact_text()?)
+    }
+
+    pub fn input_from(_context: &()) -> color_eyre::eyre::Result<String> {
+        Ok(Input::new()
+            .with_prompt("Compare from which dump file or block height?")
+            .interact_text()?)
+    }
+
+    pub fn input_to(_context: &()) -> color_eyre::eyre::Result<String> {
+        Ok(Input::new()
+            .with_prompt("Compare to which dump file or block height?")
+            .default(LATEST_BLOCK.to_string())
+            .interact_text()?)
+    }
+
+    /// Reads the state at a block height (or `latest`), or else loads a dump file.
+    async fn load_dump(
+        &self,
+        client: &Client,
+        source: &str,
+    ) -> color_eyre::eyre::Result<StateDump> {
+        let block_height = match parse_block_height(source) {
+            Ok(block_height) => block_height,
+            Err(_) => {
+                return StateDump::load(Path::new(source)).map_err(|err| {
+                    color_eyre::Report::msg(format!(
+                        "Failed to load dump {}, error: {}",
+                        source, err
+                    ))
+                })
+            }
+        };
+        if self.account_id.eq(ACCOUNT_OF_DUMPS) {
+            return Err(color_eyre::Report::msg(format!(
+                "Comparing at block height {} needs --account-id",
+                source
+            )));
+        }
+        let account_id: AccountId = self.account_id.parse().map_err(|err| {
+            color_eyre::Report::msg(format!(
+                "Invalid account id {}, error: {}",
+                self.account_id, err
+            ))
+        })?;
+        StateDump::fetch(client, account_id.clone(), block_height)
+            .await
+            .map_err(|err| {
+                color_eyre::Report::msg(format!(
+                    "Failed to read state of {} at {}, error: {}",
+                    account_id, source, err
+                ))
+            })
+    }
+
+    pub async fn process(self, _connection_config: NearEnv, client: Client) -> CliResult {
+        let from = self.load_dump(&client, &self.from).await?;
+        let to = self.load_dump(&client, &self.to).await?;
+        if from.account_id != to.account_id {
+            println!(
+                "Comparing state of different accounts: {} and {}",
+                from.account_id, to.account_id
+            );
+        }
+        let changes = from.diff(&to);
+        print_state_changes(&from, &to, &changes);
+        Ok(())
+    }
+}
@@ -0,0 +1,112 @@
+use crate::near::rpc::client::Client;
+use crate::near::state_dump::{DumpFormat, StateDump};
+use crate::near::types::NearEnv;
+use crate::CliResult;
+use dialoguer::Input;
+use near_primitives::types::{AccountId, BlockHeight};
+use std::path::{Path, PathBuf};
+
+pub const LATEST_BLOCK: &str = "latest";
+pub const DEFAULT_OUTPUT: &str = "default";
+
+#[derive(Debug, Clone, interactive_clap_derive::InteractiveClap)]
+#[interactive_clap(context = ())]
+pub struct DumpState {
+    #[interactive_clap(long)]
+    pub account_id: String,
+    #[interactive_clap(long)]
+    pub block_height: String,
+    #[interactive_clap(long)]
+    pub format: String,
+    #[interactive_clap(long)]
+    pub output: String,
+}
+
+/// Parses a `--block-height` option, `latest` stands for the latest block.
+pub fn parse_block_height(block_height: &str) -> color_eyre::eyre::Result<Option<BlockHeight>> {
+    if block_height.trim().eq_ignore_ascii_case(LATEST_BLOCK) {
+        return Ok(None);
+    }
+    block_height.trim().parse().map(Some).map_err(|_| {
+        color_eyre::Report::msg(format!(
+            "Invalid block height '{}', expected a number or 'latest'",
+            block_height
+        ))
+    })
+}
+
+impl DumpState {
+    pub fn input_account_id(_context: &()) -> color_eyre::eyre::Result<String> {
+        Ok(Input::new()
+            .with_prompt("Which contract to dump?")
+            .interact_text()?)
+    }
+
+    pub fn input_block_height(_context: &()) -> color_eyre::eyre::Result<String> {
+        Ok(Input::new()
+            .with_prompt("At which block height (old blocks need an archival rpc)?")
+            .default(LATEST_BLOCK.to_string())
+            .interact_text()?)
+    }
+
+    pub fn input_format(_context: &()) -> color_eyre::eyre::Result<String> {
+        Ok(Input::new()
+            .with_prompt("Dump as json or csv?")
+            .default("json".to_string())
+            .interact_text()?)
+    }
+
+    pub fn input_output(_context: &()) -> color_eyre::eyre::Result<String> {
+        Ok(Input::new()
+            .with_prompt(
+                "Where to save the dump ('default' is <account_id>_<block_height>.<format>)?",
+            )
+            .default(DEFAULT_OUTPUT.to_string())
+            .interact_text()?)
+    }
+
+    pub async fn process(self, _connection_config: NearEnv, client: Client) -> CliResult {
+        let account_id: AccountId = self.account_id.parse().map_err(|err| {
+            color_eyre::Report::msg(format!(
+                "Invalid account id {}, error: {}",
+                self.account_id, err
+            ))
+        })?;
+        let block_height = parse_block_height(&self.block_height)?;
+        let format: DumpFormat = self.format.parse().map_err(color_eyre::Report::msg)?;
+        if !self.output.eq(DEFAULT_OUTPUT) {
+            format
+                .check_path(Path::new(self.output.as_str()))
+                .map_err(|err| color_eyre::Report::msg(err.to_string()))?;
+        }
+
+        let dump = StateDump::fetch(&client, account_id.clone(), block_height)
+            .await
+            .map_err(|err| {
+                color_eyre::Report::msg(format!(
+                    "Failed to read state of {}, error: {}",
+                    account_id, err
+                ))
+            })?;
+        let path = if self.output.eq(DEFAULT_OUTPUT) {
+            PathBuf::from(dump.default_file_name(format))
+        } else {
+            PathBuf::from(self.output.as_str())
+        };
+        dump.save(&path, format).map_err(|err| {
+            color_eyre::Report::msg(format!(
+                "Failed to save dump to {}, error: {}",
+                path.display(),
+                err
+            ))
+        })?;
+        println!(
+            "Dumped {} keys of {} at block {} to {}",
+            dump.entries.len(),
+            dump.account_id,
+            dump.block_height,
+            path.display()
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,39 @@
+use strum::{EnumDiscriminants, EnumIter, EnumMessage};
+
+pub mod diff;
+pub mod dump;
+
+#[derive(Debug, Clone, interactive_clap_derive::InteractiveClap)]
+#[interactive_clap(context = ())]
+pub struct StateCommand {
+    #[interactive_clap(subcommand)]
+    action: StateAction,
+}
+
+impl StateCommand {
+    pub async fn process(self) -> crate::CliResult {
+        self.action.process().await
+    }
+}
+
+#[derive(Debug, Clone, EnumDiscriminants, interactive_clap_derive::InteractiveClap)]
+#[strum_discriminants(derive(EnumMessage, EnumIter))]
+#[interactive_clap(context = ())]
+///Choose state action
+pub enum StateAction {
+    #[strum_discriminants(strum(message = "Dump the key/value state of a contract"))]
+    Dump(self::dump::Dump),
+    #[strum_discriminants(strum(
+        message = "Compare the state of a contract between two dumps or block heights"
+    ))]
+    Diff(self::diff::Diff),
+}
+
+impl StateAction {
+    pub async fn process(self) -> crate::CliResult {
+        match self {
+            StateAction::Dump(dump) => dump.process().await,
+            StateAction::Diff(diff) => diff.process().await,
+        }
+    }
+}
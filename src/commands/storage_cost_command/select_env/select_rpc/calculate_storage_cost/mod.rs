@@ -0,0 +1,79 @@
+use crate::near::rpc::client::{Client, DEFAULT_DEPLOY_AND_INIT_GAS};
+use crate::near::storage::{
+    gas_reserve, print_storage_cost_table, storage_amount_per_byte, StorageCost,
+};
+use crate::near::types::{NearBalance, NearEnv};
+use crate::CliResult;
+use dialoguer::Input;
+use near_primitives::types::AccountId;
+use std::path::Path;
+
+#[derive(Debug, Clone, interactive_clap_derive::InteractiveClap)]
+#[interactive_clap(context = ())]
+pub struct CalculateStorageCost {
+    #[interactive_clap(long)]
+    pub account_ids: String,
+    #[interactive_clap(long)]
+    pub wasm_path: String,
+}
+
+impl CalculateStorageCost {
+    pub fn input_account_ids(_context: &()) -> color_eyre::eyre::Result<String> {
+        Ok(Input::new()
+            .with_prompt("Input accounts to deploy to(eg: a.testnet,b.testnet,c.testnet)")
+            .interact_text()?)
+    }
+
+    pub fn input_wasm_path(_context: &()) -> color_eyre::eyre::Result<String> {
+        Ok(Input::new()
+            .with_prompt("What is the new wasm path?")
+            .interact_text()?)
+    }
+
+    pub async fn process(self, _connection_config: NearEnv, client: Client) -> CliResult {
+        let code = std::fs::read(Path::new(self.wasm_path.as_str())).map_err(|err| {
+            color_eyre::Report::msg(format!(
+                "Failed to read wasm file {}, error: {}",
+                self.wasm_path, err
+            ))
+        })?;
+        let storage_amount_per_byte = storage_amount_per_byte(&client).await;
+        println!(
+            "Storage price: {} per byte",
+            NearBalance::from_yoctonear(storage_amount_per_byte)
+        );
+
+        // An upgrade deploys with `deploy_and_init`, its attached gas is paid from the balance.
+        let deploy_gas_reserve = gas_reserve(&client, DEFAULT_DEPLOY_AND_INIT_GAS)
+            .await
+            .map_err(|err| {
+                color_eyre::Report::msg(format!("Failed to read the gas price, error: {}", err))
+            })?;
+        let mut storage_costs = vec![];
+        for account_id in self.account_ids.split(',') {
+            let account_id: AccountId = account_id.trim().parse()?;
+            let storage_cost = StorageCost::fetch(
+                &client,
+                account_id.clone(),
+                code.len() as u64,
+                storage_amount_per_byte,
+                deploy_gas_reserve,
+            )
+            .await
+            .map_err(|err| {
+                color_eyre::Report::msg(format!(
+                    "Failed to calculate storage cost of {}, error: {}",
+                    account_id, err
+                ))
+            })?;
+            storage_costs.push(storage_cost);
+        }
+        print_storage_cost_table(&storage_costs);
+        for storage_cost in &storage_costs {
+            if let Err(err) = storage_cost.check() {
+                println!("{}", err);
+            }
+        }
+        Ok(())
+    }
+}
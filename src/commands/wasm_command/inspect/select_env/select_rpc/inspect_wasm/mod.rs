@@ -0,0 +1,77 @@
+use crate::near::rpc::client::Client;
+use crate::near::types::NearEnv;
+use crate::near::wasm::{max_contract_size, WasmInspection};
+use crate::CliResult;
+use color_eyre::owo_colors::OwoColorize;
+use dialoguer::Input;
+use std::path::Path;
+
+pub const NO_METHOD: &str = "none";
+
+#[derive(Debug, Clone, interactive_clap_derive::InteractiveClap)]
+#[interactive_clap(context = ())]
+pub struct InspectWasm {
+    #[interactive_clap(long)]
+    pub wasm_path: String,
+    #[interactive_clap(long)]
+    pub method_name: String,
+}
+
+impl InspectWasm {
+    pub fn input_wasm_path(_context: &()) -> color_eyre::eyre::Result<String> {
+        Ok(Input::new()
+            .with_prompt("What is the wasm path?")
+            .interact_text()?)
+    }
+
+    pub fn input_method_name(_context: &()) -> color_eyre::eyre::Result<String> {
+        Ok(Input::new()
+            .with_prompt("Which method must be exported(eg: the migrate method), or 'none'?")
+            .default(NO_METHOD.to_string())
+            .interact_text()?)
+    }
+
+    pub async fn process(self, _connection_config: NearEnv, client: Client) -> CliResult {
+        let code = std::fs::read(Path::new(self.wasm_path.as_str())).map_err(|err| {
+            color_eyre::Report::msg(format!(
+                "Failed to read wasm file {}, error: {}",
+                self.wasm_path, err
+            ))
+        })?;
+        let inspection = WasmInspection::inspect(&code).map_err(|err| {
+            color_eyre::Report::msg(format!(
+                "Failed to parse wasm file {}, error: {}",
+                self.wasm_path, err
+            ))
+        })?;
+        let max_contract_size = max_contract_size(&client).await;
+        inspection.print(max_contract_size);
+
+        let mut problems = vec![];
+        if let Err(err) = inspection.check_size(max_contract_size) {
+            problems.push(err.to_string());
+        }
+        if !inspection.import_problems.is_empty() {
+            problems.push(format!(
+                "{} imports are not provided by the NEAR runtime",
+                inspection.import_problems.len()
+            ));
+        }
+        if !self.method_name.eq_ignore_ascii_case(NO_METHOD) {
+            match inspection.check_method(&self.method_name) {
+                Ok(()) => println!("method {}: {}", self.method_name, "ok".green()),
+                Err(err) => problems.push(err.to_string()),
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(color_eyre::Report::msg(format!(
+                "The wasm {} is not ready to deploy:\n{}",
+                self.wasm_path,
+                problems.join("\n")
+            )))
+        }
+    }
+}
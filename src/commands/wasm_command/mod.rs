@@ -0,0 +1,35 @@
+use strum::{EnumDiscriminants, EnumIter, EnumMessage};
+
+pub mod inspect;
+
+#[derive(Debug, Clone, interactive_clap_derive::InteractiveClap)]
+#[interactive_clap(context = ())]
+pub struct WasmCommand {
+    #[interactive_clap(subcommand)]
+    action: WasmAction,
+}
+
+impl WasmCommand {
+    pub async fn process(self) -> crate::CliResult {
+        self.action.process().await
+    }
+}
+
+#[derive(Debug, Clone, EnumDiscriminants, interactive_clap_derive::InteractiveClap)]
+#[strum_discriminants(derive(EnumMessage, EnumIter))]
+#[interactive_clap(context = ())]
+///Choose wasm action
+pub enum WasmAction {
+    #[strum_discriminants(strum(
+        message = "Inspect exports, imports and size of a wasm before deploying it"
+    ))]
+    Inspect(self::inspect::Inspect),
+}
+
+impl WasmAction {
+    pub async fn process(self) -> crate::CliResult {
+        match self {
+            WasmAction::Inspect(inspect) => inspect.process().await,
+        }
+    }
+}
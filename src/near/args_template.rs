@@ -0,0 +1,209 @@
+use crate::near::rpc::client::Client;
+use crate::near::view_check::select_json_path;
+use near_primitives::types::AccountId;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// A placeholder in migration args, written as `{{...}}`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Placeholder {
+    /// `{{account_id}}`, the account being upgraded.
+    AccountId,
+    /// `{{appchain_id}}`, the first label of the account, eg: `myappchain` of
+    /// `myappchain.registry.testnet`.
+    AppchainId,
+    /// `{{view:<method>}}` or `{{view:<method>:<json path>}}`, the result of a view method
+    /// of the account without args.
+    View {
+        method: String,
+        path: Option<String>,
+    },
+}
+
+impl Placeholder {
+    fn parse(placeholder: &str) -> anyhow::Result<Self> {
+        let placeholder = placeholder.trim();
+        match placeholder {
+            "account_id" => Ok(Placeholder::AccountId),
+            "appchain_id" => Ok(Placeholder::AppchainId),
+            _ => match placeholder.strip_prefix("view:") {
+                Some(view) => {
+                    let (method, path) = match view.split_once(':') {
+                        Some((method, path)) => (method, Some(path.trim().to_string())),
+                        None => (view, None),
+                    };
+                    if method.trim().is_empty() {
+                        anyhow::bail!("Missing view method in {{{{{}}}}}", placeholder);
+                    }
+                    Ok(Placeholder::View {
+                        method: method.trim().to_string(),
+                        path,
+                    })
+                }
+                None => anyhow::bail!("Unknown placeholder {{{{{}}}}}", placeholder),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Segment {
+    Text(String),
+    Placeholder(Placeholder),
+}
+
+/// Migration args with placeholders which are rendered for every account.
+///
+/// A placeholder expands to plain text: strings without their quotes, so
+/// `"{{appchain_id}}"` renders to a JSON string, other values to their JSON. The rendered
+/// args must be valid JSON.
+#[derive(Debug, Clone)]
+pub struct ArgsTemplate {
+    segments: Vec<Segment>,
+}
+
+impl ArgsTemplate {
+    pub fn parse(template: &str) -> anyhow::Result<Self> {
+        let mut segments = vec![];
+        let mut rest = template;
+        while let Some(start) = rest.find("{{") {
+            if start > 0 {
+                segments.push(Segment::Text(rest[..start].to_string()));
+            }
+            let end = rest[start..]
+                .find("}}")
+                .ok_or_else(|| anyhow::anyhow!("Unclosed placeholder in args: {}", template))?;
+            segments.push(Segment::Placeholder(Placeholder::parse(
+                &rest[start + 2..start + end],
+            )?));
+            rest = &rest[start + end + 2..];
+        }
+        if !rest.is_empty() {
+            segments.push(Segment::Text(rest.to_string()));
+        }
+        let args_template = Self { segments };
+        if !args_template.has_placeholders() {
+            args_template.render_with(&"placeholder.near".parse()?, &HashMap::new())?;
+        }
+        Ok(args_template)
+    }
+
+    pub fn from_file(path: &Path) -> anyhow::Result<Self> {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn has_placeholders(&self) -> bool {
+        self.segments
+            .iter()
+            .any(|segment| matches!(segment, Segment::Placeholder(_)))
+    }
+
+    fn view_methods(&self) -> Vec<&str> {
+        let mut methods = vec![];
+        for segment in &self.segments {
+            if let Segment::Placeholder(Placeholder::View { method, .. }) = segment {
+                if !methods.contains(&method.as_str()) {
+                    methods.push(method.as_str());
+                }
+            }
+        }
+        methods
+    }
+
+    /// Renders the args for `account_id`, calling every view method once.
+    pub async fn render(&self, client: &Client, account_id: &AccountId) -> anyhow::Result<String> {
+        let mut views = HashMap::new();
+        for method in self.view_methods() {
+            let value: serde_json::Value = client
+                .view(account_id.clone(), method.to_string(), b"{}".to_vec())
+                .await
+                .map_err(|err| {
+                    anyhow::anyhow!("Failed to view {} of {}: {}", method, account_id, err)
+                })?
+                .json()?;
+            views.insert(method.to_string(), value);
+        }
+        self.render_with(account_id, &views)
+    }
+
+    fn render_with(
+        &self,
+        account_id: &AccountId,
+        views: &HashMap<String, serde_json::Value>,
+    ) -> anyhow::Result<String> {
+        let mut args = String::new();
+        for segment in &self.segments {
+            match segment {
+                Segment::Text(text) => args.push_str(text),
+                Segment::Placeholder(Placeholder::AccountId) => args.push_str(account_id.as_str()),
+                Segment::Placeholder(Placeholder::AppchainId) => args.push_str(
+                    account_id
+                        .as_str()
+                        .split('.')
+                        .next()
+                        .unwrap_or_else(|| account_id.as_str()),
+                ),
+                Segment::Placeholder(Placeholder::View { method, path }) => {
+                    let result = views
+                        .get(method)
+                        .ok_or_else(|| anyhow::anyhow!("Missing view result of {}", method))?;
+                    let value = match path {
+                        Some(path) => select_json_path(result, path)?.ok_or_else(|| {
+                            anyhow::anyhow!("{} of {} has nothing at {}", method, account_id, path)
+                        })?,
+                        None => result,
+                    };
+                    match value {
+                        serde_json::Value::String(value) => {
+                            let quoted = serde_json::to_string(value)?;
+                            args.push_str(&quoted[1..quoted.len() - 1]);
+                        }
+                        value => args.push_str(&value.to_string()),
+                    }
+                }
+            }
+        }
+        serde_json::from_str::<serde_json::Value>(&args).map_err(|err| {
+            anyhow::anyhow!(
+                "Args of {} are not valid JSON ({}): {}",
+                account_id,
+                err,
+                args
+            )
+        })?;
+        Ok(args)
+    }
+}
+
+#[test]
+fn test_render_args_template() {
+    let account_id: AccountId = "myappchain.registry.testnet".parse().unwrap();
+    let template = ArgsTemplate::parse(
+        r#"{"anchor": "{{account_id}}", "id": "{{ appchain_id }}", "version": "{{view:get_anchor_version}}", "era": {{view:get_anchor_status:$.era_number}}}"#,
+    )
+    .unwrap();
+    assert_eq!(
+        template.view_methods(),
+        vec!["get_anchor_version", "get_anchor_status"]
+    );
+    let mut views = HashMap::new();
+    views.insert(
+        "get_anchor_version".to_string(),
+        serde_json::json!("v2.1.0"),
+    );
+    views.insert(
+        "get_anchor_status".to_string(),
+        serde_json::json!({"era_number": 12}),
+    );
+    assert_eq!(
+        template.render_with(&account_id, &views).unwrap(),
+        r#"{"anchor": "myappchain.registry.testnet", "id": "myappchain", "version": "v2.1.0", "era": 12}"#
+    );
+
+    assert!(ArgsTemplate::parse("{}").is_ok());
+    assert!(ArgsTemplate::parse("{\"a\": 1").is_err());
+    assert!(ArgsTemplate::parse("{\"a\": \"{{unknown}}\"}").is_err());
+    assert!(ArgsTemplate::parse("{\"a\": \"{{account_id\"}").is_err());
+    let unquoted = ArgsTemplate::parse("{\"a\": {{account_id}}}").unwrap();
+    assert!(unquoted.render_with(&account_id, &HashMap::new()).is_err());
+}
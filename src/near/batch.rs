@@ -0,0 +1,232 @@
+use crate::near::gas::NearGas;
+use crate::near::rpc::result::CallExecutionDetails;
+use color_eyre::owo_colors::OwoColorize;
+use futures::stream::{self, StreamExt};
+use near_crypto::InMemorySigner;
+use near_primitives::hash::CryptoHash;
+use near_primitives::types::{AccountId, Gas};
+use near_primitives::views::FinalExecutionOutcomeView;
+use prettytable::{row, Table};
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccountStatus {
+    Succeeded,
+    Skipped,
+    Failed,
+    RolledBack,
+    NotStarted,
+}
+
+impl std::fmt::Display for AccountStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AccountStatus::Succeeded => write!(f, "{}", "succeeded".green()),
+            AccountStatus::Skipped => write!(f, "{}", "skipped".green()),
+            AccountStatus::Failed => write!(f, "{}", "failed".red()),
+            AccountStatus::RolledBack => write!(f, "{}", "rolled back".yellow()),
+            AccountStatus::NotStarted => write!(f, "{}", "not started".yellow()),
+        }
+    }
+}
+
+/// The result of running a command on one account, collected for the summary table.
+#[derive(Debug, Clone)]
+pub struct AccountOutcome {
+    pub account_id: AccountId,
+    pub status: AccountStatus,
+    /// The last transaction sent for the account.
+    pub tx_hash: Option<CryptoHash>,
+    /// Gas burnt by all transactions sent for the account.
+    pub gas_burnt: Gas,
+    pub error: Option<String>,
+}
+
+impl AccountOutcome {
+    pub fn new(account_id: AccountId) -> Self {
+        Self {
+            account_id,
+            status: AccountStatus::Succeeded,
+            tx_hash: None,
+            gas_burnt: 0,
+            error: None,
+        }
+    }
+
+    pub fn record_transaction(&mut self, outcome: &FinalExecutionOutcomeView) {
+        self.tx_hash = Some(outcome.transaction.hash);
+        self.gas_burnt += CallExecutionDetails::from(outcome.clone()).total_gas_burnt;
+    }
+
+    pub fn fail(&mut self, error: impl ToString) {
+        self.status = AccountStatus::Failed;
+        self.error = Some(error.to_string());
+    }
+
+    pub fn is_failure(&self) -> bool {
+        matches!(
+            self.status,
+            AccountStatus::Failed | AccountStatus::RolledBack
+        )
+    }
+}
+
+/// Runs `task` for every signer with at most `concurrency` accounts in flight.
+///
+/// Unless `continue_on_error` is set, accounts which are not started yet when a task fails
+/// are reported as `NotStarted`. A rollback stops the run even with `continue_on_error`, a
+/// rolled back account means the rollout itself is broken. Outcomes are returned in the order
+/// of `signers`.
+pub async fn run_for_signers<F, Fut>(
+    signers: Vec<InMemorySigner>,
+    concurrency: usize,
+    continue_on_error: bool,
+    task: F,
+) -> Vec<AccountOutcome>
+where
+    F: Fn(InMemorySigner) -> Fut,
+    Fut: Future<Output = AccountOutcome>,
+{
+    let aborted = AtomicBool::new(false);
+    let aborted = &aborted;
+    let rolled_back = AtomicBool::new(false);
+    let rolled_back = &rolled_back;
+    let task = &task;
+    stream::iter(signers.into_iter().map(|signer| async move {
+        if rolled_back.load(Ordering::SeqCst) || aborted.load(Ordering::SeqCst) {
+            let mut outcome = AccountOutcome::new(signer.account_id);
+            outcome.status = AccountStatus::NotStarted;
+            if rolled_back.load(Ordering::SeqCst) {
+                outcome.error = Some("Skipped after a rollback".to_string());
+            }
+            return outcome;
+        }
+        let outcome = task(signer).await;
+        if outcome.status == AccountStatus::RolledBack {
+            rolled_back.store(true, Ordering::SeqCst);
+        }
+        if outcome.is_failure() && !continue_on_error {
+            aborted.store(true, Ordering::SeqCst);
+        }
+        outcome
+    }))
+    .buffered(concurrency.max(1))
+    .collect()
+    .await
+}
+
+/// Prints the summary table and fails if any account failed.
+pub fn print_account_outcomes(outcomes: &[AccountOutcome]) -> crate::CliResult {
+    let mut table = Table::new();
+    table.set_titles(row!["account", "status", "tx hash", "gas used", "error"]);
+    for outcome in outcomes {
+        table.add_row(row![
+            outcome.account_id,
+            outcome.status,
+            outcome
+                .tx_hash
+                .map(|hash| hash.to_string())
+                .unwrap_or_default(),
+            if outcome.gas_burnt > 0 {
+                NearGas::from(outcome.gas_burnt).to_string()
+            } else {
+                String::new()
+            },
+            outcome.error.clone().unwrap_or_default()
+        ]);
+    }
+    table.printstd();
+
+    let failed = outcomes.iter().filter(|e| e.is_failure()).count();
+    let not_started = outcomes
+        .iter()
+        .filter(|e| e.status == AccountStatus::NotStarted)
+        .count();
+    if failed > 0 {
+        return Err(color_eyre::Report::msg(format!(
+            "{} of {} accounts failed, {} not started",
+            failed,
+            outcomes.len(),
+            not_started
+        )));
+    }
+    Ok(())
+}
+
+/// Parses the `--concurrency` option, which must be at least 1.
+pub fn parse_concurrency(concurrency: &str) -> color_eyre::eyre::Result<usize> {
+    match concurrency.trim().parse::<usize>() {
+        Ok(concurrency) if concurrency > 0 => Ok(concurrency),
+        _ => Err(color_eyre::Report::msg(format!(
+            "Invalid concurrency '{}', expected a number greater than 0",
+            concurrency
+        ))),
+    }
+}
+
+#[tokio::test]
+async fn test_run_for_signers_stops_after_failure() {
+    let signers: Vec<InMemorySigner> = ["a.testnet", "b.testnet", "c.testnet"]
+        .iter()
+        .map(|account_id| {
+            InMemorySigner::from_seed(
+                account_id.parse().unwrap(),
+                near_crypto::KeyType::ED25519,
+                account_id,
+            )
+        })
+        .collect();
+    let task = |signer: InMemorySigner| async move {
+        let mut outcome = AccountOutcome::new(signer.account_id.clone());
+        if signer.account_id.as_str() == "a.testnet" {
+            outcome.fail("boom");
+        }
+        outcome
+    };
+
+    let outcomes = run_for_signers(signers.clone(), 1, false, task).await;
+    let statuses: Vec<AccountStatus> = outcomes.iter().map(|e| e.status).collect();
+    assert_eq!(
+        statuses,
+        vec![
+            AccountStatus::Failed,
+            AccountStatus::NotStarted,
+            AccountStatus::NotStarted
+        ]
+    );
+
+    let outcomes = run_for_signers(signers.clone(), 2, true, task).await;
+    let statuses: Vec<AccountStatus> = outcomes.iter().map(|e| e.status).collect();
+    assert_eq!(
+        statuses,
+        vec![
+            AccountStatus::Failed,
+            AccountStatus::Succeeded,
+            AccountStatus::Succeeded
+        ]
+    );
+    assert!(print_account_outcomes(&outcomes).is_err());
+
+    let task = |signer: InMemorySigner| async move {
+        let mut outcome = AccountOutcome::new(signer.account_id.clone());
+        if signer.account_id.as_str() == "a.testnet" {
+            outcome.status = AccountStatus::RolledBack;
+        }
+        outcome
+    };
+    let outcomes = run_for_signers(signers, 1, true, task).await;
+    let statuses: Vec<AccountStatus> = outcomes.iter().map(|e| e.status).collect();
+    assert_eq!(
+        statuses,
+        vec![
+            AccountStatus::RolledBack,
+            AccountStatus::NotStarted,
+            AccountStatus::NotStarted
+        ]
+    );
+    assert_eq!(
+        outcomes[1].error.as_deref(),
+        Some("Skipped after a rollback")
+    );
+}
@@ -33,3 +33,10 @@ pub const DIR_NAME_TESTNET: &str = ".near-credentials/testnet/";
 pub const DIR_NAME_MAINNET: &str = ".near-credentials/mainnet/";
 pub const DIR_NAME_BETANET: &str = ".near-credentials/betanet/";
 pub const DIR_NAME_CUSTOM: &str = ".near-credentials/default/";
+
+pub const DIR_NAME_TESTNET_SNAPSHOT: &str = ".oct-cli/snapshots/testnet/";
+pub const DIR_NAME_MAINNET_SNAPSHOT: &str = ".oct-cli/snapshots/mainnet/";
+
+pub const TESTNET_OCT_TOKEN_ACCOUNT_ID: &str = "oct.beta_oct_relay.testnet";
+pub const MAINNET_OCT_TOKEN_ACCOUNT_ID: &str =
+    "f5cfbc74057c610c8ef151a439252680ac68c6dc.factory.bridge.near";
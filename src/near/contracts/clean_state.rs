@@ -1,14 +1,93 @@
-use crate::near::constants::ONE_TERA_GAS;
+use crate::near::constants::{ONE_GIGA_GAS, ONE_TERA_GAS};
+use crate::near::gas::NearGas;
 use crate::near::rpc::client::Client;
-use itertools::Itertools;
+use crate::near::rpc::result::CallExecutionDetails;
+use crate::near::rpc::tool::into_state_map;
+use crate::near::state_key::KeyFilter;
+use indicatif::{ProgressBar, ProgressStyle};
 use near_crypto::InMemorySigner;
-use near_primitives::types::AccountId;
+use near_primitives::hash::CryptoHash;
+use near_primitives::types::{AccountId, Gas};
 use near_primitives::views::FinalExecutionOutcomeView;
 use serde_json::json;
-use std::ops::Mul;
 
 pub const STATE_CLEANUP_WASM: &[u8] = include_bytes!("../../../res/state_cleanup.wasm");
 
+/// Gas attached to every `clean` call.
+pub const CLEAN_GAS: Gas = 300 * ONE_TERA_GAS;
+/// The default estimated gas of the keys removed by one `clean` call.
+pub const DEFAULT_CLEAN_GAS_BUDGET: Gas = 200 * ONE_TERA_GAS;
+/// Estimated gas to receive a `clean` call and parse its args.
+const CLEAN_BASE_GAS: Gas = 5 * ONE_TERA_GAS;
+/// Estimated gas to remove one key, the `storage_remove` base cost with some headroom.
+const CLEAN_GAS_PER_KEY: Gas = 100 * ONE_GIGA_GAS;
+/// Estimated gas for every byte of a removed key and its value.
+const CLEAN_GAS_PER_BYTE: Gas = 50_000_000;
+/// Keeps the args of a `clean` call well under the 4 MiB limit of function call args.
+const MAX_CLEAN_ARGS_BYTES: usize = 2 * 1024 * 1024;
+
+/// A state key to remove.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CleanKey {
+    pub key: Vec<u8>,
+    /// Bytes of the key and its value.
+    pub size: usize,
+}
+
+impl CleanKey {
+    fn gas(&self) -> Gas {
+        CLEAN_GAS_PER_KEY + self.size as Gas * CLEAN_GAS_PER_BYTE
+    }
+
+    /// The base64 key quoted in the json args, plus a comma.
+    fn args_bytes(&self) -> usize {
+        self.key.len().div_ceil(3) * 4 + 3
+    }
+}
+
+/// Checks the `--gas-budget` option, which must leave room for the call itself.
+pub fn parse_clean_gas_budget(gas_budget: &str) -> anyhow::Result<Gas> {
+    let gas_budget = gas_budget
+        .parse::<NearGas>()
+        .map_err(|err| anyhow::anyhow!("Invalid gas budget '{}': {}", gas_budget, err))?
+        .inner;
+    if gas_budget <= CLEAN_BASE_GAS + CLEAN_GAS_PER_KEY || gas_budget > CLEAN_GAS {
+        anyhow::bail!(
+            "Gas budget must be between {} and {}",
+            NearGas::from(CLEAN_BASE_GAS + CLEAN_GAS_PER_KEY),
+            NearGas::from(CLEAN_GAS)
+        );
+    }
+    Ok(gas_budget)
+}
+
+/// Splits keys into batches whose estimated gas stays within `gas_budget`.
+///
+/// A key which exceeds the budget alone still gets a batch of its own.
+pub fn split_into_batches(keys: &[CleanKey], gas_budget: Gas) -> Vec<&[CleanKey]> {
+    let mut batches = vec![];
+    let mut start = 0;
+    let mut gas = CLEAN_BASE_GAS;
+    let mut args_bytes = 0;
+    for (index, key) in keys.iter().enumerate() {
+        if index > start
+            && (gas + key.gas() > gas_budget
+                || args_bytes + key.args_bytes() > MAX_CLEAN_ARGS_BYTES)
+        {
+            batches.push(&keys[start..index]);
+            start = index;
+            gas = CLEAN_BASE_GAS;
+            args_bytes = 0;
+        }
+        gas += key.gas();
+        args_bytes += key.args_bytes();
+    }
+    if start < keys.len() {
+        batches.push(&keys[start..]);
+    }
+    batches
+}
+
 pub struct CleanStateContract<'s> {
     pub account_id: AccountId,
     pub client: &'s Client,
@@ -27,30 +106,171 @@ impl<'s> CleanStateContract<'s> {
             .await
     }
 
-    pub async fn clean_up_all(
+    /// Whether the account already runs the state cleanup contract, eg: an interrupted
+    /// clean up which can be resumed.
+    pub async fn is_deployed(&self) -> anyhow::Result<bool> {
+        Ok(self.client.view_code_hash(self.account_id.clone()).await?
+            == Some(CryptoHash::hash_bytes(STATE_CLEANUP_WASM)))
+    }
+
+    /// Lists all keys of the account under `prefix`, page by page.
+    ///
+    /// Prefixes which were too large to be viewed are listed as keys as well, since a key
+    /// equal to one of them can not be seen. Removing a missing key does nothing.
+    pub async fn list_keys(
         &self,
-        signer: &InMemorySigner,
-    ) -> anyhow::Result<FinalExecutionOutcomeView> {
-        let result = self
+        prefix: &[u8],
+        progress: &ProgressBar,
+    ) -> anyhow::Result<Vec<CleanKey>> {
+        progress.set_style(ProgressStyle::with_template("{prefix} {spinner} {msg}")?);
+        let (items, split_prefixes) = self
             .client
-            .view_state(signer.account_id.clone(), None, None)
+            .view_state_paged(self.account_id.clone(), prefix, None, |found, pending| {
+                progress.set_message(format!(
+                    "listing keys, {} found, {} prefixes left",
+                    found, pending
+                ));
+                progress.tick();
+            })
             .await?;
-        let keys = result.values.iter().map(|e| e.key.clone()).collect_vec();
 
-        println!(
-            "Read account state keys before clean up: \n---\n {:?} \n---\n",
-            keys
-        );
+        let mut keys = split_prefixes
+            .into_iter()
+            .map(|prefix| CleanKey {
+                size: prefix.len(),
+                key: prefix,
+            })
+            .collect::<Vec<_>>();
+        for (key, value) in into_state_map(&items)? {
+            keys.push(CleanKey {
+                size: key.len() + value.len(),
+                key,
+            });
+        }
+        Ok(keys)
+    }
 
+    /// Lists the keys of the account selected by `filter`, sorted.
+    pub async fn list_selected_keys(
+        &self,
+        filter: &KeyFilter,
+        progress: &ProgressBar,
+    ) -> anyhow::Result<Vec<CleanKey>> {
+        let mut keys = vec![];
+        if filter.prefixes.is_empty() {
+            keys = self.list_keys(&[], progress).await?;
+        } else {
+            for prefix in &filter.prefixes {
+                keys.extend(self.list_keys(prefix, progress).await?);
+            }
+        }
+        keys.retain(|e| filter.matches(&e.key));
+        keys.sort_by(|a, b| a.key.cmp(&b.key));
+        keys.dedup_by(|a, b| a.key == b.key);
+        Ok(keys)
+    }
+
+    pub async fn clean(
+        &self,
+        signer: &InMemorySigner,
+        keys: &[CleanKey],
+    ) -> anyhow::Result<FinalExecutionOutcomeView> {
+        let keys = keys
+            .iter()
+            .map(|e| base64::encode(&e.key))
+            .collect::<Vec<_>>();
         self.client
             .call(
                 signer,
                 &self.account_id,
                 "clean".to_string(),
                 json!({ "keys": keys }).to_string().into_bytes(),
-                ONE_TERA_GAS.mul(300),
+                CLEAN_GAS,
                 0,
             )
             .await
     }
+
+    /// Removes all keys of the account, see [`Self::clean_keys`].
+    ///
+    /// Keys are listed again on every run, so calling it again resumes the clean up.
+    pub async fn clean_up_all(
+        &self,
+        signer: &InMemorySigner,
+        gas_budget: Gas,
+        progress: &ProgressBar,
+    ) -> anyhow::Result<Vec<FinalExecutionOutcomeView>> {
+        let keys = self.list_keys(&[], progress).await?;
+        self.clean_keys(signer, &keys, gas_budget, progress).await
+    }
+
+    /// Removes `keys` in `clean` calls sized by `gas_budget`, stops at the first failed call.
+    pub async fn clean_keys(
+        &self,
+        signer: &InMemorySigner,
+        keys: &[CleanKey],
+        gas_budget: Gas,
+        progress: &ProgressBar,
+    ) -> anyhow::Result<Vec<FinalExecutionOutcomeView>> {
+        let batches = split_into_batches(keys, gas_budget);
+
+        progress.set_style(
+            ProgressStyle::with_template("{prefix} [{bar:40}] {pos}/{len} keys {msg}")?
+                .progress_chars("=> "),
+        );
+        progress.set_length(keys.len() as u64);
+        progress.set_position(0);
+
+        let mut outcomes = vec![];
+        for (index, batch) in batches.iter().enumerate() {
+            progress.set_message(format!("batch {}/{}", index + 1, batches.len()));
+            let outcome = self.clean(signer, batch).await?;
+            let failed = CallExecutionDetails::from(outcome.clone()).is_failure();
+            outcomes.push(outcome);
+            if failed {
+                progress.abandon_with_message(format!(
+                    "batch {}/{} failed",
+                    index + 1,
+                    batches.len()
+                ));
+                anyhow::bail!(
+                    "The clean transaction of batch {}/{} with {} keys failed",
+                    index + 1,
+                    batches.len(),
+                    batch.len()
+                );
+            }
+            progress.inc(batch.len() as u64);
+        }
+        progress.finish_with_message(format!("done in {} transactions", batches.len()));
+        Ok(outcomes)
+    }
+}
+
+#[test]
+fn test_split_into_batches() {
+    let key = |size| CleanKey {
+        key: vec![0; 3],
+        size,
+    };
+    assert!(split_into_batches(&[], DEFAULT_CLEAN_GAS_BUDGET).is_empty());
+
+    // 5 TGas base + 0.1 TGas per key + 0.05 TGas per kb.
+    let small = vec![key(0); 3000];
+    let batches = split_into_batches(&small, DEFAULT_CLEAN_GAS_BUDGET);
+    assert_eq!(
+        batches.iter().map(|e| e.len()).collect::<Vec<_>>(),
+        vec![1950, 1050]
+    );
+
+    let large = vec![key(1_000_000), key(10_000_000), key(0)];
+    let batches = split_into_batches(&large, DEFAULT_CLEAN_GAS_BUDGET);
+    assert_eq!(
+        batches.iter().map(|e| e.len()).collect::<Vec<_>>(),
+        vec![1, 1, 1]
+    );
+
+    assert!(parse_clean_gas_budget("200 TGas").is_ok());
+    assert!(parse_clean_gas_budget("301 TGas").is_err());
+    assert!(parse_clean_gas_budget("1 TGas").is_err());
 }
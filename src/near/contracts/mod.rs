@@ -1 +1,2 @@
 pub mod clean_state;
+pub mod state_restore;
@@ -0,0 +1,86 @@
+use crate::near::constants::ONE_TERA_GAS;
+use crate::near::rpc::client::Client;
+use crate::near::snapshot::StateEntry;
+use near_crypto::InMemorySigner;
+use near_primitives::types::AccountId;
+use near_primitives::views::{FinalExecutionOutcomeView, FinalExecutionStatus};
+use serde_json::json;
+use std::ops::Mul;
+
+/// Max bytes of keys and values written by a single `replace` call.
+pub const REPLACE_BATCH_BYTES: usize = 128 * 1024;
+
+/// A helper contract which writes raw key/values into its own storage, it must expose
+/// `replace({"entries": [[key_base64, value_base64], ...]})`.
+pub struct StateRestoreContract<'s> {
+    pub account_id: AccountId,
+    pub client: &'s Client,
+}
+
+impl<'s> StateRestoreContract<'s> {
+    pub async fn deploy(
+        &self,
+        signer: &InMemorySigner,
+        wasm: Vec<u8>,
+    ) -> anyhow::Result<FinalExecutionOutcomeView> {
+        self.client.deploy(signer, wasm).await
+    }
+
+    pub async fn replace(
+        &self,
+        signer: &InMemorySigner,
+        entries: &[StateEntry],
+    ) -> anyhow::Result<FinalExecutionOutcomeView> {
+        let entries: Vec<(&String, &String)> = entries.iter().map(|e| (&e.key, &e.value)).collect();
+        self.client
+            .call(
+                signer,
+                &self.account_id,
+                "replace".to_string(),
+                json!({ "entries": entries }).to_string().into_bytes(),
+                ONE_TERA_GAS.mul(300),
+                0,
+            )
+            .await
+    }
+
+    /// Writes all `entries` with as many `replace` calls as needed.
+    pub async fn replace_all(
+        &self,
+        signer: &InMemorySigner,
+        entries: &[StateEntry],
+    ) -> anyhow::Result<Vec<FinalExecutionOutcomeView>> {
+        let mut outcomes = vec![];
+        let mut start = 0;
+        while start < entries.len() {
+            let mut end = start;
+            let mut batch_bytes = 0;
+            while end < entries.len()
+                && (end == start
+                    || batch_bytes + entries[end].key.len() + entries[end].value.len()
+                        <= REPLACE_BATCH_BYTES)
+            {
+                batch_bytes += entries[end].key.len() + entries[end].value.len();
+                end += 1;
+            }
+            println!(
+                "Restore state entries {}..{} of {}",
+                start,
+                end,
+                entries.len()
+            );
+            let outcome = self.replace(signer, &entries[start..end]).await?;
+            if let FinalExecutionStatus::Failure(err) = &outcome.status {
+                anyhow::bail!(
+                    "Failed to restore state entries {}..{}: {:?}",
+                    start,
+                    end,
+                    err
+                );
+            }
+            outcomes.push(outcome);
+            start = end;
+        }
+        Ok(outcomes)
+    }
+}
@@ -1,6 +1,14 @@
+pub mod args_template;
+pub mod batch;
 pub mod constants;
 pub mod contracts;
 pub mod gas;
 pub mod rpc;
+pub mod snapshot;
+pub mod state_dump;
+pub mod state_key;
+pub mod storage;
 pub mod types;
 pub mod util;
+pub mod view_check;
+pub mod wasm;
@@ -1,5 +1,4 @@
 use std::fmt::Debug;
-use std::ops::Mul;
 use std::time::Duration;
 
 use near_crypto::{InMemorySigner, PublicKey, Signer};
@@ -7,7 +6,7 @@ use near_jsonrpc_client::errors::JsonRpcError;
 use near_jsonrpc_client::methods::health::RpcStatusError;
 use near_jsonrpc_client::methods::query::RpcQueryRequest;
 use near_jsonrpc_client::{methods, JsonRpcClient, MethodCallResult};
-use near_jsonrpc_primitives::types::query::QueryResponseKind;
+use near_jsonrpc_primitives::types::query::{QueryResponseKind, RpcQueryError};
 use near_primitives::account::{AccessKey, AccessKeyPermission};
 use near_primitives::hash::CryptoHash;
 use near_primitives::transaction::{
@@ -17,7 +16,7 @@ use near_primitives::transaction::{
 use near_primitives::types::{AccountId, Balance, BlockId, Finality, Gas, StoreKey};
 use near_primitives::views::{
     AccessKeyView, AccountView, BlockView, ContractCodeView, FinalExecutionOutcomeView,
-    QueryRequest, StatusResponse,
+    QueryRequest, StateItem, StatusResponse,
 };
 use tokio_retry::strategy::{jitter, ExponentialBackoff};
 use tokio_retry::Retry;
@@ -27,6 +26,7 @@ use crate::near::rpc::result::ViewResultDetails;
 
 pub(crate) const DEFAULT_CALL_FN_GAS: Gas = 10_000_000_000_000;
 pub(crate) const DEFAULT_CALL_DEPOSIT: Balance = 0;
+pub(crate) const DEFAULT_DEPLOY_AND_INIT_GAS: Gas = 200 * ONE_TERA_GAS;
 const ERR_INVALID_VARIANT: &str =
     "Incorrect variant retrieved while querying: maybe a bug in RPC code?";
 
@@ -156,9 +156,24 @@ impl Client {
         method_name: String,
         args: Vec<u8>,
     ) -> anyhow::Result<ViewResultDetails> {
+        self.view_at(contract_id, method_name, args, None).await
+    }
+
+    /// Calls a view method at `block_id`, or at the latest block. Old blocks need an archival rpc.
+    pub(crate) async fn view_at(
+        &self,
+        contract_id: AccountId,
+        method_name: String,
+        args: Vec<u8>,
+        block_id: Option<BlockId>,
+    ) -> anyhow::Result<ViewResultDetails> {
+        let block_reference = block_id
+            .map(Into::into)
+            .unwrap_or_else(|| Finality::None.into()); // Optimisitic query
+
         let query_resp = self
             .query(&RpcQueryRequest {
-                block_reference: Finality::None.into(), // Optimisitic query
+                block_reference,
                 request: QueryRequest::CallFunction {
                     account_id: contract_id,
                     method_name,
@@ -200,6 +215,42 @@ impl Client {
         }
     }
 
+    /// Views all state under `prefix`, even when the rpc refuses to return it at once.
+    ///
+    /// A prefix whose state is too large to be viewed is split into 256 longer prefixes, one
+    /// for every next byte. A key equal to a split prefix can not be listed this way, so the
+    /// split prefixes are returned along with the state items.
+    pub(crate) async fn view_state_paged(
+        &self,
+        contract_id: AccountId,
+        prefix: &[u8],
+        block_id: Option<BlockId>,
+        mut on_page: impl FnMut(usize, usize),
+    ) -> anyhow::Result<(Vec<StateItem>, Vec<Vec<u8>>)> {
+        let mut items = vec![];
+        let mut split_prefixes = vec![];
+        let mut prefixes = vec![prefix.to_vec()];
+        while let Some(prefix) = prefixes.pop() {
+            match self
+                .view_state(contract_id.clone(), Some(&prefix), block_id.clone())
+                .await
+            {
+                Ok(state) => items.extend(state.values),
+                Err(err) if is_too_large_state_error(&err) => {
+                    prefixes.extend((0..=u8::MAX).rev().map(|byte| {
+                        let mut child = prefix.clone();
+                        child.push(byte);
+                        child
+                    }));
+                    split_prefixes.push(prefix);
+                }
+                Err(err) => return Err(err),
+            }
+            on_page(items.len(), prefixes.len());
+        }
+        Ok((items, split_prefixes))
+    }
+
     pub(crate) async fn view_account(
         &self,
         account_id: AccountId,
@@ -244,6 +295,20 @@ impl Client {
         }
     }
 
+    /// Returns the hash of the code deployed to `account_id`, or `None` if the account
+    /// has no contract deployed yet.
+    pub(crate) async fn view_code_hash(
+        &self,
+        account_id: AccountId,
+    ) -> anyhow::Result<Option<CryptoHash>> {
+        let account = self.view_account(account_id, None).await?;
+        if account.code_hash == CryptoHash::default() {
+            Ok(None)
+        } else {
+            Ok(Some(account.code_hash))
+        }
+    }
+
     pub(crate) async fn view_block(&self, block_id: Option<BlockId>) -> anyhow::Result<BlockView> {
         let block_reference = block_id
             .map(Into::into)
@@ -256,6 +321,20 @@ impl Client {
         Ok(block_view)
     }
 
+    pub(crate) async fn protocol_config(
+        &self,
+    ) -> anyhow::Result<methods::EXPERIMENTAL_protocol_config::RpcProtocolConfigResponse> {
+        let protocol_config = self
+            .query(
+                &methods::EXPERIMENTAL_protocol_config::RpcProtocolConfigRequest {
+                    block_reference: Finality::Final.into(),
+                },
+            )
+            .await?;
+
+        Ok(protocol_config)
+    }
+
     pub(crate) async fn deploy(
         &self,
         signer: &InMemorySigner,
@@ -275,6 +354,7 @@ impl Client {
         wasm: Vec<u8>,
         method_name: String,
         args: Vec<u8>,
+        gas: Gas,
     ) -> anyhow::Result<FinalExecutionOutcomeView> {
         send_batch_tx_and_retry(
             &self,
@@ -285,7 +365,7 @@ impl Client {
                 FunctionCallAction {
                     method_name,
                     args,
-                    gas: ONE_TERA_GAS.mul(200),
+                    gas,
                     deposit: 0,
                 }
                 .into(),
@@ -465,6 +545,44 @@ pub(crate) async fn send_tx(
         .map_err(|e| anyhow::anyhow!(e))
 }
 
+/// Whether the rpc refused to view state because it is larger than its view limit.
+fn is_too_large_state_error(err: &anyhow::Error) -> bool {
+    format!("{:?}", err).contains("TooLargeContractState")
+        || err.to_string().contains("too large to be viewed")
+}
+
+/// The query error the rpc answered with, `None` for transport and other errors.
+fn query_error_of(err: &anyhow::Error) -> Option<&RpcQueryError> {
+    err.downcast_ref::<JsonRpcError<RpcQueryError>>()
+        .and_then(|e| e.handler_error())
+}
+
+/// Whether a query failed because the account does not exist.
+pub(crate) fn is_unknown_account_error(err: &anyhow::Error) -> bool {
+    matches!(
+        query_error_of(err),
+        Some(RpcQueryError::UnknownAccount { .. })
+    )
+}
+
+/// Whether a view at a block failed because the rpc does not have the block, or the account or
+/// its contract did not exist at that block.
+pub(crate) fn is_missing_at_block_error(err: &anyhow::Error) -> bool {
+    match query_error_of(err) {
+        Some(
+            RpcQueryError::UnknownBlock { .. }
+            | RpcQueryError::GarbageCollectedBlock { .. }
+            | RpcQueryError::UnknownAccount { .. }
+            | RpcQueryError::NoContractCode { .. },
+        ) => true,
+        // A call to an account without a contract fails in the contract execution.
+        Some(RpcQueryError::ContractExecutionError { vm_error, .. }) => {
+            vm_error.contains("CodeDoesNotExist")
+        }
+        _ => false,
+    }
+}
+
 pub(crate) async fn send_tx_and_retry<T, F>(
     client: &Client,
     task: F,
@@ -497,3 +615,40 @@ pub(crate) async fn send_batch_tx_and_retry(
     })
     .await
 }
+
+#[test]
+fn test_query_errors() {
+    use near_jsonrpc_client::errors::JsonRpcServerError;
+
+    let query_error = |error| {
+        anyhow::Error::from(JsonRpcError::ServerError(JsonRpcServerError::HandlerError(
+            error,
+        )))
+    };
+    assert!(is_missing_at_block_error(&query_error(
+        RpcQueryError::UnknownBlock {
+            block_reference: BlockId::Height(10).into(),
+        }
+    )));
+    assert!(is_missing_at_block_error(&query_error(
+        RpcQueryError::ContractExecutionError {
+            vm_error: "wasm execution failed with error: CompilationError(CodeDoesNotExist { account_id: \"anchor.testnet\" })".to_string(),
+            block_height: 10,
+            block_hash: CryptoHash::default(),
+        }
+    )));
+    assert!(!is_missing_at_block_error(&query_error(
+        RpcQueryError::InternalError {
+            error_message: "rate limited".to_string(),
+        }
+    )));
+    assert!(!is_missing_at_block_error(&anyhow::anyhow!("timed out")));
+
+    let unknown_account = query_error(RpcQueryError::UnknownAccount {
+        requested_account_id: "anchor.testnet".parse().unwrap(),
+        block_height: 10,
+        block_hash: CryptoHash::default(),
+    });
+    assert!(is_unknown_account_error(&unknown_account));
+    assert!(!is_unknown_account_error(&anyhow::anyhow!("timed out")));
+}
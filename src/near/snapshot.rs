@@ -0,0 +1,187 @@
+use crate::near::rpc::client::Client;
+use crate::near::rpc::tool::into_state_map;
+use crate::util::u128_dec_format;
+use itertools::Itertools;
+use near_primitives::hash::CryptoHash;
+use near_primitives::types::{AccountId, BlockHeight, BlockId};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// A single contract state record, key and value are base64 encoded.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StateEntry {
+    pub key: String,
+    pub value: String,
+}
+
+/// Everything needed to bring an account back to the moment the snapshot was taken:
+/// the deployed code, the full contract state and the account balance and storage.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContractSnapshot {
+    pub account_id: AccountId,
+    pub block_height: BlockHeight,
+    pub created_at: String,
+    #[serde(with = "u128_dec_format")]
+    pub amount: u128,
+    #[serde(with = "u128_dec_format")]
+    pub locked: u128,
+    pub storage_usage: u64,
+    pub code_hash: CryptoHash,
+    /// Base64 encoded wasm, empty if the account has no contract.
+    pub code: String,
+    pub state: Vec<StateEntry>,
+    /// Hex encoded prefixes the state was split by when viewed in pages, a key equal to one
+    /// of them is not in `state`.
+    #[serde(default)]
+    pub split_prefixes: Vec<String>,
+}
+
+impl ContractSnapshot {
+    /// Reads account, code and state of `account_id` at the same (latest) block.
+    pub async fn take(client: &Client, account_id: AccountId) -> anyhow::Result<Self> {
+        let block_height = client.view_block(None).await?.header.height;
+        let block_id = Some(BlockId::Height(block_height));
+
+        let account = client
+            .view_account(account_id.clone(), block_id.clone())
+            .await?;
+        let code = if account.code_hash == CryptoHash::default() {
+            vec![]
+        } else {
+            client
+                .view_code(account_id.clone(), block_id.clone())
+                .await?
+                .code
+        };
+        let (state_items, split_prefixes) = client
+            .view_state_paged(account_id.clone(), &[], block_id, |_, _| {})
+            .await?;
+        let state = into_state_map(&state_items)?
+            .into_iter()
+            .sorted()
+            .map(|(key, value)| StateEntry {
+                key: base64::encode(key),
+                value: base64::encode(value),
+            })
+            .collect();
+
+        Ok(Self {
+            account_id,
+            block_height,
+            created_at: chrono::Utc::now().to_rfc3339(),
+            amount: account.amount,
+            locked: account.locked,
+            storage_usage: account.storage_usage,
+            code_hash: account.code_hash,
+            code: base64::encode(code),
+            state,
+            split_prefixes: split_prefixes.iter().map(hex::encode).collect(),
+        })
+    }
+
+    /// Saves the snapshot as `<dir>/<account_id>/<timestamp>.json` and returns the file path.
+    pub fn save(&self, dir: &Path) -> anyhow::Result<PathBuf> {
+        let mut path = dir.join(self.account_id.as_str());
+        std::fs::create_dir_all(&path)?;
+        path.push(format!(
+            "{}_{}.json",
+            chrono::Utc::now().format("%Y%m%dT%H%M%SZ"),
+            self.block_height
+        ));
+        std::fs::write(&path, serde_json::to_string_pretty(self)?)?;
+        Ok(path)
+    }
+
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let content = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    pub fn code_bytes(&self) -> anyhow::Result<Vec<u8>> {
+        Ok(base64::decode(&self.code)?)
+    }
+
+    /// Fails if keys equal to a split prefix are missing, they can not be restored from it.
+    pub fn ensure_complete(&self) -> anyhow::Result<()> {
+        if !self.split_prefixes.is_empty() {
+            anyhow::bail!(
+                "State of {} was viewed in pages, keys equal to these prefixes are not in the snapshot: {}",
+                self.account_id,
+                self.split_prefixes.join(", ")
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Takes a snapshot of `account_id` and saves it under `dir`. Check `ensure_complete` before
+/// changing the account.
+pub async fn take_and_save_snapshot(
+    client: &Client,
+    account_id: AccountId,
+    dir: &Path,
+) -> anyhow::Result<(ContractSnapshot, PathBuf)> {
+    let snapshot = ContractSnapshot::take(client, account_id).await?;
+    let path = snapshot.save(dir)?;
+    Ok((snapshot, path))
+}
+
+/// Finds the latest snapshot of `account_id` saved under `dir`, file names start with the
+/// time they were taken.
+pub fn find_latest_snapshot(dir: &Path, account_id: &AccountId) -> anyhow::Result<Option<PathBuf>> {
+    let dir = dir.join(account_id.as_str());
+    if !dir.exists() {
+        return Ok(None);
+    }
+    let mut paths = vec![];
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().and_then(|e| e.to_str()) == Some("json") {
+            paths.push(path);
+        }
+    }
+    Ok(paths
+        .into_iter()
+        .max_by(|a, b| a.file_name().cmp(&b.file_name())))
+}
+
+#[test]
+fn test_paged_snapshot() {
+    let snapshot = ContractSnapshot {
+        account_id: "anchor.testnet".parse().unwrap(),
+        block_height: 100,
+        created_at: "2022-12-01T08:00:00+00:00".to_string(),
+        amount: 10,
+        locked: 0,
+        storage_usage: 200,
+        code_hash: CryptoHash::default(),
+        code: String::new(),
+        state: vec![StateEntry {
+            key: base64::encode(b"\x05alice"),
+            value: base64::encode(b"1"),
+        }],
+        split_prefixes: vec![hex::encode(b"\x05")],
+    };
+    let dir = std::env::temp_dir().join(format!("oct-cli-snapshot-{}", std::process::id()));
+    snapshot.save(&dir).unwrap();
+    let path = find_latest_snapshot(&dir, &snapshot.account_id)
+        .unwrap()
+        .unwrap();
+    let loaded = ContractSnapshot::load(&path).unwrap();
+    assert_eq!(loaded.split_prefixes, vec!["05".to_string()]);
+    assert!(loaded
+        .ensure_complete()
+        .unwrap_err()
+        .to_string()
+        .ends_with("not in the snapshot: 05"));
+
+    // Snapshots saved before split prefixes were kept load as complete.
+    let mut legacy = serde_json::to_value(&snapshot).unwrap();
+    legacy.as_object_mut().unwrap().remove("split_prefixes");
+    std::fs::write(&path, legacy.to_string()).unwrap();
+    assert!(ContractSnapshot::load(&path)
+        .unwrap()
+        .ensure_complete()
+        .is_ok());
+    std::fs::remove_dir_all(dir).unwrap();
+}
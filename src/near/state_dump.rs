@@ -0,0 +1,385 @@
+use crate::near::rpc::client::Client;
+use crate::near::rpc::tool::into_state_map;
+use crate::near::state_key::decode_key;
+use itertools::Itertools;
+use near_primitives::types::{AccountId, BlockHeight, BlockId};
+use prettytable::{row, Table};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::str::FromStr;
+
+/// How many characters of a value are shown in a diff.
+const VALUE_PREVIEW_LEN: usize = 48;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DumpFormat {
+    Json,
+    Csv,
+}
+
+impl FromStr for DumpFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "json" => Ok(DumpFormat::Json),
+            "csv" => Ok(DumpFormat::Csv),
+            _ => Err(format!("Unknown dump format '{}', expected json or csv", s)),
+        }
+    }
+}
+
+impl DumpFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            DumpFormat::Json => "json",
+            DumpFormat::Csv => "csv",
+        }
+    }
+
+    /// Picks the format by the file extension, json unless it ends with `.csv`.
+    pub fn of_path(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some(extension) if extension.eq_ignore_ascii_case("csv") => DumpFormat::Csv,
+            _ => DumpFormat::Json,
+        }
+    }
+
+    /// Fails if a dump in this format saved to `path` would be loaded as another format.
+    pub fn check_path(&self, path: &Path) -> anyhow::Result<()> {
+        if DumpFormat::of_path(path) != *self {
+            anyhow::bail!(
+                "A {} dump must be saved to a .{} file, {} would be read as {}",
+                self.extension(),
+                self.extension(),
+                path.display(),
+                DumpFormat::of_path(path).extension()
+            );
+        }
+        Ok(())
+    }
+}
+
+/// A state record, key and value are base64 encoded, `decoded_key` is for reading only.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StateDumpEntry {
+    pub key: String,
+    pub decoded_key: String,
+    pub value: String,
+}
+
+/// A row of a csv dump, account and block height are repeated in each row so any file name loads.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CsvRow {
+    account_id: AccountId,
+    block_height: BlockHeight,
+    key: String,
+    decoded_key: String,
+    value: String,
+}
+
+/// The key/value state of a contract at a block.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StateDump {
+    pub account_id: AccountId,
+    pub block_height: BlockHeight,
+    pub entries: Vec<StateDumpEntry>,
+}
+
+impl StateDump {
+    /// Reads the state at `block_height`, or at the latest block. Old blocks need an archival rpc.
+    pub async fn fetch(
+        client: &Client,
+        account_id: AccountId,
+        block_height: Option<BlockHeight>,
+    ) -> anyhow::Result<Self> {
+        let block_height = match block_height {
+            Some(block_height) => block_height,
+            None => client.view_block(None).await?.header.height,
+        };
+        let (items, split_prefixes) = client
+            .view_state_paged(
+                account_id.clone(),
+                &[],
+                Some(BlockId::Height(block_height)),
+                |_, _| {},
+            )
+            .await?;
+        if !split_prefixes.is_empty() {
+            println!(
+                "State of {} was viewed in pages, keys equal to these prefixes can not be viewed: {}",
+                account_id,
+                split_prefixes.iter().map(hex::encode).join(", ")
+            );
+        }
+        let entries = into_state_map(&items)?
+            .into_iter()
+            .sorted()
+            .map(|(key, value)| StateDumpEntry {
+                key: base64::encode(&key),
+                decoded_key: decode_key(&key),
+                value: base64::encode(value),
+            })
+            .collect();
+        Ok(Self {
+            account_id,
+            block_height,
+            entries,
+        })
+    }
+
+    /// Saves the dump, each csv row carries the account and block height of the dump.
+    pub fn save(&self, path: &Path, format: DumpFormat) -> anyhow::Result<()> {
+        format.check_path(path)?;
+        match format {
+            DumpFormat::Json => std::fs::write(path, serde_json::to_string_pretty(self)?)?,
+            DumpFormat::Csv => {
+                if self.entries.is_empty() {
+                    anyhow::bail!(
+                        "State of {} is empty at block {}, save it as json",
+                        self.account_id,
+                        self.block_height
+                    );
+                }
+                let mut writer = csv::Writer::from_path(path)?;
+                for entry in &self.entries {
+                    writer.serialize(CsvRow {
+                        account_id: self.account_id.clone(),
+                        block_height: self.block_height,
+                        key: entry.key.clone(),
+                        decoded_key: entry.decoded_key.clone(),
+                        value: entry.value.clone(),
+                    })?;
+                }
+                writer.flush()?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        match DumpFormat::of_path(path) {
+            DumpFormat::Json => Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?),
+            DumpFormat::Csv => {
+                let rows = csv::Reader::from_path(path)?
+                    .deserialize()
+                    .collect::<Result<Vec<CsvRow>, _>>()?;
+                let (account_id, block_height) = match rows.first() {
+                    Some(row) => (row.account_id.clone(), row.block_height),
+                    None => anyhow::bail!("Csv dump {} has no rows", path.display()),
+                };
+                if let Some(row) = rows
+                    .iter()
+                    .find(|e| e.account_id != account_id || e.block_height != block_height)
+                {
+                    anyhow::bail!(
+                        "Csv dump {} mixes {} at block {} with {} at block {}",
+                        path.display(),
+                        account_id,
+                        block_height,
+                        row.account_id,
+                        row.block_height
+                    );
+                }
+                Ok(Self {
+                    account_id,
+                    block_height,
+                    entries: rows
+                        .into_iter()
+                        .map(|e| StateDumpEntry {
+                            key: e.key,
+                            decoded_key: e.decoded_key,
+                            value: e.value,
+                        })
+                        .collect(),
+                })
+            }
+        }
+    }
+
+    pub fn default_file_name(&self, format: DumpFormat) -> String {
+        format!(
+            "{}_{}.{}",
+            self.account_id,
+            self.block_height,
+            format.extension()
+        )
+    }
+
+    /// Compares with a later dump, entries are sorted by key.
+    pub fn diff(&self, to: &StateDump) -> Vec<StateChange> {
+        let from_values: BTreeMap<&str, &StateDumpEntry> =
+            self.entries.iter().map(|e| (e.key.as_str(), e)).collect();
+        let to_values: BTreeMap<&str, &StateDumpEntry> =
+            to.entries.iter().map(|e| (e.key.as_str(), e)).collect();
+        from_values
+            .keys()
+            .chain(to_values.keys())
+            .sorted()
+            .dedup()
+            .filter_map(|key| {
+                let from = from_values.get(key);
+                let to = to_values.get(key);
+                let kind = match (from, to) {
+                    (None, Some(_)) => StateChangeKind::Added,
+                    (Some(_), None) => StateChangeKind::Removed,
+                    (Some(from), Some(to)) if from.value != to.value => StateChangeKind::Changed,
+                    _ => return None,
+                };
+                Some(StateChange {
+                    kind,
+                    decoded_key: from
+                        .or(to)
+                        .map(|e| e.decoded_key.clone())
+                        .unwrap_or_default(),
+                    from: from.map(|e| e.value.clone()),
+                    to: to.map(|e| e.value.clone()),
+                })
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StateChangeKind {
+    Added,
+    Removed,
+    Changed,
+}
+
+impl std::fmt::Display for StateChangeKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StateChangeKind::Added => write!(f, "added"),
+            StateChangeKind::Removed => write!(f, "removed"),
+            StateChangeKind::Changed => write!(f, "changed"),
+        }
+    }
+}
+
+/// A key whose value differs between two dumps, values are base64 encoded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StateChange {
+    pub kind: StateChangeKind,
+    pub decoded_key: String,
+    pub from: Option<String>,
+    pub to: Option<String>,
+}
+
+/// Shows a base64 value as text if it is printable, otherwise as hex, cut to a preview.
+fn preview_value(value: &Option<String>) -> String {
+    let bytes = match value.as_ref().map(base64::decode) {
+        Some(Ok(bytes)) => bytes,
+        Some(Err(_)) => return "<invalid base64>".to_string(),
+        None => return String::new(),
+    };
+    let text = match std::str::from_utf8(&bytes) {
+        Ok(text) if text.chars().all(|e| !e.is_control()) => text.to_string(),
+        _ => format!("0x{}", hex::encode(&bytes)),
+    };
+    let mut preview = text.chars().take(VALUE_PREVIEW_LEN).collect::<String>();
+    if preview.len() < text.len() {
+        preview.push_str("...");
+    }
+    format!("{} ({} bytes)", preview, bytes.len())
+}
+
+pub fn print_state_changes(from: &StateDump, to: &StateDump, changes: &[StateChange]) {
+    let mut table = Table::new();
+    table.set_titles(row![
+        "key",
+        "change",
+        format!("at {}", from.block_height),
+        format!("at {}", to.block_height)
+    ]);
+    for change in changes {
+        table.add_row(row![
+            change.decoded_key,
+            change.kind,
+            preview_value(&change.from),
+            preview_value(&change.to)
+        ]);
+    }
+    table.printstd();
+    let count = |kind| changes.iter().filter(|e| e.kind == kind).count();
+    println!(
+        "{}: {} added, {} removed, {} changed, {} keys at block {}",
+        to.account_id,
+        count(StateChangeKind::Added),
+        count(StateChangeKind::Removed),
+        count(StateChangeKind::Changed),
+        to.entries.len(),
+        to.block_height
+    );
+}
+
+#[test]
+fn test_state_dump_diff() {
+    let entry = |key: &[u8], value: &[u8]| StateDumpEntry {
+        key: base64::encode(key),
+        decoded_key: decode_key(key),
+        value: base64::encode(value),
+    };
+    let from = StateDump {
+        account_id: "anchor.testnet".parse().unwrap(),
+        block_height: 10,
+        entries: vec![entry(b"a", b"1"), entry(b"b", b"2"), entry(b"c", b"3")],
+    };
+    let to = StateDump {
+        block_height: 20,
+        entries: vec![entry(b"b", b"2"), entry(b"c", b"4"), entry(b"d", b"5")],
+        ..from.clone()
+    };
+    let changes = from.diff(&to);
+    assert_eq!(
+        changes
+            .iter()
+            .map(|e| (e.decoded_key.as_str(), e.kind))
+            .collect::<Vec<_>>(),
+        vec![
+            ("a", StateChangeKind::Removed),
+            ("c", StateChangeKind::Changed),
+            ("d", StateChangeKind::Added)
+        ]
+    );
+    assert_eq!(preview_value(&changes[1].to), "4 (1 bytes)");
+
+    let dir = std::env::temp_dir().join(format!("oct-cli-state-dump-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    for format in [DumpFormat::Json, DumpFormat::Csv] {
+        let path = dir.join(to.default_file_name(format));
+        to.save(&path, format).unwrap();
+        let loaded = StateDump::load(&path).unwrap();
+        assert_eq!(loaded.account_id, to.account_id);
+        assert_eq!(loaded.block_height, 20);
+        assert_eq!(loaded.entries, to.entries);
+    }
+    // A csv dump does not depend on its file name.
+    let path = dir.join("before.csv");
+    from.save(&path, DumpFormat::Csv).unwrap();
+    let loaded = StateDump::load(&path).unwrap();
+    assert_eq!(loaded.account_id, from.account_id);
+    assert_eq!(loaded.block_height, 10);
+    assert_eq!(loaded.entries, from.entries);
+    // The format is read back from the extension, so it must match.
+    assert!(from
+        .save(&dir.join("before.json"), DumpFormat::Csv)
+        .is_err());
+    assert!(from.save(&dir.join("before"), DumpFormat::Csv).is_err());
+    assert!(from
+        .save(&dir.join("before.csv"), DumpFormat::Json)
+        .is_err());
+    from.save(&dir.join("before"), DumpFormat::Json).unwrap();
+    assert_eq!(
+        StateDump::load(&dir.join("before")).unwrap().entries,
+        from.entries
+    );
+    assert!(StateDump {
+        entries: vec![],
+        ..from.clone()
+    }
+    .save(&dir.join("empty.csv"), DumpFormat::Csv)
+    .is_err());
+    std::fs::remove_dir_all(dir).unwrap();
+}
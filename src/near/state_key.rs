@@ -0,0 +1,204 @@
+use near_primitives::types::AccountId;
+use prettytable::{row, Table};
+
+/// Parses a key prefix written as `hex:<hex>`, `base64:<base64>` or `utf8:<text>`, plain text
+/// is read as utf8.
+pub fn parse_key_prefix(prefix: &str) -> anyhow::Result<Vec<u8>> {
+    let prefix = prefix.trim();
+    let bytes = if let Some(hex) = prefix.strip_prefix("hex:") {
+        hex::decode(hex.trim_start_matches("0x"))
+            .map_err(|err| anyhow::anyhow!("Invalid hex prefix '{}': {}", prefix, err))?
+    } else if let Some(base64) = prefix.strip_prefix("base64:") {
+        base64::decode(base64)
+            .map_err(|err| anyhow::anyhow!("Invalid base64 prefix '{}': {}", prefix, err))?
+    } else {
+        prefix
+            .strip_prefix("utf8:")
+            .unwrap_or(prefix)
+            .as_bytes()
+            .to_vec()
+    };
+    if bytes.is_empty() {
+        anyhow::bail!("Empty key prefix '{}'", prefix);
+    }
+    Ok(bytes)
+}
+
+fn parse_key_prefixes(prefixes: &str, none: &str) -> anyhow::Result<Vec<Vec<u8>>> {
+    if prefixes.trim() == none {
+        return Ok(vec![]);
+    }
+    prefixes.split(',').map(parse_key_prefix).collect()
+}
+
+/// Which state keys to select, by the `--prefix` and `--exclude-prefix` options.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KeyFilter {
+    /// Keys must start with one of these, any key if empty.
+    pub prefixes: Vec<Vec<u8>>,
+    /// Keys starting with one of these are left out.
+    pub exclude_prefixes: Vec<Vec<u8>>,
+}
+
+impl KeyFilter {
+    /// Parses comma separated prefixes, `all` and `none` stand for no prefixes.
+    pub fn parse(prefix: &str, exclude_prefix: &str) -> anyhow::Result<Self> {
+        Ok(Self {
+            prefixes: parse_key_prefixes(prefix, "all")?,
+            exclude_prefixes: parse_key_prefixes(exclude_prefix, "none")?,
+        })
+    }
+
+    /// Whether every key is selected.
+    pub fn is_empty(&self) -> bool {
+        self.prefixes.is_empty() && self.exclude_prefixes.is_empty()
+    }
+
+    pub fn matches(&self, key: &[u8]) -> bool {
+        (self.prefixes.is_empty() || self.prefixes.iter().any(|e| key.starts_with(e)))
+            && !self.exclude_prefixes.iter().any(|e| key.starts_with(e))
+    }
+
+    /// The prefix a key is grouped by in previews: the longest matching `--prefix`, or else
+    /// the leading text of the key, or its first byte.
+    pub fn group_of(&self, key: &[u8]) -> Vec<u8> {
+        if let Some(prefix) = self
+            .prefixes
+            .iter()
+            .filter(|e| key.starts_with(e))
+            .max_by_key(|e| e.len())
+        {
+            return prefix.clone();
+        }
+        let text_len = key.iter().take_while(|e| is_key_text(**e)).count();
+        key[..text_len.max(1).min(key.len())].to_vec()
+    }
+}
+
+fn is_key_text(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || byte == b'_' || byte == b'-' || byte == b'.'
+}
+
+/// Shows a key as its leading text followed by the rest in hex, eg: `validators+0x0100`.
+pub fn display_key(key: &[u8]) -> String {
+    let text_len = key.iter().take_while(|e| is_key_text(**e)).count();
+    let (text, rest) = key.split_at(text_len);
+    match (text.is_empty(), rest.is_empty()) {
+        (true, true) => "<empty>".to_string(),
+        (false, true) => String::from_utf8_lossy(text).to_string(),
+        (true, false) => format!("0x{}", hex::encode(rest)),
+        (false, false) => format!("{}+0x{}", String::from_utf8_lossy(text), hex::encode(rest)),
+    }
+}
+
+/// Decodes a key the way near-sdk collections usually write them: a text or single byte
+/// prefix followed by a u64, an account id, text or other bytes, eg: `validators+u64(12)`,
+/// `0x05+account(alice.near)`.
+pub fn decode_key(key: &[u8]) -> String {
+    if !key.is_empty() && key.iter().all(|e| is_key_text(*e)) {
+        return String::from_utf8_lossy(key).to_string();
+    }
+    let text_len = key.iter().take_while(|e| is_key_text(**e)).count();
+    let (prefix, suffix) = key.split_at(text_len.max(1).min(key.len()));
+    let prefix = display_key(prefix);
+    if suffix.is_empty() {
+        return prefix;
+    }
+    let text = decode_borsh_string(suffix).or_else(|| std::str::from_utf8(suffix).ok());
+    let suffix = match text {
+        Some(text) if text.parse::<AccountId>().is_ok() => format!("account({})", text),
+        _ if suffix.len() == 8 => format!(
+            "u64({})",
+            u64::from_le_bytes(suffix.try_into().expect("suffix has 8 bytes"))
+        ),
+        Some(text) if text.chars().all(|e| !e.is_control()) => format!("utf8({})", text),
+        _ => format!("0x{}", hex::encode(suffix)),
+    };
+    format!("{}+{}", prefix, suffix)
+}
+
+/// A borsh string is its u32 length in little endian followed by the utf8 bytes.
+fn decode_borsh_string(bytes: &[u8]) -> Option<&str> {
+    if bytes.len() < 4 {
+        return None;
+    }
+    let (len, text) = bytes.split_at(4);
+    if u32::from_le_bytes(len.try_into().ok()?) as usize != text.len() {
+        return None;
+    }
+    std::str::from_utf8(text).ok()
+}
+
+/// Prints keys grouped by prefix with their count, bytes and a few examples.
+///
+/// `keys` are raw keys with the bytes of the key and its value.
+pub fn print_key_groups(keys: &[(Vec<u8>, usize)], filter: &KeyFilter) {
+    const EXAMPLES: usize = 3;
+    let mut groups: Vec<(Vec<u8>, usize, usize, Vec<String>)> = vec![];
+    for (key, size) in keys {
+        let group = filter.group_of(key);
+        let index = match groups.iter().position(|e| e.0 == group) {
+            Some(index) => index,
+            None => {
+                groups.push((group, 0, 0, vec![]));
+                groups.len() - 1
+            }
+        };
+        let entry = &mut groups[index];
+        entry.1 += 1;
+        entry.2 += size;
+        if entry.3.len() < EXAMPLES {
+            entry.3.push(display_key(key));
+        }
+    }
+    groups.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut table = Table::new();
+    table.set_titles(row!["prefix", "keys", "bytes", "examples"]);
+    for (group, count, bytes, examples) in &groups {
+        table.add_row(row![display_key(group), count, bytes, examples.join("\n")]);
+    }
+    table.add_row(row![
+        "total",
+        keys.len(),
+        keys.iter().map(|e| e.1).sum::<usize>(),
+        ""
+    ]);
+    table.printstd();
+}
+
+#[test]
+fn test_key_filter() {
+    assert_eq!(parse_key_prefix("hex:0a01").unwrap(), vec![0x0a, 0x01]);
+    assert_eq!(parse_key_prefix("base64:AQI=").unwrap(), vec![1, 2]);
+    assert_eq!(parse_key_prefix("utf8:hex:").unwrap(), b"hex:".to_vec());
+    assert_eq!(parse_key_prefix("STATE").unwrap(), b"STATE".to_vec());
+    assert!(parse_key_prefix("hex:zz").is_err());
+    assert!(parse_key_prefix("utf8:").is_err());
+
+    let filter = KeyFilter::parse("hex:01,validators", "validators_of_era").unwrap();
+    assert!(filter.matches(&[1, 2, 3]));
+    assert!(filter.matches(b"validators\x00"));
+    assert!(!filter.matches(b"validators_of_era\x05"));
+    assert!(!filter.matches(b"STATE"));
+    assert_eq!(filter.group_of(&[1, 2, 3]), vec![1]);
+    assert!(KeyFilter::parse("all", "none").unwrap().is_empty());
+    assert_eq!(KeyFilter::default().group_of(b"STATE"), b"STATE".to_vec());
+    assert_eq!(KeyFilter::default().group_of(&[7, 1]), vec![7]);
+
+    assert_eq!(display_key(b"validators\x01\x00"), "validators+0x0100");
+    assert_eq!(display_key(&[0xff]), "0xff");
+    assert_eq!(display_key(b""), "<empty>");
+
+    assert_eq!(decode_key(b"STATE"), "STATE");
+    assert_eq!(
+        decode_key(b"validators\x0c\x00\x00\x00\x00\x00\x00\x00"),
+        "validators+u64(12)"
+    );
+    assert_eq!(
+        decode_key(b"\x05\x0a\x00\x00\x00alice.near"),
+        "0x05+account(alice.near)"
+    );
+    assert_eq!(decode_key(b"\x05alice.near"), "0x05+account(alice.near)");
+    assert_eq!(decode_key(b"\x05\xff\xfe"), "0x05+0xfffe");
+}
@@ -0,0 +1,238 @@
+use crate::near::rpc::client::Client;
+use crate::near::types::NearBalance;
+use near_primitives::hash::CryptoHash;
+use near_primitives::types::{AccountId, Balance, Gas};
+use prettytable::{row, Table};
+
+/// The storage price used when the protocol config can not be fetched, 1 NEAR per 100kb.
+pub const DEFAULT_STORAGE_AMOUNT_PER_BYTE: Balance = 10_000_000_000_000_000_000;
+
+/// The balance an account needs to stake for its storage after its code is replaced, and to pay
+/// for the gas attached to the deploy.
+///
+/// Only the code size changes are counted, state written by a migration is not.
+#[derive(Debug, Clone)]
+pub struct StorageCost {
+    pub account_id: AccountId,
+    pub amount: Balance,
+    pub locked: Balance,
+    pub storage_usage: u64,
+    pub old_code_size: u64,
+    pub new_code_size: u64,
+    pub storage_amount_per_byte: Balance,
+    /// The attached gas at the gas price, the signer is charged for it before the deploy runs.
+    pub gas_reserve: Balance,
+}
+
+impl StorageCost {
+    pub async fn fetch(
+        client: &Client,
+        account_id: AccountId,
+        new_code_size: u64,
+        storage_amount_per_byte: Balance,
+        gas_reserve: Balance,
+    ) -> anyhow::Result<Self> {
+        let account = client.view_account(account_id.clone(), None).await?;
+        let old_code_size = if account.code_hash == CryptoHash::default() {
+            0
+        } else {
+            client.view_code(account_id.clone(), None).await?.code.len() as u64
+        };
+        Ok(Self {
+            account_id,
+            amount: account.amount,
+            locked: account.locked,
+            storage_usage: account.storage_usage,
+            old_code_size,
+            new_code_size,
+            storage_amount_per_byte,
+            gas_reserve,
+        })
+    }
+
+    pub fn storage_usage_after(&self) -> u64 {
+        (self.storage_usage + self.new_code_size).saturating_sub(self.old_code_size)
+    }
+
+    pub fn required_balance(&self) -> Balance {
+        self.storage_usage_after() as Balance * self.storage_amount_per_byte
+    }
+
+    /// Locked balance counts towards storage staking as well.
+    pub fn available_balance(&self) -> Balance {
+        self.amount + self.locked
+    }
+
+    /// The gas is paid before the storage is checked, so it is needed on top of the storage.
+    pub fn top_up(&self) -> Balance {
+        (self.required_balance() + self.gas_reserve).saturating_sub(self.available_balance())
+    }
+
+    /// Fails with the exact top-up if the account can not cover the storage of the new code
+    /// and the gas of the deploy.
+    pub fn check(&self) -> anyhow::Result<()> {
+        if self.top_up() > 0 {
+            anyhow::bail!(
+                "{} needs {} for {} bytes of storage and {} for gas but has {}, top up at least {}",
+                self.account_id,
+                NearBalance::from_yoctonear(self.required_balance()),
+                self.storage_usage_after(),
+                NearBalance::from_yoctonear(self.gas_reserve),
+                NearBalance::from_yoctonear(self.available_balance()),
+                NearBalance::from_yoctonear(self.top_up())
+            );
+        }
+        Ok(())
+    }
+}
+
+/// The balance `attached_gas` costs at the gas price of the latest block.
+pub async fn gas_reserve(client: &Client, attached_gas: Gas) -> anyhow::Result<Balance> {
+    let gas_price = client.view_block(None).await?.header.gas_price;
+    Ok(attached_gas as Balance * gas_price)
+}
+
+/// Reads the storage price from the protocol config of the network.
+pub async fn storage_amount_per_byte(client: &Client) -> Balance {
+    match client.protocol_config().await {
+        Ok(protocol_config) => protocol_config.runtime_config.storage_amount_per_byte,
+        Err(err) => {
+            println!(
+                "Failed to fetch protocol config, use the default storage price {} per byte: {}",
+                NearBalance::from_yoctonear(DEFAULT_STORAGE_AMOUNT_PER_BYTE),
+                err
+            );
+            DEFAULT_STORAGE_AMOUNT_PER_BYTE
+        }
+    }
+}
+
+pub fn print_storage_cost_table(costs: &[StorageCost]) {
+    let mut table = Table::new();
+    table.set_titles(row![
+        "account",
+        "code size",
+        "storage usage",
+        "required",
+        "gas reserve",
+        "available",
+        "top up"
+    ]);
+    for cost in costs {
+        table.add_row(row![
+            cost.account_id,
+            format!("{} -> {}", cost.old_code_size, cost.new_code_size),
+            format!("{} -> {}", cost.storage_usage, cost.storage_usage_after()),
+            NearBalance::from_yoctonear(cost.required_balance()),
+            NearBalance::from_yoctonear(cost.gas_reserve),
+            NearBalance::from_yoctonear(cost.available_balance()),
+            NearBalance::from_yoctonear(cost.top_up())
+        ]);
+    }
+    table.printstd();
+}
+
+/// Storage and keys of an account before and after its state is cleaned.
+#[derive(Debug, Clone)]
+pub struct StorageReclaim {
+    pub account_id: AccountId,
+    pub storage_usage_before: u64,
+    pub storage_usage_after: u64,
+    pub keys_removed: usize,
+    pub keys_left: usize,
+    /// The code the account runs at the end.
+    pub code_hash: CryptoHash,
+    pub storage_amount_per_byte: Balance,
+}
+
+impl StorageReclaim {
+    /// The balance no longer locked for storage, zero if the storage grew.
+    pub fn released_balance(&self) -> Balance {
+        self.storage_usage_before
+            .saturating_sub(self.storage_usage_after) as Balance
+            * self.storage_amount_per_byte
+    }
+}
+
+pub fn print_storage_reclaim_table(reclaims: &[StorageReclaim]) {
+    let mut table = Table::new();
+    table.set_titles(row![
+        "account",
+        "keys removed",
+        "keys left",
+        "storage usage",
+        "released",
+        "code hash"
+    ]);
+    for reclaim in reclaims {
+        table.add_row(row![
+            reclaim.account_id,
+            reclaim.keys_removed,
+            reclaim.keys_left,
+            format!(
+                "{} -> {}",
+                reclaim.storage_usage_before, reclaim.storage_usage_after
+            ),
+            NearBalance::from_yoctonear(reclaim.released_balance()),
+            reclaim.code_hash
+        ]);
+    }
+    table.printstd();
+}
+
+#[test]
+fn test_storage_cost() {
+    let cost = StorageCost {
+        account_id: "anchor.testnet".parse().unwrap(),
+        amount: 5 * 10u128.pow(24),
+        locked: 10u128.pow(24),
+        storage_usage: 500_000,
+        old_code_size: 400_000,
+        new_code_size: 700_000,
+        storage_amount_per_byte: DEFAULT_STORAGE_AMOUNT_PER_BYTE,
+        gas_reserve: 0,
+    };
+    assert_eq!(cost.storage_usage_after(), 800_000);
+    assert_eq!(cost.required_balance(), 8 * 10u128.pow(24));
+    assert_eq!(cost.top_up(), 2 * 10u128.pow(24));
+    assert!(cost.check().is_err());
+
+    let smaller = StorageCost {
+        new_code_size: 100_000,
+        ..cost.clone()
+    };
+    assert_eq!(smaller.storage_usage_after(), 200_000);
+    assert_eq!(smaller.top_up(), 0);
+    assert!(smaller.check().is_ok());
+
+    // Exactly enough for the storage, but the 200 TGas of the deploy at 0.0001 NEAR per TGas
+    // are paid from the same balance.
+    let exact = StorageCost {
+        amount: 7 * 10u128.pow(24),
+        gas_reserve: 200 * 10u128.pow(12) * 100_000_000,
+        ..cost
+    };
+    assert_eq!(exact.required_balance(), exact.available_balance());
+    assert_eq!(exact.top_up(), 2 * 10u128.pow(22));
+    assert!(exact.check().is_err());
+}
+
+#[test]
+fn test_storage_reclaim() {
+    let reclaim = StorageReclaim {
+        account_id: "anchor.testnet".parse().unwrap(),
+        storage_usage_before: 1_300_000,
+        storage_usage_after: 300_000,
+        keys_removed: 20_000,
+        keys_left: 0,
+        code_hash: CryptoHash::default(),
+        storage_amount_per_byte: DEFAULT_STORAGE_AMOUNT_PER_BYTE,
+    };
+    assert_eq!(reclaim.released_balance(), 10 * 10u128.pow(24));
+
+    let grown = StorageReclaim {
+        storage_usage_after: 1_400_000,
+        ..reclaim
+    };
+    assert_eq!(grown.released_balance(), 0);
+}
@@ -1,14 +1,18 @@
 use near_crypto::InMemorySigner;
 use near_primitives::types::AccountId;
 use std::io;
-use std::path::Path;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
 use serde::{Deserialize, Serialize};
 
 use crate::near::constants::{
-    BLOCKPI_MAINNET_RPC_URL, BLOCKPI_TESTNET_RPC_URL, DIR_NAME_MAINNET, DIR_NAME_TESTNET,
-    MAINNET_ARCHIVAL_API_SERVER_URL, MAINNET_TRANSACTION_URL, MAINNET_WALLET_URL, ONE_NEAR,
-    TESTNET_ARCHIVAL_API_SERVER_URL, TESTNET_TRANSACTION_URL, TESTNET_WALLET_URL,
+    BLOCKPI_MAINNET_RPC_URL, BLOCKPI_TESTNET_RPC_URL, DIR_NAME_MAINNET, DIR_NAME_MAINNET_SNAPSHOT,
+    DIR_NAME_TESTNET, DIR_NAME_TESTNET_SNAPSHOT, MAINNET_ARCHIVAL_API_SERVER_URL,
+    MAINNET_OCT_TOKEN_ACCOUNT_ID, MAINNET_TRANSACTION_URL, MAINNET_WALLET_URL, ONE_NEAR,
+    TESTNET_ARCHIVAL_API_SERVER_URL, TESTNET_OCT_TOKEN_ACCOUNT_ID, TESTNET_TRANSACTION_URL,
+    TESTNET_WALLET_URL,
 };
 use crate::near::rpc::client::Client;
 
@@ -24,6 +28,18 @@ impl NearAccountWithKey {
         let content = std::fs::read_to_string(path)?;
         Ok(serde_json::from_str(&content)?)
     }
+
+    /// Writes the key to `<dir>/<account_id>.json`, an existing key file is never overwritten.
+    pub fn save(&self, dir: &Path) -> io::Result<PathBuf> {
+        std::fs::create_dir_all(dir)?;
+        let path = dir.join(format!("{}.json", self.account_id));
+        let mut file = std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+        file.write_all(serde_json::to_string(self)?.as_bytes())?;
+        Ok(path)
+    }
 }
 
 impl From<NearAccountWithKey> for InMemorySigner {
@@ -81,6 +97,20 @@ impl NearEnv {
             Self::Mainnet => DIR_NAME_MAINNET,
         }
     }
+
+    pub fn oct_token_account_id(&self) -> AccountId {
+        match self {
+            Self::Testnet => TESTNET_OCT_TOKEN_ACCOUNT_ID.parse().unwrap(),
+            Self::Mainnet => MAINNET_OCT_TOKEN_ACCOUNT_ID.parse().unwrap(),
+        }
+    }
+
+    pub fn snapshot_dir_name(&self) -> &str {
+        match self {
+            Self::Testnet => DIR_NAME_TESTNET_SNAPSHOT,
+            Self::Mainnet => DIR_NAME_MAINNET_SNAPSHOT,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Default, PartialEq, PartialOrd)]
@@ -114,3 +144,54 @@ impl std::fmt::Display for NearBalance {
         }
     }
 }
+
+impl FromStr for NearBalance {
+    type Err = String;
+
+    /// Parses an amount of NEAR such as `30`, `2.5` or `2.5 NEAR`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let amount = s.trim();
+        let amount = amount
+            .strip_suffix("NEAR")
+            .or_else(|| amount.strip_suffix('N'))
+            .unwrap_or(amount)
+            .trim();
+        let (integer, fraction) = amount.split_once('.').unwrap_or((amount, ""));
+        if fraction.len() > 24 || !fraction.chars().all(|c| c.is_ascii_digit()) {
+            return Err(format!("Invalid NEAR amount: {}", s));
+        }
+        let integer: u128 = integer
+            .parse()
+            .map_err(|_| format!("Invalid NEAR amount: {}", s))?;
+        let fraction: u128 = if fraction.is_empty() {
+            0
+        } else {
+            format!("{:0<24}", fraction).parse().unwrap()
+        };
+        integer
+            .checked_mul(ONE_NEAR)
+            .and_then(|amount| amount.checked_add(fraction))
+            .map(Self::from_yoctonear)
+            .ok_or_else(|| format!("NEAR amount is too large: {}", s))
+    }
+}
+
+#[test]
+fn test_parse_near_balance() {
+    assert_eq!(
+        NearBalance::from_str("30").unwrap().to_yoctonear(),
+        30 * ONE_NEAR
+    );
+    assert_eq!(
+        NearBalance::from_str("2.5 NEAR").unwrap().to_yoctonear(),
+        25 * ONE_NEAR / 10
+    );
+    assert_eq!(
+        NearBalance::from_str("0.000000000000000000000001")
+            .unwrap()
+            .to_yoctonear(),
+        1
+    );
+    assert!(NearBalance::from_str("1.2.3").is_err());
+    assert!(NearBalance::from_str("abc").is_err());
+}
@@ -8,6 +8,12 @@ pub fn get_default_near_account_dir_path(connection_config: &NearEnv) -> PathBuf
     home_dir
 }
 
+pub fn get_default_snapshot_dir_path(connection_config: &NearEnv) -> PathBuf {
+    let mut home_dir = dirs::home_dir().expect("Impossible to get your home dir!");
+    home_dir.push(connection_config.snapshot_dir_name());
+    home_dir
+}
+
 pub fn get_accounts_from_path(path: &Path) -> color_eyre::eyre::Result<Vec<NearAccountWithKey>> {
     let mut accounts: Vec<NearAccountWithKey> = vec![];
     for file in fs::read_dir(path)
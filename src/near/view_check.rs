@@ -0,0 +1,148 @@
+use crate::near::rpc::client::Client;
+use near_primitives::types::AccountId;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// A view call on a contract whose JSON result must equal `expected`.
+///
+/// With `path` set (eg: `$.status.era_number` or `validators[0].account_id`), only the
+/// selected part of the result is compared.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ViewCheck {
+    pub method: String,
+    #[serde(default = "default_view_args")]
+    pub args: String,
+    #[serde(default)]
+    pub path: Option<String>,
+    pub expected: serde_json::Value,
+}
+
+fn default_view_args() -> String {
+    "{}".to_string()
+}
+
+impl ViewCheck {
+    pub async fn run(&self, client: &Client, account_id: &AccountId) -> anyhow::Result<()> {
+        let result: serde_json::Value = client
+            .view(
+                account_id.clone(),
+                self.method.clone(),
+                self.args.clone().into_bytes(),
+            )
+            .await?
+            .json()?;
+        let actual = match &self.path {
+            Some(path) => select_json_path(&result, path)?.ok_or_else(|| {
+                anyhow::anyhow!(
+                    "{}({}) of {} returned {}, which has nothing at {}",
+                    self.method,
+                    self.args,
+                    account_id,
+                    result,
+                    path
+                )
+            })?,
+            None => &result,
+        };
+        if actual != &self.expected {
+            anyhow::bail!(
+                "{}({}){} of {} returned {}, expected {}",
+                self.method,
+                self.args,
+                self.path
+                    .as_ref()
+                    .map(|path| format!(" at {}", path))
+                    .unwrap_or_default(),
+                account_id,
+                actual,
+                self.expected
+            );
+        }
+        Ok(())
+    }
+}
+
+/// A file of `[[checks]]` which is run against every account after an upgrade.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ViewChecks {
+    #[serde(default)]
+    pub checks: Vec<ViewCheck>,
+}
+
+impl ViewChecks {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let content = std::fs::read(path)?;
+        Ok(toml::from_slice(&content)?)
+    }
+
+    pub async fn run(&self, client: &Client, account_id: &AccountId) -> anyhow::Result<()> {
+        for check in &self.checks {
+            check.run(client, account_id).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Selects a value by a dotted path with optional array indexes, a leading `$` is allowed.
+/// Returns `None` if the path does not exist in `value`.
+pub fn select_json_path<'v>(
+    value: &'v serde_json::Value,
+    path: &str,
+) -> anyhow::Result<Option<&'v serde_json::Value>> {
+    let path = path.trim();
+    let path = path.strip_prefix('$').unwrap_or(path);
+    let mut current = value;
+    for segment in path.split('.').filter(|segment| !segment.is_empty()) {
+        let (field, indexes) = match segment.find('[') {
+            Some(start) => segment.split_at(start),
+            None => (segment, ""),
+        };
+        if !field.is_empty() {
+            current = match current.get(field) {
+                Some(next) => next,
+                None => return Ok(None),
+            };
+        }
+        let mut indexes = indexes;
+        while !indexes.is_empty() {
+            let end = indexes
+                .find(']')
+                .filter(|_| indexes.starts_with('['))
+                .ok_or_else(|| anyhow::anyhow!("Invalid json path segment '{}'", segment))?;
+            let index: usize = indexes[1..end]
+                .parse()
+                .map_err(|_| anyhow::anyhow!("Invalid json path index in '{}'", segment))?;
+            current = match current.get(index) {
+                Some(next) => next,
+                None => return Ok(None),
+            };
+            indexes = &indexes[end + 1..];
+        }
+    }
+    Ok(Some(current))
+}
+
+#[test]
+fn test_select_json_path() {
+    let value = serde_json::json!({
+        "status": {"era_number": 3},
+        "validators": [{"account_id": "a.testnet"}, {"account_id": "b.testnet"}],
+        "matrix": [[1, 2], [3, 4]]
+    });
+    assert_eq!(
+        select_json_path(&value, "$.status.era_number").unwrap(),
+        Some(&serde_json::json!(3))
+    );
+    assert_eq!(
+        select_json_path(&value, "validators[1].account_id").unwrap(),
+        Some(&serde_json::json!("b.testnet"))
+    );
+    assert_eq!(
+        select_json_path(&value, "matrix[1][0]").unwrap(),
+        Some(&serde_json::json!(3))
+    );
+    assert_eq!(select_json_path(&value, "$").unwrap(), Some(&value));
+    assert_eq!(select_json_path(&value, "status.missing").unwrap(), None);
+    assert_eq!(select_json_path(&value, "validators[5]").unwrap(), None);
+    assert!(select_json_path(&value, "validators[x]").is_err());
+}
@@ -0,0 +1,409 @@
+use crate::near::rpc::client::Client;
+use color_eyre::owo_colors::OwoColorize;
+use near_primitives::hash::CryptoHash;
+use wasmparser::{ExternalKind, FuncType, Parser, Payload, Type, TypeRef, ValType};
+
+/// The contract size limit used when the protocol config can not be fetched.
+pub const DEFAULT_MAX_CONTRACT_SIZE: u64 = 4 * 1024 * 1024;
+
+/// The host functions a NEAR contract may import from `env`, with their param and result
+/// counts. All of them take and return `i64`, except `abort` which takes four `i32`.
+const HOST_FUNCTIONS: &[(&str, usize, usize)] = &[
+    ("read_register", 2, 0),
+    ("register_len", 1, 1),
+    ("write_register", 3, 0),
+    ("current_account_id", 1, 0),
+    ("signer_account_id", 1, 0),
+    ("signer_account_pk", 1, 0),
+    ("predecessor_account_id", 1, 0),
+    ("input", 1, 0),
+    ("block_index", 0, 1),
+    ("block_timestamp", 0, 1),
+    ("epoch_height", 0, 1),
+    ("storage_usage", 0, 1),
+    ("account_balance", 1, 0),
+    ("account_locked_balance", 1, 0),
+    ("attached_deposit", 1, 0),
+    ("prepaid_gas", 0, 1),
+    ("used_gas", 0, 1),
+    ("random_seed", 1, 0),
+    ("sha256", 3, 0),
+    ("keccak256", 3, 0),
+    ("keccak512", 3, 0),
+    ("ripemd160", 3, 0),
+    ("ecrecover", 7, 1),
+    ("ed25519_verify", 6, 1),
+    ("value_return", 2, 0),
+    ("panic", 0, 0),
+    ("panic_utf8", 2, 0),
+    ("log_utf8", 2, 0),
+    ("log_utf16", 2, 0),
+    ("abort", 4, 0),
+    ("promise_create", 8, 1),
+    ("promise_then", 9, 1),
+    ("promise_and", 2, 1),
+    ("promise_batch_create", 2, 1),
+    ("promise_batch_then", 3, 1),
+    ("promise_batch_action_create_account", 1, 0),
+    ("promise_batch_action_deploy_contract", 3, 0),
+    ("promise_batch_action_function_call", 7, 0),
+    ("promise_batch_action_function_call_weight", 8, 0),
+    ("promise_batch_action_transfer", 2, 0),
+    ("promise_batch_action_stake", 4, 0),
+    ("promise_batch_action_add_key_with_full_access", 4, 0),
+    ("promise_batch_action_add_key_with_function_call", 9, 0),
+    ("promise_batch_action_delete_key", 3, 0),
+    ("promise_batch_action_delete_account", 3, 0),
+    ("promise_results_count", 0, 1),
+    ("promise_result", 2, 1),
+    ("promise_return", 1, 0),
+    ("storage_write", 5, 1),
+    ("storage_read", 3, 1),
+    ("storage_remove", 3, 1),
+    ("storage_has_key", 2, 1),
+    ("storage_iter_prefix", 2, 1),
+    ("storage_iter_range", 4, 1),
+    ("storage_iter_next", 3, 1),
+    ("validator_stake", 3, 0),
+    ("validator_total_stake", 1, 0),
+    ("alt_bn128_g1_multiexp", 3, 0),
+    ("alt_bn128_g1_sum", 3, 0),
+    ("alt_bn128_pairing_check", 2, 1),
+];
+
+fn expected_host_signature(name: &str) -> Option<(Vec<ValType>, Vec<ValType>)> {
+    HOST_FUNCTIONS
+        .iter()
+        .find(|(host_name, _, _)| *host_name == name)
+        .map(|(_, params, results)| {
+            let param_type = if name == "abort" {
+                ValType::I32
+            } else {
+                ValType::I64
+            };
+            (vec![param_type; *params], vec![ValType::I64; *results])
+        })
+}
+
+fn format_signature(params: &[ValType], results: &[ValType]) -> String {
+    let join = |types: &[ValType]| {
+        types
+            .iter()
+            .map(|t| format!("{:?}", t).to_lowercase())
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    format!("({}) -> ({})", join(params), join(results))
+}
+
+#[derive(Debug, Clone)]
+pub struct ExportedFunction {
+    pub name: String,
+    pub params: Vec<ValType>,
+    pub results: Vec<ValType>,
+}
+
+impl ExportedFunction {
+    /// Only functions without params and results can be called as contract methods.
+    pub fn is_callable(&self) -> bool {
+        self.params.is_empty() && self.results.is_empty()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct WasmInspection {
+    pub size: u64,
+    pub code_hash: CryptoHash,
+    pub exports: Vec<ExportedFunction>,
+    /// Imports which are not provided by the NEAR runtime or have a different signature.
+    pub import_problems: Vec<String>,
+}
+
+impl WasmInspection {
+    pub fn inspect(code: &[u8]) -> anyhow::Result<Self> {
+        let mut types: Vec<FuncType> = vec![];
+        // Function index space: imported functions first, then the defined ones.
+        let mut function_types: Vec<u32> = vec![];
+        let mut exports: Vec<(String, u32)> = vec![];
+        let mut import_problems = vec![];
+
+        for payload in Parser::new(0).parse_all(code) {
+            match payload? {
+                Payload::TypeSection(reader) => {
+                    for ty in reader {
+                        let Type::Func(func_type) = ty?;
+                        types.push(func_type);
+                    }
+                }
+                Payload::ImportSection(reader) => {
+                    for import in reader {
+                        let import = import?;
+                        match import.ty {
+                            TypeRef::Func(type_index) => {
+                                function_types.push(type_index);
+                                let func_type =
+                                    types.get(type_index as usize).ok_or_else(|| {
+                                        anyhow::anyhow!("Invalid type index {}", type_index)
+                                    })?;
+                                if let Some(problem) =
+                                    check_function_import(import.module, import.name, func_type)
+                                {
+                                    import_problems.push(problem);
+                                }
+                            }
+                            TypeRef::Memory(_)
+                                if import.module == "env" && import.name == "memory" => {}
+                            _ => import_problems.push(format!(
+                                "{}.{}: unsupported {:?} import",
+                                import.module, import.name, import.ty
+                            )),
+                        }
+                    }
+                }
+                Payload::FunctionSection(reader) => {
+                    for type_index in reader {
+                        function_types.push(type_index?);
+                    }
+                }
+                Payload::ExportSection(reader) => {
+                    for export in reader {
+                        let export = export?;
+                        if export.kind == ExternalKind::Func {
+                            exports.push((export.name.to_string(), export.index));
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let exports = exports
+            .into_iter()
+            .map(|(name, function_index)| {
+                let func_type = function_types
+                    .get(function_index as usize)
+                    .and_then(|type_index| types.get(*type_index as usize))
+                    .ok_or_else(|| {
+                        anyhow::anyhow!("Invalid function index {} of {}", function_index, name)
+                    })?;
+                Ok(ExportedFunction {
+                    name,
+                    params: func_type.params().to_vec(),
+                    results: func_type.results().to_vec(),
+                })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        Ok(Self {
+            size: code.len() as u64,
+            code_hash: CryptoHash::hash_bytes(code),
+            exports,
+            import_problems,
+        })
+    }
+
+    /// Fails if `method_name` is not exported as a callable contract method.
+    pub fn check_method(&self, method_name: &str) -> anyhow::Result<()> {
+        match self.exports.iter().find(|e| e.name == method_name) {
+            Some(export) if export.is_callable() => Ok(()),
+            Some(export) => anyhow::bail!(
+                "Method {} is exported with signature {}, it can not be called as a contract method",
+                method_name,
+                format_signature(&export.params, &export.results)
+            ),
+            None => {
+                let similar: Vec<&str> = self
+                    .exports
+                    .iter()
+                    .filter(|e| e.is_callable() && edit_distance(&e.name, method_name) <= 2)
+                    .map(|e| e.name.as_str())
+                    .collect();
+                if similar.is_empty() {
+                    anyhow::bail!("Method {} is not exported by the wasm", method_name)
+                } else {
+                    anyhow::bail!(
+                        "Method {} is not exported by the wasm, did you mean {}?",
+                        method_name,
+                        similar.join(" or ")
+                    )
+                }
+            }
+        }
+    }
+
+    pub fn check_size(&self, max_contract_size: u64) -> anyhow::Result<()> {
+        if self.size > max_contract_size {
+            anyhow::bail!(
+                "The wasm is {} bytes, larger than the contract size limit {} bytes",
+                self.size,
+                max_contract_size
+            );
+        }
+        Ok(())
+    }
+
+    pub fn check_imports(&self) -> anyhow::Result<()> {
+        if !self.import_problems.is_empty() {
+            anyhow::bail!(
+                "The wasm has imports the NEAR runtime does not provide:\n{}",
+                self.import_problems.join("\n")
+            );
+        }
+        Ok(())
+    }
+
+    pub fn print(&self, max_contract_size: u64) {
+        println!("code hash: {}", self.code_hash);
+        let size = format!("size: {} / {} bytes", self.size, max_contract_size);
+        if self.size > max_contract_size {
+            println!("{}", size.red());
+        } else {
+            println!("{}", size);
+        }
+        println!("exported methods:");
+        for export in &self.exports {
+            if export.is_callable() {
+                println!("  {}", export.name);
+            } else {
+                println!(
+                    "  {} {}",
+                    export.name,
+                    format!(
+                        "{} (not callable)",
+                        format_signature(&export.params, &export.results)
+                    )
+                    .yellow()
+                );
+            }
+        }
+        if self.import_problems.is_empty() {
+            println!("imports: {}", "ok".green());
+        } else {
+            println!("imports:");
+            for problem in &self.import_problems {
+                println!("  {}", problem.red());
+            }
+        }
+    }
+}
+
+fn check_function_import(module: &str, name: &str, func_type: &FuncType) -> Option<String> {
+    if module != "env" {
+        return Some(format!(
+            "{}.{}: unknown import module {}",
+            module, name, module
+        ));
+    }
+    let (params, results) = match expected_host_signature(name) {
+        Some(signature) => signature,
+        None => return Some(format!("env.{}: unknown host function", name)),
+    };
+    if func_type.params() != params.as_slice() || func_type.results() != results.as_slice() {
+        return Some(format!(
+            "env.{}: signature {} does not match the host function {}",
+            name,
+            format_signature(func_type.params(), func_type.results()),
+            format_signature(&params, &results)
+        ));
+    }
+    None
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            current[j + 1] = (previous[j] + usize::from(ca != *cb))
+                .min(previous[j + 1] + 1)
+                .min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Reads the contract size limit from the protocol config of the network.
+pub async fn max_contract_size(client: &Client) -> u64 {
+    match client.protocol_config().await {
+        Ok(protocol_config) => {
+            protocol_config
+                .runtime_config
+                .wasm_config
+                .limit_config
+                .max_contract_size
+        }
+        Err(err) => {
+            println!(
+                "Failed to fetch protocol config, use the default contract size limit {}: {}",
+                DEFAULT_MAX_CONTRACT_SIZE, err
+            );
+            DEFAULT_MAX_CONTRACT_SIZE
+        }
+    }
+}
+
+/// Inspects `code` before it is deployed and fails on anything which would make the deploy
+/// or the call to `method_name` fail.
+pub async fn pre_check_deploy(
+    client: &Client,
+    code: &[u8],
+    method_name: &str,
+) -> anyhow::Result<WasmInspection> {
+    let inspection = WasmInspection::inspect(code)?;
+    inspection.check_size(max_contract_size(client).await)?;
+    inspection.check_imports()?;
+    inspection.check_method(method_name)?;
+    Ok(inspection)
+}
+
+#[test]
+fn test_inspect_wasm() {
+    let encode_name = |name: &str| {
+        let mut bytes = vec![name.len() as u8];
+        bytes.extend(name.as_bytes());
+        bytes
+    };
+    let mut imports = vec![3];
+    for (module, name, type_index) in [
+        ("env", "panic", 0),
+        ("env", "log_utf8", 1),
+        ("wasi", "fd_write", 0),
+    ] {
+        imports.extend(encode_name(module));
+        imports.extend(encode_name(name));
+        imports.extend([0x00, type_index]);
+    }
+    let mut exports = vec![2];
+    exports.extend(encode_name("migrate_state"));
+    exports.extend([0x00, 3]);
+    exports.extend(encode_name("helper"));
+    exports.extend([0x00, 4]);
+    let mut code = b"\0asm\x01\0\0\0".to_vec();
+    for (id, content) in [
+        // () -> (), (i64) -> ()
+        (1, vec![2, 0x60, 0, 0, 0x60, 1, 0x7e, 0]),
+        (2, imports),
+        (3, vec![2, 0, 1]),
+        (7, exports),
+        (10, vec![2, 2, 0, 0x0b, 2, 0, 0x0b]),
+    ] {
+        code.push(id);
+        code.push(content.len() as u8);
+        code.extend(content);
+    }
+
+    let inspection = WasmInspection::inspect(&code).unwrap();
+    assert_eq!(inspection.size, code.len() as u64);
+    assert_eq!(inspection.exports.len(), 2);
+    assert!(inspection.check_method("migrate_state").is_ok());
+    let err = inspection.check_method("migrate_stat").unwrap_err();
+    assert!(err.to_string().contains("did you mean migrate_state"));
+    assert!(inspection.check_method("helper").is_err());
+    assert_eq!(inspection.import_problems.len(), 2);
+    assert!(inspection.import_problems[0].starts_with("env.log_utf8"));
+    assert!(inspection.import_problems[1].starts_with("wasi.fd_write"));
+    assert!(inspection.check_size(code.len() as u64).is_ok());
+    assert!(inspection.check_size(code.len() as u64 - 1).is_err());
+}
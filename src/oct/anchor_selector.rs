@@ -0,0 +1,137 @@
+use crate::near::rpc::client::Client;
+use crate::near::types::NearAccountWithKey;
+use crate::oct::contracts::registry::RegistryContract;
+use appchain_registry::types::AppchainState;
+use near_crypto::InMemorySigner;
+use near_primitives::hash::CryptoHash;
+use near_primitives::types::AccountId;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+pub const ALL_APPCHAIN_STATES: &str = "all";
+pub const ANY_CODE_HASH: &str = "any";
+
+/// Selects the anchors(`<appchain_id>.<registry>`) of all appchains in a registry.
+///
+/// `appchain_states` is `all` or a comma separated list of `AppchainState`s, eg: `Active,Frozen`.
+/// `code_hash` is `any` or the code hash the anchors should currently be running.
+pub async fn select_anchors_of_registry(
+    client: &Client,
+    registry_account_id: AccountId,
+    appchain_states: &str,
+    code_hash: &str,
+) -> anyhow::Result<Vec<AccountId>> {
+    let states = parse_appchain_states(appchain_states)?;
+    let code_hash: Option<CryptoHash> = if code_hash.eq_ignore_ascii_case(ANY_CODE_HASH) {
+        None
+    } else {
+        Some(
+            CryptoHash::from_str(code_hash)
+                .map_err(|err| anyhow::anyhow!("Invalid code hash '{}': {}", code_hash, err))?,
+        )
+    };
+
+    let registry = RegistryContract::new(registry_account_id, client);
+    let mut anchor_ids = vec![];
+    for appchain_id in registry.get_appchain_ids().await? {
+        let anchor_id = registry.anchor_account_id_of(&appchain_id)?;
+        if let Some(states) = &states {
+            let appchain_state = registry
+                .get_appchain_status_of(appchain_id.clone())
+                .await?
+                .appchain_state;
+            if !states.contains(&format!("{:?}", appchain_state).to_lowercase()) {
+                println!(
+                    "Skip {}, the appchain state is {:?}",
+                    anchor_id, appchain_state
+                );
+                continue;
+            }
+        }
+        if let Some(code_hash) = &code_hash {
+            let current_code_hash = client.view_code_hash(anchor_id.clone()).await?;
+            if current_code_hash.as_ref() != Some(code_hash) {
+                println!(
+                    "Skip {}, the code hash is {}",
+                    anchor_id,
+                    current_code_hash
+                        .map(|hash| hash.to_string())
+                        .unwrap_or_else(|| "<no contract>".to_string())
+                );
+                continue;
+            }
+        }
+        anchor_ids.push(anchor_id);
+    }
+    Ok(anchor_ids)
+}
+
+/// All states of an appchain in the registry.
+fn appchain_state_names() -> Vec<String> {
+    use AppchainState::*;
+    [
+        Registered, Audited, Voting, Booting, Active, Frozen, Broken, Dead,
+    ]
+    .iter()
+    .map(|state| format!("{:?}", state))
+    .collect()
+}
+
+/// Parses `all` or a comma separated list of `AppchainState`s into lowercase state names.
+fn parse_appchain_states(appchain_states: &str) -> anyhow::Result<Option<Vec<String>>> {
+    if appchain_states.eq_ignore_ascii_case(ALL_APPCHAIN_STATES) {
+        return Ok(None);
+    }
+    let valid_names = appchain_state_names();
+    appchain_states
+        .split(',')
+        .map(|state| {
+            let state = state.trim().to_lowercase();
+            if valid_names.iter().any(|e| e.to_lowercase() == state) {
+                Ok(state)
+            } else {
+                Err(anyhow::anyhow!(
+                    "Unknown appchain state '{}', expected 'all' or some of {}",
+                    state,
+                    valid_names.join(", ")
+                ))
+            }
+        })
+        .collect::<anyhow::Result<Vec<_>>>()
+        .map(Some)
+}
+
+/// Splits `account_ids` into the signers found in `credentials` and the account ids without
+/// credentials.
+pub fn match_credentials(
+    account_ids: Vec<AccountId>,
+    credentials: Vec<NearAccountWithKey>,
+) -> (Vec<InMemorySigner>, Vec<AccountId>) {
+    let mut credentials: HashMap<AccountId, NearAccountWithKey> = credentials
+        .into_iter()
+        .map(|e| (e.account_id.clone(), e))
+        .collect();
+    let mut signers = vec![];
+    let mut missing = vec![];
+    for account_id in account_ids {
+        match credentials.remove(&account_id) {
+            Some(account) => signers.push(account.into()),
+            None => missing.push(account_id),
+        }
+    }
+    (signers, missing)
+}
+
+#[test]
+fn test_parse_appchain_states() {
+    assert_eq!(parse_appchain_states("ALL").unwrap(), None);
+    assert_eq!(
+        parse_appchain_states("Active, frozen").unwrap(),
+        Some(vec!["active".to_string(), "frozen".to_string()])
+    );
+    let error = parse_appchain_states("Active,Runing")
+        .unwrap_err()
+        .to_string();
+    assert!(error.contains("'runing'"));
+    assert!(error.contains("Registered, Audited, Voting, Booting, Active, Frozen, Broken, Dead"));
+}
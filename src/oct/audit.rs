@@ -0,0 +1,473 @@
+use crate::near::rpc::client::Client;
+use crate::oct::contracts::anchor::AnchorContract;
+use crate::oct::scan::{scan_progress_bar, ActiveAnchor, AppchainScanResult};
+use appchain_anchor::types::{AppchainDelegator, AppchainValidator, ProtocolSettings};
+use color_eyre::owo_colors::OwoColorize;
+use futures::stream::{self, StreamExt};
+use near_primitives::types::AccountId;
+use prettytable::{row, Cell, Row, Table};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Stake is in OCT.
+const OCT_DECIMALS: i32 = 18;
+
+/// The protocol settings of an anchor the stake is audited against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StakeLimits {
+    pub maximum_validator_stake_percent: u16,
+    pub minimum_validator_count: u64,
+    pub maximum_validator_count: u64,
+    pub maximum_validators_per_delegator: u64,
+}
+
+impl From<&ProtocolSettings> for StakeLimits {
+    fn from(settings: &ProtocolSettings) -> Self {
+        Self {
+            maximum_validator_stake_percent: settings.maximum_validator_stake_percent,
+            minimum_validator_count: settings.minimum_validator_count.0,
+            maximum_validator_count: settings.maximum_validator_count.0,
+            maximum_validators_per_delegator: settings.maximum_validators_per_delegator.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StakeViolation {
+    StakeShareTooHigh {
+        validator_id: AccountId,
+        /// Share of the total stake in basis points.
+        share_bps: u128,
+        maximum_percent: u16,
+    },
+    TooFewValidators {
+        count: u64,
+        minimum: u64,
+    },
+    TooManyValidators {
+        count: u64,
+        maximum: u64,
+    },
+    TooManyValidatorsPerDelegator {
+        delegator_id: AccountId,
+        count: u64,
+        maximum: u64,
+    },
+}
+
+impl fmt::Display for StakeViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StakeViolation::StakeShareTooHigh {
+                validator_id,
+                share_bps,
+                maximum_percent,
+            } => write!(
+                f,
+                "{} has {} of the total stake, more than the maximum {}%",
+                validator_id,
+                format_share(*share_bps),
+                maximum_percent
+            ),
+            StakeViolation::TooFewValidators { count, minimum } => write!(
+                f,
+                "{} validators, fewer than the minimum {}",
+                count, minimum
+            ),
+            StakeViolation::TooManyValidators { count, maximum } => {
+                write!(f, "{} validators, more than the maximum {}", count, maximum)
+            }
+            StakeViolation::TooManyValidatorsPerDelegator {
+                delegator_id,
+                count,
+                maximum,
+            } => write!(
+                f,
+                "{} delegates to {} validators, more than the maximum {}",
+                delegator_id, count, maximum
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidatorStake {
+    pub validator_id: AccountId,
+    pub total_stake: u128,
+    /// Share of the total stake in basis points.
+    pub share_bps: u128,
+    pub delegator_count: usize,
+    pub is_unbonding: bool,
+}
+
+/// The stake distribution of an anchor and how it complies with its protocol settings.
+#[derive(Debug, Clone)]
+pub struct StakeAudit {
+    pub appchain_id: String,
+    pub anchor_account_id: AccountId,
+    pub limits: StakeLimits,
+    pub total_stake: u128,
+    pub validators: Vec<ValidatorStake>,
+    /// Delegators and the validators they delegate to, for those delegating to more than one.
+    pub multi_validator_delegators: BTreeMap<AccountId, Vec<AccountId>>,
+    pub violations: Vec<StakeViolation>,
+}
+
+fn format_share(share_bps: u128) -> String {
+    format!("{}.{:02}%", share_bps / 100, share_bps % 100)
+}
+
+fn to_oct(amount: u128) -> f64 {
+    amount as f64 / 10f64.powi(OCT_DECIMALS)
+}
+
+fn account_id_of(id: &near_sdk::AccountId) -> anyhow::Result<AccountId> {
+    Ok(AccountId::try_from(id.to_string())?)
+}
+
+/// Audits the validators of an anchor with the delegators of each validator.
+pub fn audit_stake(
+    appchain_id: &str,
+    anchor_account_id: &AccountId,
+    limits: StakeLimits,
+    validators: &[AppchainValidator],
+    delegators: &[Vec<AppchainDelegator>],
+) -> anyhow::Result<StakeAudit> {
+    let total_stake = validators.iter().map(|e| e.total_stake.0).sum::<u128>();
+    let mut violations = vec![];
+
+    let count = validators.len() as u64;
+    if count < limits.minimum_validator_count {
+        violations.push(StakeViolation::TooFewValidators {
+            count,
+            minimum: limits.minimum_validator_count,
+        });
+    }
+    if count > limits.maximum_validator_count {
+        violations.push(StakeViolation::TooManyValidators {
+            count,
+            maximum: limits.maximum_validator_count,
+        });
+    }
+
+    let mut validator_stakes = vec![];
+    for (index, validator) in validators.iter().enumerate() {
+        let validator_id = account_id_of(&validator.validator_id)?;
+        let share_bps = validator
+            .total_stake
+            .0
+            .saturating_mul(10_000)
+            .checked_div(total_stake)
+            .unwrap_or_default();
+        if share_bps > limits.maximum_validator_stake_percent as u128 * 100 {
+            violations.push(StakeViolation::StakeShareTooHigh {
+                validator_id: validator_id.clone(),
+                share_bps,
+                maximum_percent: limits.maximum_validator_stake_percent,
+            });
+        }
+        validator_stakes.push(ValidatorStake {
+            validator_id,
+            total_stake: validator.total_stake.0,
+            share_bps,
+            delegator_count: delegators.get(index).map(|e| e.len()).unwrap_or_default(),
+            is_unbonding: validator.is_unbonding,
+        });
+    }
+    validator_stakes.sort_by(|a, b| {
+        b.total_stake
+            .cmp(&a.total_stake)
+            .then(a.validator_id.cmp(&b.validator_id))
+    });
+
+    let mut validators_of_delegator = BTreeMap::<AccountId, Vec<AccountId>>::new();
+    for delegator in delegators.iter().flatten() {
+        let validator_id = account_id_of(&delegator.validator_id)?;
+        let validator_ids = validators_of_delegator
+            .entry(account_id_of(&delegator.delegator_id)?)
+            .or_default();
+        if !validator_ids.contains(&validator_id) {
+            validator_ids.push(validator_id);
+        }
+    }
+    validators_of_delegator.retain(|_, validator_ids| validator_ids.len() > 1);
+    for (delegator_id, validator_ids) in &validators_of_delegator {
+        if validator_ids.len() as u64 > limits.maximum_validators_per_delegator {
+            violations.push(StakeViolation::TooManyValidatorsPerDelegator {
+                delegator_id: delegator_id.clone(),
+                count: validator_ids.len() as u64,
+                maximum: limits.maximum_validators_per_delegator,
+            });
+        }
+    }
+
+    Ok(StakeAudit {
+        appchain_id: appchain_id.to_string(),
+        anchor_account_id: anchor_account_id.clone(),
+        limits,
+        total_stake,
+        validators: validator_stakes,
+        multi_validator_delegators: validators_of_delegator,
+        violations,
+    })
+}
+
+/// Reads the validators of the current era of every active anchor and the delegators of each
+/// validator with at most `concurrency` requests in flight, then audits them.
+///
+/// Results are in the order of `anchors`, an error of an anchor is reported as `Failed`
+/// without stopping the others.
+pub async fn fetch_stake_audits(
+    client: &Client,
+    anchors: Vec<Result<ActiveAnchor, AppchainScanResult>>,
+    concurrency: usize,
+) -> anyhow::Result<Vec<Result<StakeAudit, AppchainScanResult>>> {
+    let progress = scan_progress_bar(anchors.len(), "validator lists")?;
+    let progress_ref = &progress;
+    let validator_lists: Vec<Result<(ActiveAnchor, Vec<AppchainValidator>), AppchainScanResult>> =
+        stream::iter(anchors.into_iter().map(|anchor| async move {
+            let anchor = anchor?;
+            let validators = AnchorContract::new(anchor.anchor_account_id.clone(), client)
+                .get_validator_list_of(None)
+                .await;
+            progress_ref.inc(1);
+            match validators {
+                Ok(validators) => Ok((anchor, validators)),
+                Err(err) => Err(AppchainScanResult::Failed {
+                    appchain_id: anchor.appchain_id,
+                    error: format!("Failed to get validator list, error: {}", err),
+                }),
+            }
+        }))
+        .buffered(concurrency.max(1))
+        .collect()
+        .await;
+    progress.finish_and_clear();
+
+    let requests = validator_lists
+        .iter()
+        .filter_map(|e| e.as_ref().ok())
+        .flat_map(|(anchor, validators)| validators.iter().map(move |e| (anchor, e)))
+        .collect::<Vec<_>>();
+    let progress = scan_progress_bar(requests.len(), "validators")?;
+    let progress_ref = &progress;
+    let delegator_results: Vec<(String, anyhow::Result<Vec<AppchainDelegator>>)> =
+        stream::iter(requests.into_iter().map(|(anchor, validator)| async move {
+            let anchor_contract = AnchorContract::new(anchor.anchor_account_id.clone(), client);
+            let result = match account_id_of(&validator.validator_id) {
+                Ok(validator_id) => anchor_contract
+                    .get_delegators_of_validator_in_era(None, validator_id)
+                    .await
+                    .map_err(|err| {
+                        anyhow::anyhow!(
+                            "Failed to get delegators of {}, error: {}",
+                            validator.validator_id,
+                            err
+                        )
+                    }),
+                Err(err) => Err(err),
+            };
+            progress_ref.inc(1);
+            (anchor.appchain_id.clone(), result)
+        }))
+        .buffered(concurrency.max(1))
+        .collect()
+        .await;
+    progress.finish_and_clear();
+
+    let mut delegator_results = delegator_results.into_iter().peekable();
+    let mut results = vec![];
+    for validator_list in validator_lists {
+        let (anchor, validators) = match validator_list {
+            Ok(validator_list) => validator_list,
+            Err(result) => {
+                results.push(Err(result));
+                continue;
+            }
+        };
+        let mut delegators = vec![];
+        let mut error = None;
+        while let Some((_, result)) =
+            delegator_results.next_if(|(appchain_id, _)| appchain_id == &anchor.appchain_id)
+        {
+            match result {
+                Ok(result) => delegators.push(result),
+                Err(err) => {
+                    error.get_or_insert(err.to_string());
+                }
+            }
+        }
+        let audit = match error {
+            Some(error) => Err(anyhow::anyhow!(error)),
+            None => audit_stake(
+                &anchor.appchain_id,
+                &anchor.anchor_account_id,
+                StakeLimits::from(&anchor.settings),
+                &validators,
+                &delegators,
+            ),
+        };
+        results.push(audit.map_err(|err| AppchainScanResult::Failed {
+            appchain_id: anchor.appchain_id,
+            error: err.to_string(),
+        }));
+    }
+    Ok(results)
+}
+
+pub fn print_stake_audit(audit: &StakeAudit) {
+    let limits = &audit.limits;
+    let mut table = Table::new();
+    table.set_titles(Row::new(vec![Cell::new(
+        format!(
+            "{} stake, {} OCT of {} validators (allowed {} to {}), at most {}% each",
+            audit.anchor_account_id.blue(),
+            to_oct(audit.total_stake),
+            audit.validators.len(),
+            limits.minimum_validator_count,
+            limits.maximum_validator_count,
+            limits.maximum_validator_stake_percent
+        )
+        .as_str(),
+    )
+    .style_spec("H4")]));
+    table.add_row(row![
+        "validator",
+        "total stake (OCT)",
+        "share",
+        "delegators"
+    ]);
+    for validator in &audit.validators {
+        let share = format_share(validator.share_bps);
+        let share = if validator.share_bps > limits.maximum_validator_stake_percent as u128 * 100 {
+            share.red().to_string()
+        } else {
+            share
+        };
+        let validator_id = if validator.is_unbonding {
+            format!("{} (unbonding)", validator.validator_id)
+        } else {
+            validator.validator_id.to_string()
+        };
+        table.add_row(row![
+            validator_id,
+            to_oct(validator.total_stake),
+            share,
+            validator.delegator_count
+        ]);
+    }
+    table.printstd();
+
+    if !audit.multi_validator_delegators.is_empty() {
+        let mut delegators = Table::new();
+        delegators.set_titles(Row::new(vec![Cell::new(
+            format!(
+                "delegators of more than one validator, at most {} validators each",
+                limits.maximum_validators_per_delegator
+            )
+            .as_str(),
+        )
+        .style_spec("H2")]));
+        for (delegator_id, validator_ids) in &audit.multi_validator_delegators {
+            let count = if validator_ids.len() as u64 > limits.maximum_validators_per_delegator {
+                validator_ids.len().red().to_string()
+            } else {
+                validator_ids.len().to_string()
+            };
+            delegators.add_row(row![
+                delegator_id,
+                format!(
+                    "{}: {}",
+                    count,
+                    validator_ids
+                        .iter()
+                        .map(|e| e.as_str())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )
+            ]);
+        }
+        delegators.printstd();
+    }
+
+    if audit.violations.is_empty() {
+        println!("{}\n", "No violation of the protocol settings.".green());
+    } else {
+        for violation in &audit.violations {
+            println!("{}", format!("Violation: {}", violation).red());
+        }
+        println!();
+    }
+}
+
+#[test]
+fn test_audit_stake() {
+    use near_sdk::json_types::{U128, U64};
+
+    let validator = |validator_id: &str, total_stake: u128| AppchainValidator {
+        validator_id: validator_id.parse().unwrap(),
+        validator_id_in_appchain: String::new(),
+        deposit_amount: U128(total_stake),
+        total_stake: U128(total_stake),
+        delegators_count: U64(0),
+        can_be_delegated_to: true,
+        is_unbonding: false,
+    };
+    let delegator = |delegator_id: &str, validator_id: &str| AppchainDelegator {
+        delegator_id: delegator_id.parse().unwrap(),
+        validator_id: validator_id.parse().unwrap(),
+        delegation_amount: U128(1),
+    };
+    let limits = StakeLimits {
+        maximum_validator_stake_percent: 50,
+        minimum_validator_count: 4,
+        maximum_validator_count: 10,
+        maximum_validators_per_delegator: 1,
+    };
+    let audit = audit_stake(
+        "fusotao",
+        &"fusotao.octopus-registry.near".parse().unwrap(),
+        limits,
+        &[
+            validator("alice.near", 300),
+            validator("bob.near", 100),
+            validator("carol.near", 100),
+        ],
+        &[
+            vec![delegator("dave.near", "alice.near")],
+            vec![
+                delegator("dave.near", "bob.near"),
+                delegator("erin.near", "bob.near"),
+            ],
+            vec![],
+        ],
+    )
+    .unwrap();
+    assert_eq!(audit.total_stake, 500);
+    assert_eq!(audit.validators[0].validator_id.as_str(), "alice.near");
+    assert_eq!(audit.validators[0].share_bps, 6000);
+    assert_eq!(audit.validators[1].delegator_count, 2);
+    assert_eq!(
+        audit.violations,
+        vec![
+            StakeViolation::TooFewValidators {
+                count: 3,
+                minimum: 4
+            },
+            StakeViolation::StakeShareTooHigh {
+                validator_id: "alice.near".parse().unwrap(),
+                share_bps: 6000,
+                maximum_percent: 50
+            },
+            StakeViolation::TooManyValidatorsPerDelegator {
+                delegator_id: "dave.near".parse().unwrap(),
+                count: 2,
+                maximum: 1
+            },
+        ]
+    );
+    assert_eq!(
+        audit.violations[1].to_string(),
+        "alice.near has 60.00% of the total stake, more than the maximum 50%"
+    );
+}
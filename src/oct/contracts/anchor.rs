@@ -1,7 +1,10 @@
-use near_primitives::types::AccountId;
+use near_crypto::InMemorySigner;
+use near_primitives::types::{AccountId, BlockId, Gas};
+use near_primitives::views::FinalExecutionOutcomeView;
 use near_sdk::json_types::U64;
 use serde_json::json;
 
+use crate::near::constants::ONE_TERA_GAS;
 use crate::near::rpc::client::Client;
 use crate::oct::contracts::NearContract;
 use appchain_anchor::types::{
@@ -9,6 +12,8 @@ use appchain_anchor::types::{
     RewardHistory, ValidatorSetInfo,
 };
 
+const DEFAULT_ANCHOR_INIT_GAS: Gas = 200 * ONE_TERA_GAS;
+
 pub struct AnchorContract<'s> {
     pub account_id: AccountId,
     pub client: &'s Client,
@@ -29,15 +34,49 @@ impl<'s> AnchorContract<'s> {
         return Self { account_id, client };
     }
 
+    /// Initializes a freshly deployed anchor.
+    pub async fn init(
+        &self,
+        signer: &InMemorySigner,
+        appchain_id: &str,
+        appchain_registry: &AccountId,
+        oct_token: &AccountId,
+    ) -> anyhow::Result<FinalExecutionOutcomeView> {
+        self.client
+            .call(
+                signer,
+                &self.account_id,
+                "new".to_string(),
+                json!({
+                    "appchain_id": appchain_id,
+                    "appchain_registry": appchain_registry,
+                    "oct_token": oct_token,
+                })
+                .to_string()
+                .into_bytes(),
+                DEFAULT_ANCHOR_INIT_GAS,
+                0,
+            )
+            .await
+    }
+
     pub async fn get_anchor_status(&self) -> anyhow::Result<AnchorStatus> {
+        self.get_anchor_status_at(None).await
+    }
+
+    pub async fn get_anchor_status_at(
+        &self,
+        block_id: Option<BlockId>,
+    ) -> anyhow::Result<AnchorStatus> {
         self.client
-            .view(
+            .view_at(
                 self.account_id.clone(),
                 "get_anchor_status".to_string(),
                 json!({}).to_string().into_bytes(),
+                block_id,
             )
-            .await
-            .map(|e| e.json().unwrap())
+            .await?
+            .json()
     }
 
     pub async fn get_validator_rewards_of(
@@ -58,8 +97,44 @@ impl<'s> AnchorContract<'s> {
                 .to_string()
                 .into_bytes(),
             )
-            .await
-            .map(|e| e.json().unwrap())
+            .await?
+            .json()
+    }
+
+    pub async fn get_delegator_rewards_of(
+        &self,
+        start_era: u64,
+        end_era: u64,
+        delegator_id: AccountId,
+        validator_id: AccountId,
+    ) -> anyhow::Result<Vec<RewardHistory>> {
+        self.client
+            .view(
+                self.account_id.clone(),
+                "get_delegator_rewards_of".to_string(),
+                json!({
+                    "start_era": start_era.to_string(),
+                    "end_era": end_era.to_string(),
+                    "delegator_id": delegator_id,
+                    "validator_id": validator_id
+                })
+                .to_string()
+                .into_bytes(),
+            )
+            .await?
+            .json()
+    }
+
+    /// The wrapped appchain token info, its `metadata` has `symbol` and `decimals`.
+    pub async fn get_wrapped_appchain_token(&self) -> anyhow::Result<serde_json::Value> {
+        self.client
+            .view(
+                self.account_id.clone(),
+                "get_wrapped_appchain_token".to_string(),
+                json!({}).to_string().into_bytes(),
+            )
+            .await?
+            .json()
     }
 
     pub async fn get_validator_list_of(
@@ -76,8 +151,8 @@ impl<'s> AnchorContract<'s> {
                 .to_string()
                 .into_bytes(),
             )
-            .await
-            .map(|e| e.json().unwrap())
+            .await?
+            .json()
     }
 
     pub async fn get_protocol_settings(&self) -> anyhow::Result<ProtocolSettings> {
@@ -87,16 +162,24 @@ impl<'s> AnchorContract<'s> {
                 "get_protocol_settings".to_string(),
                 json!({}).to_string().into_bytes(),
             )
-            .await
-            .map(|e| e.json().unwrap())
+            .await?
+            .json()
     }
 
     pub async fn get_validator_set_info_of(
         &self,
         era_number: u64,
+    ) -> anyhow::Result<Option<ValidatorSetInfo>> {
+        self.get_validator_set_info_of_at(era_number, None).await
+    }
+
+    pub async fn get_validator_set_info_of_at(
+        &self,
+        era_number: u64,
+        block_id: Option<BlockId>,
     ) -> anyhow::Result<Option<ValidatorSetInfo>> {
         self.client
-            .view(
+            .view_at(
                 self.account_id.clone(),
                 "get_validator_set_info_of".to_string(),
                 json!({
@@ -104,9 +187,10 @@ impl<'s> AnchorContract<'s> {
                 })
                 .to_string()
                 .into_bytes(),
+                block_id,
             )
-            .await
-            .map(|e| e.json().unwrap())
+            .await?
+            .json()
     }
     //
     pub async fn get_appchain_state(&self) -> anyhow::Result<AppchainState> {
@@ -116,8 +200,8 @@ impl<'s> AnchorContract<'s> {
                 "get_appchain_state".to_string(),
                 json!({}).to_string().into_bytes(),
             )
-            .await
-            .map(|e| e.json().unwrap())
+            .await?
+            .json()
     }
     //
     pub async fn get_delegators_of_validator_in_era(
@@ -136,8 +220,8 @@ impl<'s> AnchorContract<'s> {
                 .to_string()
                 .into_bytes(),
             )
-            .await
-            .map(|e| e.json().unwrap())
+            .await?
+            .json()
     }
 }
 
@@ -2,7 +2,7 @@ use crate::near::constants::ONE_TERA_GAS;
 use crate::near::rpc::client::Client;
 use crate::oct::contracts::NearContract;
 use near_crypto::InMemorySigner;
-use near_primitives::types::{AccountId};
+use near_primitives::types::AccountId;
 use near_primitives::views::FinalExecutionOutcomeView;
 use near_sdk::json_types::U128;
 use serde_json::json;
@@ -34,8 +34,8 @@ impl<'s> FungibleTokenContract<'s> {
                 "ft_balance_of".to_string(),
                 json!({ "account_id": account_id }).to_string().into_bytes(),
             )
-            .await
-            .map(|e| e.json().unwrap())
+            .await?
+            .json()
     }
 
     pub async fn ft_transfer_call(
@@ -1,7 +1,7 @@
 use crate::near::rpc::client::Client;
+use crate::oct::contracts::NearContract;
 use appchain_registry::types::AppchainStatus;
 use near_primitives::types::AccountId;
-use crate::oct::contracts::NearContract;
 use serde_json::json;
 
 pub struct RegistryContract<'s> {
@@ -24,6 +24,11 @@ impl<'s> RegistryContract<'s> {
         return Self { account_id, client };
     }
 
+    /// The anchor of an appchain is deployed to `<appchain_id>.<registry>`.
+    pub fn anchor_account_id_of(&self, appchain_id: &str) -> anyhow::Result<AccountId> {
+        Ok(format!("{}.{}", appchain_id, self.account_id).parse()?)
+    }
+
     pub async fn get_appchain_ids(&self) -> anyhow::Result<Vec<String>> {
         self.client
             .view(
@@ -31,8 +36,8 @@ impl<'s> RegistryContract<'s> {
                 "get_appchain_ids".to_string(),
                 json!({}).to_string().into_bytes(),
             )
-            .await
-            .map(|e| e.json().unwrap())
+            .await?
+            .json()
     }
 
     pub async fn get_appchain_status_of(
@@ -47,7 +52,7 @@ impl<'s> RegistryContract<'s> {
                     .to_string()
                     .into_bytes(),
             )
-            .await
-            .map(|e| e.json().unwrap())
+            .await?
+            .json()
     }
 }
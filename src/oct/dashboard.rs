@@ -0,0 +1,640 @@
+use crate::near::rpc::client::Client;
+use crate::oct::contracts::anchor::AnchorContract;
+use crate::oct::contracts::registry::RegistryContract;
+use crate::oct::metrics::{collect_appchain_metrics, AppchainMetrics};
+use crate::oct::scan::hide_scan_progress;
+use appchain_anchor::types::{AppchainDelegator, AppchainValidator};
+use crossterm::event::{Event, EventStream, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::execute;
+use crossterm::terminal::{
+    disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen,
+};
+use futures::future::{self, Future};
+use futures::stream::{self, StreamExt};
+use near_primitives::types::AccountId;
+use ratatui::backend::{Backend, CrosstermBackend};
+use ratatui::layout::{Constraint, Direction, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Span, Spans};
+use ratatui::widgets::{Block, Borders, Cell, Paragraph, Row, Table, TableState};
+use ratatui::{Frame, Terminal};
+use std::io::Stdout;
+use std::pin::Pin;
+use std::time::Duration;
+
+/// Stake and delegations are in OCT.
+const OCT_DECIMALS: i32 = 18;
+const KEYS_HELP: &str = "↑/↓ select  enter open  esc back  r refresh  q quit";
+
+type Pending<'a, T> = Pin<Box<dyn Future<Output = T> + 'a>>;
+
+/// What the dashboard shows of an anchor when it is opened.
+#[derive(Debug, Clone)]
+pub struct AnchorDetail {
+    pub appchain_id: String,
+    pub anchor_account_id: AccountId,
+    /// Protocol settings by name, in the order of the contract.
+    pub settings: Vec<(String, String)>,
+    pub validators: Vec<AppchainValidator>,
+    /// The delegators of each of `validators`.
+    pub delegators: Vec<Vec<AppchainDelegator>>,
+}
+
+/// Reads the protocol settings, the validators of the current era and their delegators of the
+/// anchor of an appchain, with at most `concurrency` requests in flight.
+pub async fn fetch_anchor_detail(
+    client: &Client,
+    registry_account_id: &AccountId,
+    appchain_id: &str,
+    concurrency: usize,
+) -> anyhow::Result<AnchorDetail> {
+    let anchor_account_id = RegistryContract::new(registry_account_id.clone(), client)
+        .anchor_account_id_of(appchain_id)?;
+    let anchor = AnchorContract::new(anchor_account_id.clone(), client);
+    let settings = anchor.get_protocol_settings().await.map_err(|err| {
+        anyhow::anyhow!(
+            "Failed to get protocol settings of anchor({}), error: {}",
+            anchor_account_id,
+            err
+        )
+    })?;
+    let validators = anchor.get_validator_list_of(None).await.map_err(|err| {
+        anyhow::anyhow!(
+            "Failed to get validator list of anchor({}), error: {}",
+            anchor_account_id,
+            err
+        )
+    })?;
+    let anchor = &anchor;
+    let delegators = stream::iter(validators.iter().map(|validator| async move {
+        let validator_id = AccountId::try_from(validator.validator_id.to_string())?;
+        anchor
+            .get_delegators_of_validator_in_era(None, validator_id)
+            .await
+            .map_err(|err| {
+                anyhow::anyhow!(
+                    "Failed to get delegators of {}, error: {}",
+                    validator.validator_id,
+                    err
+                )
+            })
+    }))
+    .buffered(concurrency.max(1))
+    .collect::<Vec<_>>()
+    .await
+    .into_iter()
+    .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let settings = match serde_json::to_value(&settings)? {
+        serde_json::Value::Object(fields) => fields
+            .into_iter()
+            .map(|(name, value)| {
+                let value = match value {
+                    serde_json::Value::String(value) => value,
+                    value => value.to_string(),
+                };
+                (name, value)
+            })
+            .collect(),
+        _ => vec![],
+    };
+    Ok(AnchorDetail {
+        appchain_id: appchain_id.to_string(),
+        anchor_account_id,
+        settings,
+        validators,
+        delegators,
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum View {
+    Appchains,
+    Anchor,
+}
+
+/// What the dashboard asks of the event loop after a key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DashboardAction {
+    None,
+    Quit,
+    Refresh,
+    OpenAnchor(String),
+}
+
+/// The state of the dashboard, updated by keys and refreshes and drawn by `render`.
+#[derive(Debug, Default)]
+pub struct Dashboard {
+    pub registry_account_id: String,
+    pub appchains: Vec<AppchainMetrics>,
+    pub appchain_table: TableState,
+    /// The appchain whose anchor is open, if any.
+    pub open_appchain_id: Option<String>,
+    pub detail: Option<Result<AnchorDetail, String>>,
+    pub validator_table: TableState,
+    /// The last refresh, or its error.
+    pub status: String,
+}
+
+impl Dashboard {
+    pub fn new(registry_account_id: &AccountId) -> Self {
+        Self {
+            registry_account_id: registry_account_id.to_string(),
+            status: "Loading appchains...".to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn view(&self) -> View {
+        match self.open_appchain_id {
+            Some(_) => View::Anchor,
+            None => View::Appchains,
+        }
+    }
+
+    /// Replaces the appchains, an error keeps the last ones.
+    pub fn set_appchains(&mut self, appchains: anyhow::Result<Vec<AppchainMetrics>>, time: &str) {
+        match appchains {
+            Ok(appchains) => {
+                self.appchains = appchains;
+                self.status = format!("Refreshed at {}", time);
+                let selected = match self.appchain_table.selected() {
+                    _ if self.appchains.is_empty() => None,
+                    Some(selected) => Some(selected.min(self.appchains.len() - 1)),
+                    None => Some(0),
+                };
+                self.appchain_table.select(selected);
+            }
+            Err(err) => self.status = format!("Failed to refresh at {}: {}", time, err),
+        }
+    }
+
+    /// Shows the detail of an anchor unless another one has been opened since.
+    pub fn set_detail(&mut self, appchain_id: &str, detail: anyhow::Result<AnchorDetail>) {
+        if self.open_appchain_id.as_deref() != Some(appchain_id) {
+            return;
+        }
+        let detail = detail.map_err(|err| err.to_string());
+        let validator_count = detail.as_ref().map(|e| e.validators.len()).unwrap_or(0);
+        let selected = match self.validator_table.selected() {
+            _ if validator_count == 0 => None,
+            Some(selected) => Some(selected.min(validator_count - 1)),
+            None => Some(0),
+        };
+        self.validator_table.select(selected);
+        self.detail = Some(detail);
+    }
+
+    fn move_selection(state: &mut TableState, len: usize, down: bool) {
+        if len == 0 {
+            state.select(None);
+            return;
+        }
+        let selected = match (state.selected(), down) {
+            (None, _) => 0,
+            (Some(selected), true) => (selected + 1).min(len - 1),
+            (Some(selected), false) => selected.saturating_sub(1),
+        };
+        state.select(Some(selected));
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> DashboardAction {
+        let ctrl_c =
+            key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            _ if ctrl_c => DashboardAction::Quit,
+            KeyCode::Char('q') => DashboardAction::Quit,
+            KeyCode::Char('r') => DashboardAction::Refresh,
+            KeyCode::Up | KeyCode::Down | KeyCode::Char('k') | KeyCode::Char('j') => {
+                let down = matches!(key.code, KeyCode::Down | KeyCode::Char('j'));
+                match self.view() {
+                    View::Appchains => {
+                        Self::move_selection(&mut self.appchain_table, self.appchains.len(), down)
+                    }
+                    View::Anchor => {
+                        let len = match &self.detail {
+                            Some(Ok(detail)) => detail.validators.len(),
+                            _ => 0,
+                        };
+                        Self::move_selection(&mut self.validator_table, len, down)
+                    }
+                }
+                DashboardAction::None
+            }
+            KeyCode::Enter if self.view() == View::Appchains => {
+                match self
+                    .appchain_table
+                    .selected()
+                    .and_then(|e| self.appchains.get(e))
+                {
+                    Some(appchain) => {
+                        let appchain_id = appchain.appchain_id.clone();
+                        self.open_appchain_id = Some(appchain_id.clone());
+                        self.detail = None;
+                        self.validator_table = TableState::default();
+                        DashboardAction::OpenAnchor(appchain_id)
+                    }
+                    None => DashboardAction::None,
+                }
+            }
+            KeyCode::Esc | KeyCode::Backspace | KeyCode::Left if self.view() == View::Anchor => {
+                self.open_appchain_id = None;
+                self.detail = None;
+                DashboardAction::None
+            }
+            _ => DashboardAction::None,
+        }
+    }
+}
+
+fn to_oct(amount: u128) -> String {
+    format!("{:.2}", amount as f64 / 10f64.powi(OCT_DECIMALS))
+}
+
+fn or_unknown<T: ToString>(value: Option<T>) -> String {
+    value
+        .map(|e| e.to_string())
+        .unwrap_or_else(|| "-".to_string())
+}
+
+fn titled(title: String) -> Block<'static> {
+    Block::default().borders(Borders::ALL).title(title)
+}
+
+fn header(cells: &[&'static str]) -> Row<'static> {
+    Row::new(cells.to_vec()).style(Style::default().add_modifier(Modifier::BOLD))
+}
+
+fn selected_style() -> Style {
+    Style::default().add_modifier(Modifier::REVERSED)
+}
+
+pub fn render<B: Backend>(frame: &mut Frame<B>, dashboard: &mut Dashboard) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(3), Constraint::Min(0)].as_ref())
+        .split(frame.size());
+    let status = Paragraph::new(Spans::from(vec![
+        Span::styled(
+            dashboard.registry_account_id.clone(),
+            Style::default().fg(Color::Cyan),
+        ),
+        Span::raw(format!("  {}  ", dashboard.status)),
+        Span::styled(KEYS_HELP, Style::default().fg(Color::DarkGray)),
+    ]))
+    .block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title("Octopus dashboard"),
+    );
+    frame.render_widget(status, chunks[0]);
+
+    match dashboard.view() {
+        View::Appchains => render_appchains(frame, dashboard, chunks[1]),
+        View::Anchor => render_anchor(frame, dashboard, chunks[1]),
+    }
+}
+
+fn render_appchains<B: Backend>(frame: &mut Frame<B>, dashboard: &mut Dashboard, area: Rect) {
+    let rows = dashboard.appchains.iter().map(|appchain| {
+        let state = or_unknown(appchain.appchain_state.as_ref());
+        let state_style = match state.as_str() {
+            "Active" => Style::default().fg(Color::Green),
+            "Frozen" | "Broken" | "Dead" => Style::default().fg(Color::Red),
+            _ => Style::default().fg(Color::Yellow),
+        };
+        let unprofitable_style = match appchain.unprofitable_validator_count {
+            Some(count) if count > 0 => Style::default().fg(Color::Red),
+            _ => Style::default(),
+        };
+        let up = if appchain.up {
+            Cell::from("ok").style(Style::default().fg(Color::Green))
+        } else {
+            Cell::from("read failed").style(Style::default().fg(Color::Red))
+        };
+        Row::new(vec![
+            Cell::from(appchain.appchain_id.clone()),
+            Cell::from(state).style(state_style),
+            Cell::from(or_unknown(appchain.current_era)),
+            Cell::from(or_unknown(appchain.validator_count)),
+            Cell::from(or_unknown(appchain.unprofitable_validator_count)).style(unprofitable_style),
+            Cell::from(or_unknown(appchain.total_stake.map(to_oct))),
+            up,
+        ])
+    });
+    let widths = [
+        Constraint::Percentage(20),
+        Constraint::Percentage(12),
+        Constraint::Percentage(12),
+        Constraint::Percentage(12),
+        Constraint::Percentage(14),
+        Constraint::Percentage(18),
+        Constraint::Percentage(12),
+    ];
+    let table = Table::new(rows)
+        .header(header(&[
+            "appchain",
+            "state",
+            "era",
+            "validators",
+            "unprofitable",
+            "total stake (OCT)",
+            "read",
+        ]))
+        .block(titled(format!("Appchains ({})", dashboard.appchains.len())))
+        .widths(&widths)
+        .highlight_style(selected_style());
+    frame.render_stateful_widget(table, area, &mut dashboard.appchain_table);
+}
+
+fn render_anchor<B: Backend>(frame: &mut Frame<B>, dashboard: &mut Dashboard, area: Rect) {
+    let appchain_id = dashboard.open_appchain_id.clone().unwrap_or_default();
+    let detail = match &dashboard.detail {
+        Some(Ok(detail)) => detail,
+        Some(Err(err)) => {
+            let error = Paragraph::new(err.clone())
+                .style(Style::default().fg(Color::Red))
+                .block(titled(appchain_id));
+            frame.render_widget(error, area);
+            return;
+        }
+        None => {
+            let loading = Paragraph::new("Loading anchor...").block(titled(appchain_id));
+            frame.render_widget(loading, area);
+            return;
+        }
+    };
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(55), Constraint::Percentage(45)].as_ref())
+        .split(area);
+    let right = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+        .split(columns[1]);
+
+    let validator_rows =
+        detail
+            .validators
+            .iter()
+            .zip(&detail.delegators)
+            .map(|(validator, delegators)| {
+                let flags = match (validator.is_unbonding, validator.can_be_delegated_to) {
+                    (true, _) => Cell::from("unbonding").style(Style::default().fg(Color::Red)),
+                    (false, false) => Cell::from("no delegation"),
+                    (false, true) => Cell::from(""),
+                };
+                Row::new(vec![
+                    Cell::from(validator.validator_id.to_string()),
+                    Cell::from(to_oct(validator.total_stake.0)),
+                    Cell::from(to_oct(validator.deposit_amount.0)),
+                    Cell::from(delegators.len().to_string()),
+                    flags,
+                ])
+            });
+    let validator_widths = [
+        Constraint::Percentage(36),
+        Constraint::Percentage(18),
+        Constraint::Percentage(18),
+        Constraint::Percentage(12),
+        Constraint::Percentage(16),
+    ];
+    let validators = Table::new(validator_rows)
+        .header(header(&[
+            "validator",
+            "stake (OCT)",
+            "deposit (OCT)",
+            "delegators",
+            "",
+        ]))
+        .block(titled(format!(
+            "{} validators ({})",
+            detail.anchor_account_id,
+            detail.validators.len()
+        )))
+        .widths(&validator_widths)
+        .highlight_style(selected_style());
+    frame.render_stateful_widget(validators, columns[0], &mut dashboard.validator_table);
+
+    let selected = dashboard.validator_table.selected();
+    let delegators = selected
+        .and_then(|e| detail.delegators.get(e))
+        .cloned()
+        .unwrap_or_default();
+    let validator_id = selected
+        .and_then(|e| detail.validators.get(e))
+        .map(|e| e.validator_id.to_string())
+        .unwrap_or_default();
+    let delegator_rows = delegators.iter().map(|delegator| {
+        Row::new(vec![
+            delegator.delegator_id.to_string(),
+            to_oct(delegator.delegation_amount.0),
+        ])
+    });
+    let delegator_widths = [Constraint::Percentage(65), Constraint::Percentage(35)];
+    let delegator_table = Table::new(delegator_rows)
+        .header(header(&["delegator", "amount (OCT)"]))
+        .block(titled(format!(
+            "Delegators of {} ({})",
+            validator_id,
+            delegators.len()
+        )))
+        .widths(&delegator_widths);
+    frame.render_widget(delegator_table, right[0]);
+
+    let setting_rows = detail
+        .settings
+        .iter()
+        .map(|(name, value)| Row::new(vec![name.clone(), value.clone()]));
+    let setting_widths = [Constraint::Percentage(65), Constraint::Percentage(35)];
+    let settings = Table::new(setting_rows)
+        .block(titled("Protocol settings".to_string()))
+        .widths(&setting_widths);
+    frame.render_widget(settings, right[1]);
+}
+
+/// Restores the terminal when the dashboard exits, also on errors.
+struct DashboardTerminal(Terminal<CrosstermBackend<Stdout>>);
+
+impl DashboardTerminal {
+    fn enter() -> anyhow::Result<Self> {
+        enable_raw_mode()?;
+        let mut stdout = std::io::stdout();
+        execute!(stdout, EnterAlternateScreen)?;
+        Ok(Self(Terminal::new(CrosstermBackend::new(stdout))?))
+    }
+}
+
+impl Drop for DashboardTerminal {
+    fn drop(&mut self) {
+        let _ = disable_raw_mode();
+        let _ = execute!(self.0.backend_mut(), LeaveAlternateScreen);
+        let _ = self.0.show_cursor();
+    }
+}
+
+fn refresh_appchains<'a>(
+    client: &'a Client,
+    registry_account_id: &'a AccountId,
+    delay: Duration,
+    concurrency: usize,
+) -> Pending<'a, anyhow::Result<Vec<AppchainMetrics>>> {
+    Box::pin(async move {
+        tokio::time::sleep(delay).await;
+        collect_appchain_metrics(client, registry_account_id.clone(), concurrency).await
+    })
+}
+
+fn load_anchor<'a>(
+    client: &'a Client,
+    registry_account_id: &'a AccountId,
+    appchain_id: String,
+    concurrency: usize,
+) -> Pending<'a, (String, anyhow::Result<AnchorDetail>)> {
+    Box::pin(async move {
+        let detail =
+            fetch_anchor_detail(client, registry_account_id, &appchain_id, concurrency).await;
+        (appchain_id, detail)
+    })
+}
+
+/// Runs the dashboard until the user quits, the appchains and the open anchor are read again
+/// every `interval`.
+pub async fn run_dashboard(
+    client: &Client,
+    registry_account_id: &AccountId,
+    interval: Duration,
+    concurrency: usize,
+) -> anyhow::Result<()> {
+    hide_scan_progress();
+    let mut terminal = DashboardTerminal::enter()?;
+    let mut dashboard = Dashboard::new(registry_account_id);
+    let mut events = EventStream::new();
+    let mut appchains = refresh_appchains(client, registry_account_id, Duration::ZERO, concurrency);
+    let mut anchor: Pending<(String, anyhow::Result<AnchorDetail>)> = Box::pin(future::pending());
+
+    loop {
+        terminal.0.draw(|frame| render(frame, &mut dashboard))?;
+        tokio::select! {
+            result = &mut appchains => {
+                let time = chrono::Local::now().format("%H:%M:%S").to_string();
+                dashboard.set_appchains(result, &time);
+                if let Some(appchain_id) = dashboard.open_appchain_id.clone() {
+                    anchor = load_anchor(client, registry_account_id, appchain_id, concurrency);
+                }
+                appchains = refresh_appchains(client, registry_account_id, interval, concurrency);
+            }
+            (appchain_id, detail) = &mut anchor => {
+                dashboard.set_detail(&appchain_id, detail);
+                anchor = Box::pin(future::pending());
+            }
+            event = events.next() => match event {
+                Some(Ok(Event::Key(key))) if key.kind == KeyEventKind::Press => {
+                    match dashboard.handle_key(key) {
+                        DashboardAction::Quit => break,
+                        DashboardAction::Refresh => {
+                            dashboard.status = "Refreshing...".to_string();
+                            appchains = refresh_appchains(
+                                client,
+                                registry_account_id,
+                                Duration::ZERO,
+                                concurrency,
+                            );
+                        }
+                        DashboardAction::OpenAnchor(appchain_id) => {
+                            anchor = load_anchor(
+                                client,
+                                registry_account_id,
+                                appchain_id,
+                                concurrency,
+                            );
+                        }
+                        DashboardAction::None => {}
+                    }
+                }
+                // Other events such as a resize only need a redraw.
+                Some(Ok(_)) => {}
+                Some(Err(err)) => return Err(err.into()),
+                None => break,
+            }
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_dashboard_navigation() {
+    use ratatui::backend::TestBackend;
+
+    let registry_account_id: AccountId = "octopus-registry.near".parse().unwrap();
+    let mut dashboard = Dashboard::new(&registry_account_id);
+    let appchain = |appchain_id: &str, state: &str| AppchainMetrics {
+        appchain_id: appchain_id.to_string(),
+        up: true,
+        appchain_state: Some(state.to_string()),
+        ..Default::default()
+    };
+    dashboard.set_appchains(
+        Ok(vec![
+            AppchainMetrics {
+                current_era: Some(52),
+                validator_count: Some(4),
+                unprofitable_validator_count: Some(1),
+                ..appchain("fusotao", "Active")
+            },
+            appchain("barnacle", "Booting"),
+        ]),
+        "12:00:00",
+    );
+    assert_eq!(dashboard.appchain_table.selected(), Some(0));
+
+    let key = |code| KeyEvent::new(code, KeyModifiers::NONE);
+    dashboard.handle_key(key(KeyCode::Down));
+    dashboard.handle_key(key(KeyCode::Down));
+    assert_eq!(dashboard.appchain_table.selected(), Some(1));
+    dashboard.handle_key(key(KeyCode::Up));
+    assert_eq!(
+        dashboard.handle_key(key(KeyCode::Enter)),
+        DashboardAction::OpenAnchor("fusotao".to_string())
+    );
+    assert_eq!(dashboard.view(), View::Anchor);
+
+    // A detail of an anchor closed in the meantime is dropped.
+    dashboard.set_detail("barnacle", Err(anyhow::anyhow!("closed")));
+    assert!(dashboard.detail.is_none());
+    dashboard.set_detail("fusotao", Err(anyhow::anyhow!("rpc timeout")));
+    let mut terminal = Terminal::new(TestBackend::new(100, 12)).unwrap();
+    terminal
+        .draw(|frame| render(frame, &mut dashboard))
+        .unwrap();
+    let screen = terminal
+        .backend()
+        .buffer()
+        .content()
+        .iter()
+        .map(|e| e.symbol.as_str())
+        .collect::<String>();
+    assert!(screen.contains("rpc timeout"));
+
+    dashboard.handle_key(key(KeyCode::Esc));
+    assert_eq!(dashboard.view(), View::Appchains);
+    dashboard.set_appchains(Err(anyhow::anyhow!("rpc timeout")), "12:01:00");
+    assert_eq!(dashboard.appchains.len(), 2);
+    terminal
+        .draw(|frame| render(frame, &mut dashboard))
+        .unwrap();
+    let screen = terminal
+        .backend()
+        .buffer()
+        .content()
+        .iter()
+        .map(|e| e.symbol.as_str())
+        .collect::<String>();
+    assert!(screen.contains("fusotao"));
+    assert!(screen.contains("Booting"));
+    assert!(screen.contains("Failed to refresh at 12:01:00"));
+    assert_eq!(
+        dashboard.handle_key(KeyEvent::new(KeyCode::Char('c'), KeyModifiers::CONTROL)),
+        DashboardAction::Quit
+    );
+}
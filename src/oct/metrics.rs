@@ -0,0 +1,386 @@
+use crate::near::rpc::client::Client;
+use crate::oct::contracts::anchor::AnchorContract;
+use crate::oct::contracts::fungible_token::FungibleTokenContract;
+use crate::oct::scan::{active_anchors_of_registry, ActiveAnchor, AppchainScanResult};
+use futures::stream::{self, StreamExt};
+use hyper::header::CONTENT_TYPE;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use near_primitives::types::AccountId;
+use std::convert::Infallible;
+use std::fmt::Write;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+/// States of a registered appchain, each is exported as a sample of `octopus_appchain_state`.
+const APPCHAIN_STATES: [&str; 8] = [
+    "Registered",
+    "Audited",
+    "Voting",
+    "Booting",
+    "Active",
+    "Frozen",
+    "Broken",
+    "Dead",
+];
+/// OCT and the stake of validators have 18 decimals.
+const OCT_DECIMALS: i32 = 18;
+
+/// What the exporter knows of an appchain, `None` if it could not be read.
+#[derive(Debug, Clone, Default)]
+pub struct AppchainMetrics {
+    pub appchain_id: String,
+    /// Whether everything of the appchain could be read.
+    pub up: bool,
+    pub appchain_state: Option<String>,
+    pub current_era: Option<u64>,
+    pub validator_count: Option<usize>,
+    pub total_stake: Option<u128>,
+    pub unprofitable_validator_count: Option<usize>,
+    /// Why the appchain could not be read, `None` if it is up.
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ExporterMetrics {
+    pub appchains: Vec<AppchainMetrics>,
+    /// OCT balance of each fund account, `None` if it could not be read.
+    pub fund_balances: Vec<(AccountId, Option<u128>)>,
+    /// Unix seconds of the refresh.
+    pub refreshed_at: u64,
+}
+
+/// Reads the appchains of a registry and the OCT balances of `fund_accounts` with at most
+/// `concurrency` requests in flight.
+pub async fn collect_metrics(
+    client: &Client,
+    registry_account_id: AccountId,
+    oct_token_account_id: &AccountId,
+    fund_accounts: &[AccountId],
+    concurrency: usize,
+) -> anyhow::Result<ExporterMetrics> {
+    let appchains = collect_appchain_metrics(client, registry_account_id, concurrency).await?;
+
+    let oct_token = FungibleTokenContract::new(oct_token_account_id.clone(), client);
+    let oct_token = &oct_token;
+    let fund_balances = stream::iter(fund_accounts.iter().map(|account_id| async move {
+        let balance = oct_token.ft_balance_of(account_id).await.ok();
+        (account_id.clone(), balance.map(|e| e.0))
+    }))
+    .buffered(concurrency.max(1))
+    .collect()
+    .await;
+
+    Ok(ExporterMetrics {
+        appchains,
+        fund_balances,
+        refreshed_at: std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)?
+            .as_secs(),
+    })
+}
+
+/// Reads every appchain of a registry with at most `concurrency` requests in flight, in the
+/// order of the registry.
+pub async fn collect_appchain_metrics(
+    client: &Client,
+    registry_account_id: AccountId,
+    concurrency: usize,
+) -> anyhow::Result<Vec<AppchainMetrics>> {
+    let anchors = active_anchors_of_registry(client, registry_account_id, &[], concurrency).await?;
+    Ok(stream::iter(anchors.into_iter().map(|anchor| async move {
+        match anchor {
+            Ok(anchor) => appchain_metrics_of(client, &anchor).await,
+            Err(AppchainScanResult::Skipped {
+                appchain_id,
+                appchain_state,
+                ..
+            }) => AppchainMetrics {
+                appchain_id,
+                up: true,
+                appchain_state: Some(format!("{:?}", appchain_state)),
+                ..Default::default()
+            },
+            Err(AppchainScanResult::Failed { appchain_id, error }) => AppchainMetrics {
+                appchain_id,
+                error: Some(error),
+                ..Default::default()
+            },
+            Err(result) => AppchainMetrics {
+                appchain_id: result.appchain_id().to_string(),
+                ..Default::default()
+            },
+        }
+    }))
+    .buffered(concurrency.max(1))
+    .collect()
+    .await)
+}
+
+async fn appchain_metrics_of(client: &Client, anchor: &ActiveAnchor) -> AppchainMetrics {
+    let mut metrics = AppchainMetrics {
+        appchain_id: anchor.appchain_id.clone(),
+        appchain_state: Some("Active".to_string()),
+        current_era: Some(anchor.current_era),
+        ..Default::default()
+    };
+    let info = AnchorContract::new(anchor.anchor_account_id.clone(), client)
+        .get_validator_set_info_of(anchor.current_era)
+        .await;
+    match info {
+        Ok(Some(info)) => {
+            metrics.up = true;
+            metrics.validator_count = Some(info.validator_list.len());
+            metrics.total_stake = Some(info.total_stake.0);
+            metrics.unprofitable_validator_count = Some(info.unprofitable_validator_ids.len());
+        }
+        Ok(None) => {
+            metrics.error = Some(format!(
+                "No validator set info of era {} in {}",
+                anchor.current_era, anchor.anchor_account_id
+            ))
+        }
+        Err(err) => {
+            metrics.error = Some(format!(
+                "Failed to read validator set info of era {} in {}: {}",
+                anchor.current_era, anchor.anchor_account_id, err
+            ))
+        }
+    }
+    metrics
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn to_oct(amount: u128) -> f64 {
+    amount as f64 / 10f64.powi(OCT_DECIMALS)
+}
+
+/// Writes a gauge in the Prometheus text format, `samples` are labels and values.
+fn write_gauge(out: &mut String, name: &str, help: &str, samples: Vec<(String, String)>) {
+    if samples.is_empty() {
+        return;
+    }
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} gauge", name);
+    for (labels, value) in samples {
+        if labels.is_empty() {
+            let _ = writeln!(out, "{} {}", name, value);
+        } else {
+            let _ = writeln!(out, "{}{{{}}} {}", name, labels, value);
+        }
+    }
+}
+
+/// Renders the metrics in the Prometheus text format.
+pub fn render_metrics(metrics: &ExporterMetrics) -> String {
+    let appchain_label =
+        |e: &AppchainMetrics| format!("appchain_id=\"{}\"", escape_label(&e.appchain_id));
+    let appchain_gauge = |value: &dyn Fn(&AppchainMetrics) -> Option<String>| {
+        metrics
+            .appchains
+            .iter()
+            .filter_map(|e| value(e).map(|value| (appchain_label(e), value)))
+            .collect::<Vec<_>>()
+    };
+
+    let mut out = String::new();
+    write_gauge(
+        &mut out,
+        "octopus_appchain_up",
+        "Whether the appchain could be read in the last refresh.",
+        appchain_gauge(&|e| Some(if e.up { "1" } else { "0" }.to_string())),
+    );
+    write_gauge(
+        &mut out,
+        "octopus_appchain_current_era",
+        "Current era of the appchain anchor.",
+        appchain_gauge(&|e| e.current_era.map(|e| e.to_string())),
+    );
+    write_gauge(
+        &mut out,
+        "octopus_appchain_validator_count",
+        "Validators in the current era.",
+        appchain_gauge(&|e| e.validator_count.map(|e| e.to_string())),
+    );
+    write_gauge(
+        &mut out,
+        "octopus_appchain_total_stake_oct",
+        "Total stake of the current era in OCT.",
+        appchain_gauge(&|e| e.total_stake.map(|e| to_oct(e).to_string())),
+    );
+    write_gauge(
+        &mut out,
+        "octopus_appchain_unprofitable_validator_count",
+        "Unprofitable validators in the current era.",
+        appchain_gauge(&|e| e.unprofitable_validator_count.map(|e| e.to_string())),
+    );
+
+    let mut states = vec![];
+    for appchain in &metrics.appchains {
+        let current = match &appchain.appchain_state {
+            Some(current) => current,
+            None => continue,
+        };
+        let mut names = APPCHAIN_STATES.to_vec();
+        if !names.contains(&current.as_str()) {
+            names.push(current);
+        }
+        for name in names {
+            states.push((
+                format!(
+                    "{},state=\"{}\"",
+                    appchain_label(appchain),
+                    escape_label(name)
+                ),
+                if name == current { "1" } else { "0" }.to_string(),
+            ));
+        }
+    }
+    write_gauge(
+        &mut out,
+        "octopus_appchain_state",
+        "State of the appchain, 1 for the current state.",
+        states,
+    );
+
+    write_gauge(
+        &mut out,
+        "octopus_fund_balance_oct",
+        "OCT balance of a fund account.",
+        metrics
+            .fund_balances
+            .iter()
+            .filter_map(|(account_id, balance)| {
+                balance.map(|balance| {
+                    (
+                        format!("account_id=\"{}\"", escape_label(account_id.as_str())),
+                        to_oct(balance).to_string(),
+                    )
+                })
+            })
+            .collect(),
+    );
+    write_gauge(
+        &mut out,
+        "octopus_exporter_last_refresh_timestamp_seconds",
+        "Unix time of the last refresh.",
+        vec![(String::new(), metrics.refreshed_at.to_string())],
+    );
+    out
+}
+
+async fn respond(request: Request<Body>, metrics: Arc<RwLock<Option<String>>>) -> Response<Body> {
+    let mut response = Response::new(Body::empty());
+    match (request.method(), request.uri().path()) {
+        (&Method::GET, "/metrics") => match metrics.read().await.clone() {
+            Some(metrics) => {
+                response.headers_mut().insert(
+                    CONTENT_TYPE,
+                    "text/plain; version=0.0.4".parse().expect("valid header"),
+                );
+                *response.body_mut() = Body::from(metrics);
+            }
+            None => {
+                *response.status_mut() = StatusCode::SERVICE_UNAVAILABLE;
+                *response.body_mut() = Body::from("Metrics are not collected yet.\n");
+            }
+        },
+        _ => *response.status_mut() = StatusCode::NOT_FOUND,
+    }
+    response
+}
+
+/// Binds a server answering `GET /metrics` with the latest rendered metrics, or 503 until
+/// metrics are first collected. It runs until `shutdown` completes. Returns the bound address
+/// and the server to await.
+pub fn bind_metrics_server(
+    addr: SocketAddr,
+    metrics: Arc<RwLock<Option<String>>>,
+    shutdown: impl Future<Output = ()>,
+) -> anyhow::Result<(SocketAddr, impl Future<Output = hyper::Result<()>>)> {
+    let make_service = make_service_fn(move |_| {
+        let metrics = metrics.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                let metrics = metrics.clone();
+                async move { Ok::<_, Infallible>(respond(request, metrics).await) }
+            }))
+        }
+    });
+    let server = Server::try_bind(&addr)?.serve(make_service);
+    let local_addr = server.local_addr();
+    Ok((local_addr, server.with_graceful_shutdown(shutdown)))
+}
+
+#[tokio::test]
+async fn test_metrics_server() {
+    let metrics = ExporterMetrics {
+        appchains: vec![
+            AppchainMetrics {
+                appchain_id: "fusotao".to_string(),
+                up: true,
+                appchain_state: Some("Active".to_string()),
+                current_era: Some(52),
+                validator_count: Some(4),
+                total_stake: Some(1_500_000_000_000_000_000_000),
+                unprofitable_validator_count: Some(1),
+                error: None,
+            },
+            AppchainMetrics {
+                appchain_id: "barnacle".to_string(),
+                up: true,
+                appchain_state: Some("Booting".to_string()),
+                ..Default::default()
+            },
+        ],
+        fund_balances: vec![("fund.near".parse().unwrap(), Some(2 * 10u128.pow(18)))],
+        refreshed_at: 1_664_611_200,
+    };
+    let text = render_metrics(&metrics);
+    assert!(text.contains("# TYPE octopus_appchain_current_era gauge\n"));
+    assert!(text.contains("octopus_appchain_current_era{appchain_id=\"fusotao\"} 52\n"));
+    assert!(!text.contains("octopus_appchain_current_era{appchain_id=\"barnacle\"}"));
+    assert!(text.contains("octopus_appchain_total_stake_oct{appchain_id=\"fusotao\"} 1500\n"));
+    assert!(text.contains("octopus_appchain_state{appchain_id=\"barnacle\",state=\"Booting\"} 1\n"));
+    assert!(text.contains("octopus_appchain_state{appchain_id=\"barnacle\",state=\"Active\"} 0\n"));
+    assert!(text.contains("octopus_fund_balance_oct{account_id=\"fund.near\"} 2\n"));
+
+    let shared = Arc::new(RwLock::new(None));
+    let (shutdown_sender, shutdown) = tokio::sync::oneshot::channel::<()>();
+    let (addr, server) =
+        bind_metrics_server("127.0.0.1:0".parse().unwrap(), shared.clone(), async {
+            let _ = shutdown.await;
+        })
+        .unwrap();
+    let server = tokio::spawn(server);
+
+    let http = reqwest::Client::new();
+    let response = http
+        .get(format!("http://{}/metrics", addr))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::SERVICE_UNAVAILABLE);
+
+    *shared.write().await = Some(text.clone());
+    let response = http
+        .get(format!("http://{}/metrics", addr))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    assert_eq!(response.text().await.unwrap(), text);
+    let response = http.get(format!("http://{}/", addr)).send().await.unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+
+    shutdown_sender.send(()).unwrap();
+    server.await.unwrap().unwrap();
+}
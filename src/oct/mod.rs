@@ -1 +1,10 @@
+pub mod anchor_selector;
+pub mod audit;
 pub mod contracts;
+pub mod dashboard;
+pub mod metrics;
+pub mod monitor;
+pub mod portfolio;
+pub mod report;
+pub mod rewards;
+pub mod scan;
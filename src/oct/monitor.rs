@@ -0,0 +1,529 @@
+use crate::near::rpc::client::Client;
+use crate::oct::contracts::anchor::AnchorContract;
+use crate::oct::scan::{scan_registry, AnchorScan, AppchainScanResult, ScanFilter};
+use futures::stream::{self, StreamExt};
+use near_primitives::types::AccountId;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::time::Duration;
+
+/// How long a webhook may take to accept an event.
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Parses a duration such as `30s`, `5m`, `2h` or `1d`, a plain number is in seconds.
+pub fn parse_duration(duration: &str) -> anyhow::Result<Duration> {
+    let duration = duration.trim();
+    let (number, unit) = match duration.find(|e: char| !e.is_ascii_digit()) {
+        Some(index) => duration.split_at(index),
+        None => (duration, "s"),
+    };
+    let seconds = match unit.trim() {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => anyhow::bail!(
+            "Invalid duration '{}', expected eg: 30s, 5m, 2h or 1d",
+            duration
+        ),
+    };
+    let number: u64 = number
+        .parse()
+        .map_err(|_| anyhow::anyhow!("Invalid duration '{}'", duration))?;
+    Ok(Duration::from_secs(number * seconds))
+}
+
+/// A change worth an alert, sent to webhooks as JSON with an `event` field naming the variant.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum MonitorEvent {
+    NewUnprofitableValidator {
+        appchain_id: String,
+        validator_id: String,
+        eras: Vec<u64>,
+    },
+    AppchainStateChanged {
+        appchain_id: String,
+        from: String,
+        to: String,
+    },
+    EraNotAdvancing {
+        appchain_id: String,
+        era: u64,
+        stalled_secs: u64,
+    },
+    AnchorAnomaly {
+        appchain_id: String,
+        anomaly: String,
+        detail: String,
+    },
+}
+
+impl std::fmt::Display for MonitorEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MonitorEvent::NewUnprofitableValidator {
+                appchain_id,
+                validator_id,
+                eras,
+            } => write!(
+                f,
+                "{}: validator {} is unprofitable in eras {:?}",
+                appchain_id, validator_id, eras
+            ),
+            MonitorEvent::AppchainStateChanged {
+                appchain_id,
+                from,
+                to,
+            } => write!(f, "{}: state changed from {} to {}", appchain_id, from, to),
+            MonitorEvent::EraNotAdvancing {
+                appchain_id,
+                era,
+                stalled_secs,
+            } => write!(
+                f,
+                "{}: era {} has not advanced for {} minutes",
+                appchain_id,
+                era,
+                stalled_secs / 60
+            ),
+            MonitorEvent::AnchorAnomaly {
+                appchain_id,
+                anomaly,
+                detail,
+            } => write!(f, "{}: {}, {}", appchain_id, anomaly, detail),
+        }
+    }
+}
+
+/// Something wrong with an anchor, alerts are deduplicated by `kind`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Anomaly {
+    pub kind: String,
+    pub detail: String,
+}
+
+/// What a scan saw of an appchain.
+#[derive(Debug, Clone, Default)]
+pub struct AppchainObservation {
+    pub appchain_id: String,
+    /// `None` if the appchain could not be read.
+    pub appchain_state: Option<String>,
+    /// `None` if the appchain is not active or could not be read.
+    pub current_era: Option<u64>,
+    /// Unprofitable validators with the eras they were unprofitable in, `None` if the appchain
+    /// could not be read.
+    pub unprofitable: Option<BTreeMap<String, Vec<u64>>>,
+    pub anomalies: Vec<Anomaly>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct AppchainMemory {
+    appchain_state: Option<String>,
+    /// The current era and since when (unix seconds) it is current.
+    era: Option<(u64, u64)>,
+    /// Conditions which have been alerted and still hold, eg: `unprofitable:alice.near`.
+    active: BTreeSet<String>,
+}
+
+/// Remembers what the previous scans saw, so that a condition is alerted once while it holds
+/// and again only after it has cleared.
+#[derive(Debug, Clone, Default)]
+pub struct MonitorState {
+    appchains: HashMap<String, AppchainMemory>,
+}
+
+impl MonitorState {
+    /// Compares a scan with the previous ones and returns the new events. `now` is in unix
+    /// seconds, an era current for `max_era_age` or longer is reported as not advancing.
+    pub fn observe(
+        &mut self,
+        observations: &[AppchainObservation],
+        now: u64,
+        max_era_age: Duration,
+    ) -> Vec<MonitorEvent> {
+        let mut events = vec![];
+        for observation in observations {
+            let memory = self
+                .appchains
+                .entry(observation.appchain_id.clone())
+                .or_default();
+            // Conditions which hold now, keyed for deduplication.
+            let mut conditions: Vec<(String, MonitorEvent)> = vec![];
+            let mut active = BTreeSet::new();
+
+            if let Some(state) = &observation.appchain_state {
+                match &memory.appchain_state {
+                    Some(previous) if previous != state => {
+                        events.push(MonitorEvent::AppchainStateChanged {
+                            appchain_id: observation.appchain_id.clone(),
+                            from: previous.clone(),
+                            to: state.clone(),
+                        })
+                    }
+                    _ => {}
+                }
+                memory.appchain_state = Some(state.clone());
+            }
+
+            match &observation.unprofitable {
+                // The appchain could not be read, keep what is known of it.
+                None => active.extend(
+                    memory
+                        .active
+                        .iter()
+                        .filter(|e| !e.starts_with("anomaly:"))
+                        .cloned(),
+                ),
+                Some(unprofitable) => {
+                    for (validator_id, eras) in unprofitable {
+                        conditions.push((
+                            format!("unprofitable:{}", validator_id),
+                            MonitorEvent::NewUnprofitableValidator {
+                                appchain_id: observation.appchain_id.clone(),
+                                validator_id: validator_id.clone(),
+                                eras: eras.clone(),
+                            },
+                        ));
+                    }
+                    memory.era = observation.current_era.map(|era| match memory.era {
+                        Some((previous, since)) if previous == era => (era, since),
+                        _ => (era, now),
+                    });
+                    if let Some((era, since)) = memory.era {
+                        let stalled_secs = now.saturating_sub(since);
+                        if stalled_secs >= max_era_age.as_secs() {
+                            conditions.push((
+                                "era_not_advancing".to_string(),
+                                MonitorEvent::EraNotAdvancing {
+                                    appchain_id: observation.appchain_id.clone(),
+                                    era,
+                                    stalled_secs,
+                                },
+                            ));
+                        }
+                    }
+                }
+            }
+
+            for anomaly in &observation.anomalies {
+                conditions.push((
+                    format!("anomaly:{}", anomaly.kind),
+                    MonitorEvent::AnchorAnomaly {
+                        appchain_id: observation.appchain_id.clone(),
+                        anomaly: anomaly.kind.clone(),
+                        detail: anomaly.detail.clone(),
+                    },
+                ));
+            }
+            for (key, event) in conditions {
+                if !memory.active.contains(&key) {
+                    events.push(event);
+                }
+                active.insert(key);
+            }
+            memory.active = active;
+        }
+        events
+    }
+}
+
+/// Scans the registry and reads the status of every active anchor.
+pub async fn observe_registry(
+    client: &Client,
+    registry_account_id: AccountId,
+    concurrency: usize,
+) -> anyhow::Result<Vec<AppchainObservation>> {
+    let results = scan_registry(
+        client,
+        None,
+        registry_account_id,
+        &ScanFilter::default(),
+        concurrency,
+    )
+    .await?;
+    Ok(stream::iter(results.into_iter().map(|result| async move {
+        match result {
+            AppchainScanResult::Scanned(scan) => observe_anchor(client, scan).await,
+            AppchainScanResult::Skipped {
+                appchain_id,
+                appchain_state,
+                ..
+            } => AppchainObservation {
+                appchain_id,
+                appchain_state: Some(format!("{:?}", appchain_state)),
+                unprofitable: Some(BTreeMap::new()),
+                ..Default::default()
+            },
+            AppchainScanResult::Failed { appchain_id, error } => AppchainObservation {
+                appchain_id,
+                anomalies: vec![Anomaly {
+                    kind: "read_failed".to_string(),
+                    detail: error,
+                }],
+                ..Default::default()
+            },
+        }
+    }))
+    .buffered(concurrency.max(1))
+    .collect()
+    .await)
+}
+
+async fn observe_anchor(client: &Client, scan: AnchorScan) -> AppchainObservation {
+    let mut unprofitable: BTreeMap<String, Vec<u64>> = BTreeMap::new();
+    for era in &scan.eras {
+        for validator_id in &era.unprofitable_validator_ids {
+            unprofitable
+                .entry(validator_id.to_string())
+                .or_default()
+                .push(era.era);
+        }
+    }
+    let anomalies = match anchor_anomalies(client, &scan.anchor_account_id).await {
+        Ok(anomalies) => anomalies,
+        Err(err) => vec![Anomaly {
+            kind: "read_failed".to_string(),
+            detail: err.to_string(),
+        }],
+    };
+    AppchainObservation {
+        appchain_id: scan.appchain_id,
+        appchain_state: Some("Active".to_string()),
+        current_era: Some(scan.current_era),
+        unprofitable: Some(unprofitable),
+        anomalies,
+    }
+}
+
+/// Paused transfers or withdrawals, and too few validators for the next era.
+async fn anchor_anomalies(
+    client: &Client,
+    anchor_account_id: &AccountId,
+) -> anyhow::Result<Vec<Anomaly>> {
+    let anchor = AnchorContract::new(anchor_account_id.clone(), client);
+    let status = anchor.get_anchor_status().await?;
+    let settings = anchor.get_protocol_settings().await?;
+    let mut anomalies = vec![];
+    if status.asset_transfer_is_paused {
+        anomalies.push(Anomaly {
+            kind: "asset_transfer_paused".to_string(),
+            detail: format!("asset transfer of {} is paused", anchor_account_id),
+        });
+    }
+    if status.rewards_withdrawal_is_paused {
+        anomalies.push(Anomaly {
+            kind: "rewards_withdrawal_paused".to_string(),
+            detail: format!("rewards withdrawal of {} is paused", anchor_account_id),
+        });
+    }
+    if status.validator_count_in_next_era.0 < settings.minimum_validator_count.0 {
+        anomalies.push(Anomaly {
+            kind: "too_few_validators".to_string(),
+            detail: format!(
+                "{} validators in the next era, the minimum is {}",
+                status.validator_count_in_next_era.0, settings.minimum_validator_count.0
+            ),
+        });
+    }
+    Ok(anomalies)
+}
+
+/// The JSON body POSTed to webhooks.
+#[derive(Debug, Clone, Serialize)]
+struct WebhookPayload<'e> {
+    registry_account_id: &'e AccountId,
+    detected_at: String,
+    #[serde(flatten)]
+    event: &'e MonitorEvent,
+}
+
+/// POSTs an event to every webhook, returns the webhooks which failed with their errors.
+pub async fn post_event(
+    http: &reqwest::Client,
+    webhooks: &[url::Url],
+    registry_account_id: &AccountId,
+    event: &MonitorEvent,
+) -> Vec<(url::Url, String)> {
+    let payload = WebhookPayload {
+        registry_account_id,
+        detected_at: chrono::Utc::now().to_rfc3339(),
+        event,
+    };
+    let mut failures = vec![];
+    for webhook in webhooks {
+        let result = http
+            .post(webhook.clone())
+            .timeout(WEBHOOK_TIMEOUT)
+            .json(&payload)
+            .send()
+            .await
+            .and_then(|e| e.error_for_status());
+        if let Err(err) = result {
+            failures.push((webhook.clone(), err.to_string()));
+        }
+    }
+    failures
+}
+
+#[test]
+fn test_monitor_state_dedup() {
+    let observation =
+        |state: &str, era, unprofitable: &[&str], anomalies: &[&str]| AppchainObservation {
+            appchain_id: "fusotao".to_string(),
+            appchain_state: Some(state.to_string()),
+            current_era: Some(era),
+            unprofitable: Some(
+                unprofitable
+                    .iter()
+                    .map(|e| (e.to_string(), vec![era]))
+                    .collect(),
+            ),
+            anomalies: anomalies
+                .iter()
+                .map(|e| Anomaly {
+                    kind: e.to_string(),
+                    detail: String::new(),
+                })
+                .collect(),
+        };
+    let max_era_age = Duration::from_secs(100);
+    let mut state = MonitorState::default();
+
+    assert_eq!(
+        state.observe(&[observation("Active", 5, &[], &[])], 0, max_era_age),
+        vec![]
+    );
+    let events = state.observe(
+        &[observation(
+            "Active",
+            5,
+            &["alice.near"],
+            &["asset_transfer_paused"],
+        )],
+        10,
+        max_era_age,
+    );
+    assert_eq!(events.len(), 2);
+    // Still the same conditions, nothing new.
+    assert_eq!(
+        state.observe(
+            &[observation(
+                "Active",
+                5,
+                &["alice.near"],
+                &["asset_transfer_paused"]
+            )],
+            20,
+            max_era_age
+        ),
+        vec![]
+    );
+    // An unreadable appchain keeps its conditions.
+    let failed = AppchainObservation {
+        appchain_id: "fusotao".to_string(),
+        ..Default::default()
+    };
+    state.observe(&[failed], 30, max_era_age);
+    assert_eq!(
+        state.observe(
+            &[observation("Active", 5, &["alice.near"], &[])],
+            40,
+            max_era_age
+        ),
+        vec![]
+    );
+    assert_eq!(
+        state.observe(
+            &[observation("Active", 5, &["alice.near"], &[])],
+            100,
+            max_era_age
+        ),
+        vec![MonitorEvent::EraNotAdvancing {
+            appchain_id: "fusotao".to_string(),
+            era: 5,
+            stalled_secs: 100
+        }]
+    );
+    // A condition which cleared is alerted again.
+    assert_eq!(
+        state.observe(&[observation("Frozen", 6, &[], &[])], 110, max_era_age),
+        vec![MonitorEvent::AppchainStateChanged {
+            appchain_id: "fusotao".to_string(),
+            from: "Active".to_string(),
+            to: "Frozen".to_string()
+        }]
+    );
+    assert_eq!(
+        state
+            .observe(
+                &[observation("Frozen", 6, &["alice.near"], &[])],
+                120,
+                max_era_age
+            )
+            .len(),
+        1
+    );
+
+    assert_eq!(parse_duration("5m").unwrap(), Duration::from_secs(300));
+    assert_eq!(parse_duration("90").unwrap(), Duration::from_secs(90));
+    assert!(parse_duration("5w").is_err());
+}
+
+#[tokio::test]
+async fn test_post_event_to_webhook() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    // A stand-in webhook which answers 200 and hands over the request it got.
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let webhook: url::Url = format!("http://{}/alerts", listener.local_addr().unwrap())
+        .parse()
+        .unwrap();
+    let server = tokio::spawn(async move {
+        let (mut socket, _) = listener.accept().await.unwrap();
+        let mut request = vec![];
+        let mut buffer = [0u8; 4096];
+        loop {
+            let read = socket.read(&mut buffer).await.unwrap();
+            request.extend_from_slice(&buffer[..read]);
+            let text = String::from_utf8_lossy(&request).to_string();
+            if let Some((head, body)) = text.split_once("\r\n\r\n") {
+                let length = head
+                    .lines()
+                    .find_map(|e| {
+                        e.to_lowercase()
+                            .strip_prefix("content-length:")
+                            .map(|e| e.trim().parse::<usize>().unwrap())
+                    })
+                    .unwrap_or_default();
+                if body.len() >= length {
+                    socket
+                        .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")
+                        .await
+                        .unwrap();
+                    return body.to_string();
+                }
+            }
+        }
+    });
+
+    let event = MonitorEvent::NewUnprofitableValidator {
+        appchain_id: "fusotao".to_string(),
+        validator_id: "alice.near".to_string(),
+        eras: vec![51, 52],
+    };
+    let failures = post_event(
+        &reqwest::Client::new(),
+        &[webhook],
+        &"octopus-registry.near".parse().unwrap(),
+        &event,
+    )
+    .await;
+    assert!(failures.is_empty(), "{:?}", failures);
+
+    let body: serde_json::Value = serde_json::from_str(&server.await.unwrap()).unwrap();
+    assert_eq!(body["event"], "new_unprofitable_validator");
+    assert_eq!(body["registry_account_id"], "octopus-registry.near");
+    assert_eq!(body["validator_id"], "alice.near");
+    assert_eq!(body["eras"], serde_json::json!([51, 52]));
+}
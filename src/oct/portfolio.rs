@@ -0,0 +1,413 @@
+use crate::near::rpc::client::Client;
+use crate::oct::contracts::anchor::AnchorContract;
+use crate::oct::rewards::{
+    reward_targets, RewardQuery, RewardStatus, RewardTarget, RewardToken, RewardTotals,
+};
+use crate::oct::scan::{scan_progress_bar, ActiveAnchor, AppchainScanResult};
+use appchain_anchor::types::RewardHistory;
+use color_eyre::owo_colors::OwoColorize;
+use futures::stream::{self, StreamExt};
+use near_primitives::types::AccountId;
+use near_sdk::json_types::U64;
+use prettytable::{row, Cell, Row, Table};
+use std::collections::{BTreeMap, HashMap};
+
+/// Delegations are made in OCT.
+const OCT_DECIMALS: u8 = 18;
+
+/// A delegation to a validator with its reward history.
+#[derive(Debug, Clone)]
+pub struct Delegation {
+    pub validator_id: AccountId,
+    /// The last era of a validator which is not in the current era any more, the delegation is
+    /// the one of that era.
+    pub last_era: Option<u64>,
+    pub delegation_amount: u128,
+    pub rewards: Vec<RewardHistory>,
+}
+
+/// The delegations of a delegator in an anchor, rewards are from `start_era` to `end_era`.
+#[derive(Debug, Clone)]
+pub struct AnchorDelegations {
+    pub anchor: ActiveAnchor,
+    pub token: RewardToken,
+    pub start_era: u64,
+    pub end_era: u64,
+    /// Validators which left before this era are not searched, the anchor no longer keeps
+    /// their validator sets.
+    pub searched_from_era: u64,
+    pub delegations: Vec<Delegation>,
+}
+
+impl AnchorDelegations {
+    pub fn status_of(&self, reward: &RewardHistory) -> RewardStatus {
+        RewardStatus::at_anchor(reward, &self.anchor)
+    }
+}
+
+/// The first era after which an unwithdrawn reward is lost, if any is left to withdraw.
+pub fn next_expiry_era(
+    rewards: &[RewardHistory],
+    status_of: impl Fn(&RewardHistory) -> RewardStatus,
+) -> Option<u64> {
+    rewards
+        .iter()
+        .filter_map(|e| status_of(e).expires_after_era())
+        .min()
+}
+
+/// Finds the delegations of `delegator_id` to the validators of every active anchor in the eras
+/// whose rewards have not expired, and reads those rewards, with at most `concurrency` requests
+/// in flight. A validator unbonded since still has rewards to withdraw.
+///
+/// Results are in the order of `anchors`, an error of an anchor is reported as `Failed`
+/// without stopping the others.
+pub async fn fetch_delegations(
+    client: &Client,
+    delegator_id: &AccountId,
+    anchors: Vec<Result<ActiveAnchor, AppchainScanResult>>,
+    concurrency: usize,
+) -> anyhow::Result<Vec<Result<AnchorDelegations, AppchainScanResult>>> {
+    let query = RewardQuery::default();
+    let targets = reward_targets(client, anchors, &query, concurrency).await;
+
+    // Validator sets of the past eras whose rewards have not expired.
+    let era_requests = targets
+        .iter()
+        .filter_map(|e| e.as_ref().ok())
+        .flat_map(|target| past_eras_of(target, &query).map(move |era| (target, era)))
+        .collect::<Vec<_>>();
+    let progress = scan_progress_bar(era_requests.len(), "eras")?;
+    let progress_ref = &progress;
+    let era_results: Vec<(String, u64, anyhow::Result<Vec<AccountId>>)> =
+        stream::iter(era_requests.into_iter().map(|(target, era)| async move {
+            let result = validator_ids_of_era(client, target, era).await;
+            progress_ref.inc(1);
+            (target.anchor.appchain_id.clone(), era, result)
+        }))
+        .buffered(concurrency.max(1))
+        .collect()
+        .await;
+    progress.finish_and_clear();
+    let mut past_validators: HashMap<String, Vec<(u64, Vec<AccountId>)>> = HashMap::new();
+    let mut errors: HashMap<String, String> = HashMap::new();
+    for (appchain_id, era, result) in era_results {
+        match result {
+            Ok(validator_ids) => past_validators
+                .entry(appchain_id)
+                .or_default()
+                .push((era, validator_ids)),
+            Err(err) => {
+                errors.entry(appchain_id).or_insert_with(|| err.to_string());
+            }
+        }
+    }
+
+    let requests = targets
+        .iter()
+        .filter_map(|e| e.as_ref().ok())
+        .filter(|target| !errors.contains_key(&target.anchor.appchain_id))
+        .flat_map(|target| {
+            let past = past_validators
+                .get(&target.anchor.appchain_id)
+                .map(Vec::as_slice)
+                .unwrap_or_default();
+            validators_in_window(&target.validator_ids, past)
+                .into_iter()
+                .map(move |(validator_id, last_era)| (target, validator_id, last_era))
+        })
+        .collect::<Vec<_>>();
+    let progress = scan_progress_bar(requests.len(), "validators")?;
+    let progress_ref = &progress;
+    let query_ref = &query;
+    let delegation_results: Vec<(String, anyhow::Result<Option<Delegation>>)> = stream::iter(
+        requests
+            .into_iter()
+            .map(|(target, validator_id, last_era)| async move {
+                let result = delegation_of(
+                    client,
+                    target,
+                    &validator_id,
+                    last_era,
+                    delegator_id,
+                    query_ref,
+                )
+                .await;
+                progress_ref.inc(1);
+                (target.anchor.appchain_id.clone(), result)
+            }),
+    )
+    .buffered(concurrency.max(1))
+    .collect()
+    .await;
+    progress.finish_and_clear();
+
+    let mut delegation_results = delegation_results.into_iter().peekable();
+    let mut results = vec![];
+    for target in targets {
+        let target = match target {
+            Ok(target) => target,
+            Err(result) => {
+                results.push(Err(result));
+                continue;
+            }
+        };
+        let mut delegations = vec![];
+        let mut error = errors.remove(&target.anchor.appchain_id);
+        while let Some((_, result)) =
+            delegation_results.next_if(|(appchain_id, _)| appchain_id == &target.anchor.appchain_id)
+        {
+            match result {
+                Ok(Some(delegation)) => delegations.push(delegation),
+                Ok(None) => {}
+                Err(err) => {
+                    error.get_or_insert(err.to_string());
+                }
+            }
+        }
+        let (start_era, end_era) = query.era_range_of(&target.anchor);
+        let searched_from_era = start_era.max(target.anchor.oldest_era);
+        results.push(match error {
+            Some(error) => Err(AppchainScanResult::Failed {
+                appchain_id: target.anchor.appchain_id,
+                error,
+            }),
+            None => Ok(AnchorDelegations {
+                anchor: target.anchor,
+                token: target.token,
+                start_era,
+                end_era,
+                searched_from_era,
+                delegations,
+            }),
+        });
+    }
+    Ok(results)
+}
+
+/// The past eras of the reward window the anchor still keeps the validator set of.
+fn past_eras_of(target: &RewardTarget, query: &RewardQuery) -> std::ops::Range<u64> {
+    let (start_era, _) = query.era_range_of(&target.anchor);
+    start_era.max(target.anchor.oldest_era)..target.anchor.current_era
+}
+
+async fn validator_ids_of_era(
+    client: &Client,
+    target: &RewardTarget,
+    era: u64,
+) -> anyhow::Result<Vec<AccountId>> {
+    let info = AnchorContract::new(target.anchor.anchor_account_id.clone(), client)
+        .get_validator_set_info_of(era)
+        .await
+        .map_err(|err| {
+            anyhow::anyhow!(
+                "Failed to get validator set info of era {}, error: {}",
+                era,
+                err
+            )
+        })?;
+    Ok(info
+        .map(|e| e.validator_list)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|e| AccountId::try_from(e.validator_id.to_string()))
+        .collect::<Result<Vec<_>, _>>()?)
+}
+
+/// The validators of the current era, then the validators which left since with the last era
+/// they were in, `past` is sorted by era.
+fn validators_in_window(
+    current: &[AccountId],
+    past: &[(u64, Vec<AccountId>)],
+) -> Vec<(AccountId, Option<u64>)> {
+    let mut left = BTreeMap::new();
+    for (era, validator_ids) in past {
+        for validator_id in validator_ids.iter().filter(|e| !current.contains(e)) {
+            left.insert(validator_id.clone(), *era);
+        }
+    }
+    current
+        .iter()
+        .map(|e| (e.clone(), None))
+        .chain(left.into_iter().map(|(e, era)| (e, Some(era))))
+        .collect()
+}
+
+async fn delegation_of(
+    client: &Client,
+    target: &RewardTarget,
+    validator_id: &AccountId,
+    last_era: Option<u64>,
+    delegator_id: &AccountId,
+    query: &RewardQuery,
+) -> anyhow::Result<Option<Delegation>> {
+    let anchor = AnchorContract::new(target.anchor.anchor_account_id.clone(), client);
+    let delegator = anchor
+        .get_delegators_of_validator_in_era(last_era.map(U64), validator_id.clone())
+        .await
+        .map_err(|err| {
+            anyhow::anyhow!(
+                "Failed to get delegators of {}, error: {}",
+                validator_id,
+                err
+            )
+        })?
+        .into_iter()
+        .find(|e| e.delegator_id.as_str() == delegator_id.as_str());
+    let delegator = match delegator {
+        Some(delegator) => delegator,
+        None => return Ok(None),
+    };
+    let (start_era, end_era) = query.era_range_of(&target.anchor);
+    let rewards = anchor
+        .get_delegator_rewards_of(
+            start_era,
+            end_era,
+            delegator_id.clone(),
+            validator_id.clone(),
+        )
+        .await
+        .map_err(|err| {
+            anyhow::anyhow!(
+                "Failed to get rewards delegated to {}, error: {}",
+                validator_id,
+                err
+            )
+        })?;
+    Ok(Some(Delegation {
+        validator_id: validator_id.clone(),
+        last_era,
+        delegation_amount: delegator.delegation_amount.0,
+        rewards,
+    }))
+}
+
+/// Prints every delegation with its reward totals, then every era with an unwithdrawn reward.
+pub fn print_delegator_portfolio(delegator_id: &AccountId, portfolio: &[AnchorDelegations]) {
+    let oct = RewardToken {
+        symbol: "OCT".to_string(),
+        decimals: OCT_DECIMALS,
+    };
+    let mut table = Table::new();
+    table.set_titles(Row::new(vec![Cell::new(
+        format!("Delegations of {}", delegator_id.blue()).as_str(),
+    )
+    .style_spec("H8")]));
+    table.add_row(row![
+        "appchain",
+        "validator",
+        "delegation",
+        "total reward",
+        "withdrawn",
+        "unwithdrawn",
+        "expiring",
+        "next expiry"
+    ]);
+    let mut unwithdrawn = Table::new();
+    unwithdrawn.set_titles(row![
+        "appchain",
+        "validator",
+        "era",
+        "unwithdrawn",
+        "status"
+    ]);
+    for anchor in portfolio {
+        let token = &anchor.token;
+        for delegation in &anchor.delegations {
+            let validator = match delegation.last_era {
+                Some(era) => format!("{} (left after era {})", delegation.validator_id, era),
+                None => delegation.validator_id.to_string(),
+            };
+            let totals = RewardTotals::of(&delegation.rewards, |e| anchor.status_of(e));
+            let next_expiry = match next_expiry_era(&delegation.rewards, |e| anchor.status_of(e)) {
+                Some(era) if totals.expiring > 0 => {
+                    format!("after era {}", era).yellow().to_string()
+                }
+                Some(era) => format!("after era {}", era),
+                None => "-".to_string(),
+            };
+            table.add_row(row![
+                anchor.anchor.appchain_id,
+                validator,
+                oct.format(delegation.delegation_amount),
+                token.format(totals.total),
+                token.format(totals.withdrawn),
+                token.format(totals.unwithdrawn),
+                token.format(totals.expiring),
+                next_expiry
+            ]);
+            for reward in delegation
+                .rewards
+                .iter()
+                .filter(|e| e.unwithdrawn_reward.0 > 0)
+            {
+                unwithdrawn.add_row(row![
+                    anchor.anchor.appchain_id,
+                    validator,
+                    reward.era_number.0,
+                    token.format(reward.unwithdrawn_reward.0),
+                    anchor.status_of(reward).colored()
+                ]);
+            }
+        }
+    }
+    table.printstd();
+    if !unwithdrawn.is_empty() {
+        unwithdrawn.printstd();
+    }
+}
+
+#[test]
+fn test_next_expiry_era() {
+    use near_sdk::json_types::{U128, U64};
+
+    let reward = |era, unwithdrawn, expired| RewardHistory {
+        era_number: U64(era),
+        total_reward: U128(100),
+        unwithdrawn_reward: U128(unwithdrawn),
+        expired,
+    };
+    let rewards = [
+        reward(5, 100, true),
+        reward(10, 0, false),
+        reward(11, 100, false),
+        reward(12, 100, false),
+    ];
+    assert_eq!(
+        next_expiry_era(&rewards, |e| RewardStatus::of(e, 90, 84)),
+        Some(95)
+    );
+    assert_eq!(
+        next_expiry_era(&rewards[..2], |e| RewardStatus::of(e, 90, 84)),
+        None
+    );
+}
+
+#[test]
+fn test_validators_in_window() {
+    let ids = |ids: &[&str]| {
+        ids.iter()
+            .map(|e| e.parse().unwrap())
+            .collect::<Vec<AccountId>>()
+    };
+    let validators = validators_in_window(
+        &ids(&["alice.near", "bob.near"]),
+        &[
+            (50, ids(&["alice.near", "carol.near", "dave.near"])),
+            (51, ids(&["alice.near", "carol.near"])),
+            (52, ids(&["alice.near", "bob.near"])),
+        ],
+    );
+    assert_eq!(
+        validators
+            .iter()
+            .map(|(id, era)| (id.as_str(), *era))
+            .collect::<Vec<_>>(),
+        vec![
+            ("alice.near", None),
+            ("bob.near", None),
+            ("carol.near", Some(51)),
+            ("dave.near", Some(50)),
+        ]
+    );
+}
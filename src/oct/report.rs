@@ -0,0 +1,269 @@
+use crate::oct::rewards::{AnchorRewards, RewardStatus};
+use crate::oct::scan::AnchorScan;
+use near_primitives::types::AccountId;
+use serde::Serialize;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+/// Stands for no report file.
+pub const NO_REPORT: &str = "none";
+
+const REPORT_STYLE: &str = "body{font-family:sans-serif;margin:2em;color:#222}\
+table{border-collapse:collapse;margin-bottom:2em}\
+caption{font-weight:bold;text-align:left;padding:.5em 0}\
+th,td{border:1px solid #ccc;padding:.3em .6em;text-align:center}\
+td.account{text-align:left}\
+.profitable,.withdrawn{background:#dff0d8}\
+.unprofitable,.expired{background:#f2dede}\
+.expiring{background:#fcf8e3}\
+.note{color:#8a6d3b}";
+
+/// Parses a report file option, `none` stands for no report.
+pub fn parse_report_path(path: &str) -> Option<PathBuf> {
+    if path.trim().is_empty() || path.trim().eq_ignore_ascii_case(NO_REPORT) {
+        return None;
+    }
+    Some(PathBuf::from(path.trim()))
+}
+
+/// A row of the unprofitable validators csv, one per validator and era.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct UnprofitableRecord {
+    pub appchain_id: String,
+    pub anchor_account_id: AccountId,
+    pub validator_id: AccountId,
+    pub era: u64,
+    pub unprofitable: bool,
+}
+
+/// A row of the rewards csv, one per validator and era. Amounts are in the reward token.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RewardRecord {
+    pub appchain_id: String,
+    pub validator_id: AccountId,
+    pub era: u64,
+    pub token: String,
+    pub total_reward: String,
+    pub withdrawn_reward: String,
+    pub unwithdrawn_reward: String,
+    #[serde(skip)]
+    pub reward_status: RewardStatus,
+    pub status: String,
+}
+
+pub fn unprofitable_records(scans: &[&AnchorScan]) -> Vec<UnprofitableRecord> {
+    let mut records = vec![];
+    for scan in scans {
+        let matrix = scan.unprofitable_matrix();
+        for (validator_id, unprofitable) in &matrix.rows {
+            for (era, unprofitable) in matrix.eras.iter().zip(unprofitable) {
+                records.push(UnprofitableRecord {
+                    appchain_id: scan.appchain_id.clone(),
+                    anchor_account_id: scan.anchor_account_id.clone(),
+                    validator_id: validator_id.clone(),
+                    era: *era,
+                    unprofitable: *unprofitable,
+                });
+            }
+        }
+    }
+    records
+}
+
+pub fn reward_records(rewards: &[&AnchorRewards]) -> Vec<RewardRecord> {
+    let mut records = vec![];
+    for anchor_rewards in rewards {
+        let token = &anchor_rewards.token;
+        for validator in &anchor_rewards.validators {
+            for reward in &validator.rewards {
+                let reward_status = anchor_rewards.status_of(reward);
+                records.push(RewardRecord {
+                    appchain_id: anchor_rewards.anchor.appchain_id.clone(),
+                    validator_id: validator.validator_id.clone(),
+                    era: reward.era_number.0,
+                    token: token.symbol.clone(),
+                    total_reward: token.amount(reward.total_reward.0),
+                    withdrawn_reward: token.amount(
+                        reward
+                            .total_reward
+                            .0
+                            .saturating_sub(reward.unwithdrawn_reward.0),
+                    ),
+                    unwithdrawn_reward: token.amount(reward.unwithdrawn_reward.0),
+                    reward_status,
+                    status: reward_status.label(),
+                });
+            }
+        }
+    }
+    records
+}
+
+/// Writes records as csv with a header row.
+pub fn write_csv<T: Serialize>(writer: impl std::io::Write, records: &[T]) -> anyhow::Result<()> {
+    let mut writer = csv::Writer::from_writer(writer);
+    for record in records {
+        writer.serialize(record)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+pub fn save_csv<T: Serialize>(path: &Path, records: &[T]) -> anyhow::Result<()> {
+    write_csv(std::fs::File::create(path)?, records)
+}
+
+fn escape_html(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// A self-contained html page, `body` is already escaped.
+fn html_page(title: &str, generated_at: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n\
+         <style>{style}</style>\n</head>\n<body>\n<h1>{title}</h1>\n<p>Generated at {generated_at}.</p>\n\
+         {body}</body>\n</html>\n",
+        title = escape_html(title),
+        style = REPORT_STYLE,
+        generated_at = escape_html(generated_at),
+        body = body
+    )
+}
+
+/// An html report with the unprofitable validators table of each anchor.
+pub fn unprofitable_html(scans: &[&AnchorScan], title: &str, generated_at: &str) -> String {
+    let mut body = String::new();
+    for scan in scans {
+        let matrix = scan.unprofitable_matrix();
+        let _ = writeln!(
+            body,
+            "<table>\n<caption>{} unprofitable validators</caption>",
+            escape_html(scan.anchor_account_id.as_str())
+        );
+        if matrix.eras.is_empty() {
+            let _ = writeln!(body, "<tr><td>No era to check.</td></tr>");
+        } else if matrix.rows.is_empty() {
+            let _ = writeln!(
+                body,
+                "<tr><td class=\"profitable\">No unprofitable validator from era {} to era {}.</td></tr>",
+                matrix.eras[0],
+                matrix.eras[matrix.eras.len() - 1]
+            );
+        } else {
+            body.push_str("<tr><th>validators\\eras</th>");
+            for era in &matrix.eras {
+                let _ = write!(body, "<th>{}</th>", era);
+            }
+            body.push_str("</tr>\n");
+            for (validator_id, unprofitable) in &matrix.rows {
+                let _ = write!(
+                    body,
+                    "<tr><td class=\"account\">{}</td>",
+                    escape_html(validator_id.as_str())
+                );
+                for unprofitable in unprofitable {
+                    body.push_str(if *unprofitable {
+                        "<td class=\"unprofitable\">×</td>"
+                    } else {
+                        "<td class=\"profitable\">√</td>"
+                    });
+                }
+                body.push_str("</tr>\n");
+            }
+        }
+        body.push_str("</table>\n");
+        if !scan.missing_eras.is_empty() {
+            let _ = writeln!(
+                body,
+                "<p class=\"note\">No validator set info of eras {}.</p>",
+                scan.missing_eras
+                    .iter()
+                    .map(|e| e.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+    }
+    html_page(title, generated_at, &body)
+}
+
+fn status_class(status: &RewardStatus) -> &'static str {
+    match status {
+        RewardStatus::Withdrawn => "withdrawn",
+        RewardStatus::Unwithdrawn { .. } => "unwithdrawn",
+        RewardStatus::Expiring { .. } => "expiring",
+        RewardStatus::Expired => "expired",
+    }
+}
+
+/// An html report with a rewards table of each appchain in `records`.
+pub fn rewards_html(records: &[RewardRecord], title: &str, generated_at: &str) -> String {
+    let mut body = String::new();
+    let mut records = records.iter().peekable();
+    while let Some(first) = records.peek().cloned() {
+        let _ = writeln!(
+            body,
+            "<table>\n<caption>{} rewards</caption>\n<tr><th>validator</th><th>era</th>\
+             <th>total</th><th>withdrawn</th><th>unwithdrawn</th><th>status</th></tr>",
+            escape_html(&first.appchain_id)
+        );
+        while let Some(record) = records.next_if(|e| e.appchain_id == first.appchain_id) {
+            let _ = writeln!(
+                body,
+                "<tr><td class=\"account\">{}</td><td>{}</td><td>{} {token}</td><td>{} {token}</td>\
+                 <td>{} {token}</td><td class=\"{}\">{}</td></tr>",
+                escape_html(record.validator_id.as_str()),
+                record.era,
+                record.total_reward,
+                record.withdrawn_reward,
+                record.unwithdrawn_reward,
+                status_class(&record.reward_status),
+                escape_html(&record.status),
+                token = escape_html(&record.token)
+            );
+        }
+        body.push_str("</table>\n");
+    }
+    html_page(title, generated_at, &body)
+}
+
+#[test]
+fn test_unprofitable_report() {
+    use crate::oct::scan::EraValidators;
+
+    let era = |era, validators: &[&str]| EraValidators {
+        era,
+        unprofitable_validator_ids: validators.iter().map(|e| e.parse().unwrap()).collect(),
+    };
+    let scan = AnchorScan {
+        appchain_id: "fusotao".to_string(),
+        anchor_account_id: "fusotao.octopus-registry.near".parse().unwrap(),
+        current_era: 52,
+        maximum_allowed_unprofitable_era_count: 3,
+        eras: vec![era(51, &["bob.near"]), era(52, &["alice.near", "bob.near"])],
+        missing_eras: vec![50],
+    };
+
+    let mut csv = vec![];
+    write_csv(&mut csv, &unprofitable_records(&[&scan])).unwrap();
+    assert_eq!(
+        String::from_utf8(csv).unwrap(),
+        "appchain_id,anchor_account_id,validator_id,era,unprofitable\n\
+         fusotao,fusotao.octopus-registry.near,alice.near,51,false\n\
+         fusotao,fusotao.octopus-registry.near,alice.near,52,true\n\
+         fusotao,fusotao.octopus-registry.near,bob.near,51,true\n\
+         fusotao,fusotao.octopus-registry.near,bob.near,52,true\n"
+    );
+
+    let html = unprofitable_html(&[&scan], "Unprofitable <validators>", "2022-10-01");
+    assert!(html.starts_with("<!DOCTYPE html>"));
+    assert!(html.contains("<h1>Unprofitable &lt;validators&gt;</h1>"));
+    assert!(html.contains(
+        "<tr><td class=\"account\">alice.near</td><td class=\"profitable\">√</td><td class=\"unprofitable\">×</td></tr>"
+    ));
+    assert!(html.contains("No validator set info of eras 50."));
+}
@@ -0,0 +1,521 @@
+use crate::near::rpc::client::Client;
+use crate::near::view_check::select_json_path;
+use crate::oct::contracts::anchor::AnchorContract;
+use crate::oct::scan::{scan_progress_bar, ActiveAnchor, AppchainScanResult};
+use appchain_anchor::types::RewardHistory;
+use color_eyre::owo_colors::OwoColorize;
+use futures::stream::{self, StreamExt};
+use near_primitives::types::AccountId;
+use prettytable::{row, Cell, Row, Table};
+
+/// Unwithdrawn rewards which expire within this many eras are highlighted.
+pub const EXPIRING_WITHIN_ERAS: u64 = 3;
+/// Used when the wrapped appchain token has no decimals in its metadata.
+const DEFAULT_TOKEN_DECIMALS: u8 = 18;
+
+/// The wrapped appchain token rewards are paid in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RewardToken {
+    pub symbol: String,
+    pub decimals: u8,
+}
+
+impl RewardToken {
+    pub async fn of_anchor(anchor: &AnchorContract<'_>) -> anyhow::Result<Self> {
+        Ok(Self::from_token_info(
+            &anchor.get_wrapped_appchain_token().await?,
+        ))
+    }
+
+    /// Reads `metadata.symbol` and `metadata.decimals` of the wrapped appchain token info.
+    fn from_token_info(token: &serde_json::Value) -> Self {
+        let field = |path| select_json_path(token, path).ok().flatten();
+        Self {
+            symbol: field("$.metadata.symbol")
+                .and_then(|e| e.as_str())
+                .unwrap_or_default()
+                .to_string(),
+            decimals: field("$.metadata.decimals")
+                .and_then(|e| e.as_u64())
+                .and_then(|e| u8::try_from(e).ok())
+                .filter(|e| *e <= 38)
+                .unwrap_or(DEFAULT_TOKEN_DECIMALS),
+        }
+    }
+
+    /// Converts an amount in the smallest unit to a decimal number, eg: `12.5`.
+    pub fn amount(&self, amount: u128) -> String {
+        let one = 10u128.pow(self.decimals as u32);
+        if amount.is_multiple_of(one) {
+            (amount / one).to_string()
+        } else {
+            format!(
+                "{}.{}",
+                amount / one,
+                format!("{:0>1$}", amount % one, self.decimals as usize).trim_end_matches('0')
+            )
+        }
+    }
+
+    /// Formats an amount in the smallest unit, eg: `12.5 ATP`.
+    pub fn format(&self, amount: u128) -> String {
+        let amount = self.amount(amount);
+        if self.symbol.is_empty() {
+            amount
+        } else {
+            format!("{} {}", amount, self.symbol)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RewardStatus {
+    Withdrawn,
+    Unwithdrawn {
+        expires_after_era: u64,
+    },
+    /// Unwithdrawn and expires within `EXPIRING_WITHIN_ERAS` eras.
+    Expiring {
+        expires_after_era: u64,
+    },
+    Expired,
+}
+
+impl RewardStatus {
+    /// A reward of an era can be withdrawn until `maximum_era_count_of_unwithdrawn_reward` eras
+    /// later, the anchor tells whether it has expired already.
+    pub fn of(
+        reward: &RewardHistory,
+        current_era: u64,
+        maximum_era_count_of_unwithdrawn_reward: u64,
+    ) -> Self {
+        if reward.expired {
+            return RewardStatus::Expired;
+        }
+        if reward.unwithdrawn_reward.0 == 0 {
+            return RewardStatus::Withdrawn;
+        }
+        let expires_after_era = reward.era_number.0 + maximum_era_count_of_unwithdrawn_reward;
+        if expires_after_era.saturating_sub(current_era) <= EXPIRING_WITHIN_ERAS {
+            RewardStatus::Expiring { expires_after_era }
+        } else {
+            RewardStatus::Unwithdrawn { expires_after_era }
+        }
+    }
+
+    pub fn at_anchor(reward: &RewardHistory, anchor: &ActiveAnchor) -> Self {
+        Self::of(
+            reward,
+            anchor.current_era,
+            anchor.settings.maximum_era_count_of_unwithdrawn_reward.0,
+        )
+    }
+
+    /// The last era the reward can be withdrawn in, if it is still to be withdrawn.
+    pub fn expires_after_era(&self) -> Option<u64> {
+        match self {
+            RewardStatus::Unwithdrawn { expires_after_era }
+            | RewardStatus::Expiring { expires_after_era } => Some(*expires_after_era),
+            RewardStatus::Withdrawn | RewardStatus::Expired => None,
+        }
+    }
+
+    pub fn label(&self) -> String {
+        match self {
+            RewardStatus::Withdrawn => "withdrawn".to_string(),
+            RewardStatus::Unwithdrawn { expires_after_era } => {
+                format!("unwithdrawn, expires after era {}", expires_after_era)
+            }
+            RewardStatus::Expiring { expires_after_era } => {
+                format!("expiring after era {}", expires_after_era)
+            }
+            RewardStatus::Expired => "expired".to_string(),
+        }
+    }
+
+    pub fn colored(&self) -> String {
+        match self {
+            RewardStatus::Withdrawn => self.label().green().to_string(),
+            RewardStatus::Unwithdrawn { .. } => self.label(),
+            RewardStatus::Expiring { .. } => self.label().yellow().to_string(),
+            RewardStatus::Expired => self.label().red().to_string(),
+        }
+    }
+}
+
+/// Which eras and validators to read rewards of, `None` and empty stand for the defaults.
+#[derive(Debug, Clone, Default)]
+pub struct RewardQuery {
+    /// Defaults to the oldest era whose rewards have not expired.
+    pub start_era: Option<u64>,
+    /// Defaults to the current era.
+    pub end_era: Option<u64>,
+    /// Defaults to the validators of the current era.
+    pub validator_ids: Vec<AccountId>,
+}
+
+impl RewardQuery {
+    pub fn era_range_of(&self, anchor: &ActiveAnchor) -> (u64, u64) {
+        let start_era = self.start_era.unwrap_or_else(|| {
+            anchor
+                .current_era
+                .saturating_sub(anchor.settings.maximum_era_count_of_unwithdrawn_reward.0)
+        });
+        (start_era, self.end_era.unwrap_or(anchor.current_era))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ValidatorRewards {
+    pub validator_id: AccountId,
+    pub rewards: Vec<RewardHistory>,
+}
+
+/// The rewards of the validators of an anchor from `start_era` to `end_era`.
+#[derive(Debug, Clone)]
+pub struct AnchorRewards {
+    pub anchor: ActiveAnchor,
+    pub token: RewardToken,
+    pub start_era: u64,
+    pub end_era: u64,
+    pub validators: Vec<ValidatorRewards>,
+}
+
+impl AnchorRewards {
+    pub fn totals_of(&self, validator: &ValidatorRewards) -> RewardTotals {
+        RewardTotals::of(&validator.rewards, |e| self.status_of(e))
+    }
+
+    pub fn status_of(&self, reward: &RewardHistory) -> RewardStatus {
+        RewardStatus::at_anchor(reward, &self.anchor)
+    }
+}
+
+/// Sums of rewards, `unwithdrawn` leaves out expired rewards and includes `expiring`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RewardTotals {
+    pub total: u128,
+    pub withdrawn: u128,
+    pub unwithdrawn: u128,
+    pub expiring: u128,
+}
+
+impl RewardTotals {
+    pub fn of(
+        rewards: &[RewardHistory],
+        status_of: impl Fn(&RewardHistory) -> RewardStatus,
+    ) -> Self {
+        let mut totals = RewardTotals::default();
+        for reward in rewards {
+            totals.total += reward.total_reward.0;
+            totals.withdrawn += reward
+                .total_reward
+                .0
+                .saturating_sub(reward.unwithdrawn_reward.0);
+            match status_of(reward) {
+                RewardStatus::Expired => {}
+                RewardStatus::Expiring { .. } => {
+                    totals.unwithdrawn += reward.unwithdrawn_reward.0;
+                    totals.expiring += reward.unwithdrawn_reward.0;
+                }
+                _ => totals.unwithdrawn += reward.unwithdrawn_reward.0,
+            }
+        }
+        totals
+    }
+
+    pub fn add(&mut self, other: &RewardTotals) {
+        self.total += other.total;
+        self.withdrawn += other.withdrawn;
+        self.unwithdrawn += other.unwithdrawn;
+        self.expiring += other.expiring;
+    }
+}
+
+/// The validators of an anchor to read rewards of, with the reward token.
+#[derive(Debug, Clone)]
+pub struct RewardTarget {
+    pub anchor: ActiveAnchor,
+    pub token: RewardToken,
+    pub validator_ids: Vec<AccountId>,
+}
+
+/// Reads the reward token and the validators to query of every active anchor.
+pub async fn reward_targets(
+    client: &Client,
+    anchors: Vec<Result<ActiveAnchor, AppchainScanResult>>,
+    query: &RewardQuery,
+    concurrency: usize,
+) -> Vec<Result<RewardTarget, AppchainScanResult>> {
+    stream::iter(anchors.into_iter().map(|anchor| async move {
+        let anchor = anchor?;
+        reward_target_of(client, anchor.clone(), query)
+            .await
+            .map_err(|err| AppchainScanResult::Failed {
+                appchain_id: anchor.appchain_id,
+                error: err.to_string(),
+            })
+    }))
+    .buffered(concurrency.max(1))
+    .collect()
+    .await
+}
+
+/// Reads the rewards of the active anchors with at most `concurrency` requests in flight.
+///
+/// Results are in the order of `anchors`, an error of an anchor is reported as `Failed`
+/// without stopping the others.
+pub async fn fetch_rewards(
+    client: &Client,
+    anchors: Vec<Result<ActiveAnchor, AppchainScanResult>>,
+    query: &RewardQuery,
+    concurrency: usize,
+) -> anyhow::Result<Vec<Result<AnchorRewards, AppchainScanResult>>> {
+    let targets = reward_targets(client, anchors, query, concurrency).await;
+
+    let requests = targets
+        .iter()
+        .filter_map(|e| e.as_ref().ok())
+        .flat_map(|target| target.validator_ids.iter().map(move |e| (target, e)))
+        .collect::<Vec<_>>();
+    let progress = scan_progress_bar(requests.len(), "validators")?;
+    let progress_ref = &progress;
+    let reward_results: Vec<(String, anyhow::Result<ValidatorRewards>)> = stream::iter(
+        requests
+            .into_iter()
+            .map(|(target, validator_id)| async move {
+                let (start_era, end_era) = query.era_range_of(&target.anchor);
+                let result = AnchorContract::new(target.anchor.anchor_account_id.clone(), client)
+                    .get_validator_rewards_of(start_era, end_era, validator_id.clone())
+                    .await
+                    .map(|rewards| ValidatorRewards {
+                        validator_id: validator_id.clone(),
+                        rewards,
+                    })
+                    .map_err(|err| {
+                        anyhow::anyhow!("Failed to get rewards of {}, error: {}", validator_id, err)
+                    });
+                progress_ref.inc(1);
+                (target.anchor.appchain_id.clone(), result)
+            }),
+    )
+    .buffered(concurrency.max(1))
+    .collect()
+    .await;
+    progress.finish_and_clear();
+
+    let mut reward_results = reward_results.into_iter().peekable();
+    let mut results = vec![];
+    for target in targets {
+        let target = match target {
+            Ok(target) => target,
+            Err(result) => {
+                results.push(Err(result));
+                continue;
+            }
+        };
+        let mut validators = vec![];
+        let mut error = None;
+        while let Some((_, result)) =
+            reward_results.next_if(|(appchain_id, _)| appchain_id == &target.anchor.appchain_id)
+        {
+            match result {
+                Ok(rewards) => validators.push(rewards),
+                Err(err) => {
+                    error.get_or_insert(err.to_string());
+                }
+            }
+        }
+        let (start_era, end_era) = query.era_range_of(&target.anchor);
+        results.push(match error {
+            Some(error) => Err(AppchainScanResult::Failed {
+                appchain_id: target.anchor.appchain_id,
+                error,
+            }),
+            None => Ok(AnchorRewards {
+                anchor: target.anchor,
+                token: target.token,
+                start_era,
+                end_era,
+                validators,
+            }),
+        });
+    }
+    Ok(results)
+}
+
+async fn reward_target_of(
+    client: &Client,
+    anchor: ActiveAnchor,
+    query: &RewardQuery,
+) -> anyhow::Result<RewardTarget> {
+    let (start_era, end_era) = query.era_range_of(&anchor);
+    if start_era > end_era {
+        anyhow::bail!("Era range {}..{} is empty", start_era, end_era);
+    }
+    let contract = AnchorContract::new(anchor.anchor_account_id.clone(), client);
+    let token = RewardToken::of_anchor(&contract).await.map_err(|err| {
+        anyhow::anyhow!(
+            "Failed to get wrapped appchain token of {}, error: {}",
+            anchor.anchor_account_id,
+            err
+        )
+    })?;
+    let validator_ids = if query.validator_ids.is_empty() {
+        contract
+            .get_validator_list_of(None)
+            .await
+            .map_err(|err| anyhow::anyhow!("Failed to get validator list, error: {}", err))?
+            .into_iter()
+            .map(|e| AccountId::try_from(e.validator_id.to_string()))
+            .collect::<Result<Vec<_>, _>>()?
+    } else {
+        query.validator_ids.clone()
+    };
+    Ok(RewardTarget {
+        anchor,
+        token,
+        validator_ids,
+    })
+}
+
+/// Prints the rewards of every era with a reward, then the totals of every validator.
+pub fn print_anchor_rewards(rewards: &AnchorRewards) {
+    let token = &rewards.token;
+    let mut table = Table::new();
+    table.set_titles(Row::new(vec![Cell::new(
+        format!(
+            "{} rewards from era {} to era {}, current era {}",
+            rewards.anchor.anchor_account_id.blue(),
+            rewards.start_era,
+            rewards.end_era,
+            rewards.anchor.current_era
+        )
+        .as_str(),
+    )
+    .style_spec("H6")]));
+    table.add_row(row![
+        "validator",
+        "era",
+        "total",
+        "withdrawn",
+        "unwithdrawn",
+        "status"
+    ]);
+    for validator in &rewards.validators {
+        for reward in validator
+            .rewards
+            .iter()
+            .filter(|e| e.total_reward.0 > 0 || e.unwithdrawn_reward.0 > 0)
+        {
+            table.add_row(row![
+                validator.validator_id,
+                reward.era_number.0,
+                token.format(reward.total_reward.0),
+                token.format(
+                    reward
+                        .total_reward
+                        .0
+                        .saturating_sub(reward.unwithdrawn_reward.0)
+                ),
+                token.format(reward.unwithdrawn_reward.0),
+                rewards.status_of(reward).colored()
+            ]);
+        }
+    }
+    table.printstd();
+
+    let mut totals = Table::new();
+    totals.set_titles(row![
+        "validator",
+        "total",
+        "withdrawn",
+        "unwithdrawn",
+        format!("expiring in {} eras", EXPIRING_WITHIN_ERAS)
+    ]);
+    let mut all = RewardTotals::default();
+    for validator in &rewards.validators {
+        let sums = rewards.totals_of(validator);
+        let expiring = if sums.expiring > 0 {
+            token.format(sums.expiring).yellow().to_string()
+        } else {
+            token.format(0)
+        };
+        totals.add_row(row![
+            validator.validator_id,
+            token.format(sums.total),
+            token.format(sums.withdrawn),
+            token.format(sums.unwithdrawn),
+            expiring
+        ]);
+        all.add(&sums);
+    }
+    totals.add_row(row![
+        "all validators",
+        token.format(all.total),
+        token.format(all.withdrawn),
+        token.format(all.unwithdrawn),
+        token.format(all.expiring)
+    ]);
+    totals.printstd();
+}
+
+#[test]
+fn test_reward_status() {
+    use near_sdk::json_types::{U128, U64};
+
+    let token = RewardToken::from_token_info(&serde_json::json!({
+        "metadata": { "symbol": "ATP", "decimals": 18 }
+    }));
+    assert_eq!(token.format(12_500_000_000_000_000_000), "12.5 ATP");
+    assert_eq!(token.format(0), "0 ATP");
+    let token = RewardToken::from_token_info(&serde_json::json!({}));
+    assert_eq!(token.decimals, DEFAULT_TOKEN_DECIMALS);
+    assert_eq!(token.format(1), "0.000000000000000001");
+
+    let reward = |era, unwithdrawn, expired| RewardHistory {
+        era_number: U64(era),
+        total_reward: U128(100),
+        unwithdrawn_reward: U128(unwithdrawn),
+        expired,
+    };
+    assert_eq!(
+        RewardStatus::of(&reward(10, 0, false), 50, 84),
+        RewardStatus::Withdrawn
+    );
+    assert_eq!(
+        RewardStatus::of(&reward(10, 100, false), 50, 84),
+        RewardStatus::Unwithdrawn {
+            expires_after_era: 94
+        }
+    );
+    assert_eq!(
+        RewardStatus::of(&reward(10, 100, false), 91, 84),
+        RewardStatus::Expiring {
+            expires_after_era: 94
+        }
+    );
+    assert_eq!(
+        RewardStatus::of(&reward(10, 100, true), 95, 84),
+        RewardStatus::Expired
+    );
+
+    let rewards = [
+        reward(10, 0, false),
+        reward(11, 40, false),
+        reward(12, 100, false),
+    ];
+    assert_eq!(
+        RewardTotals::of(&rewards, |e| RewardStatus::of(e, 93, 84)),
+        RewardTotals {
+            total: 300,
+            withdrawn: 160,
+            unwithdrawn: 140,
+            expiring: 140
+        }
+    );
+    assert_eq!(
+        RewardTotals::of(&rewards, |e| RewardStatus::of(e, 50, 84)).expiring,
+        0
+    );
+}
@@ -0,0 +1,689 @@
+use crate::near::rpc::client::{is_missing_at_block_error, Client};
+use crate::oct::contracts::anchor::AnchorContract;
+use crate::oct::contracts::registry::RegistryContract;
+use appchain_anchor::types::ProtocolSettings;
+use appchain_registry::types::AppchainState;
+use futures::stream::{self, StreamExt};
+use indicatif::{ProgressBar, ProgressStyle};
+use near_primitives::types::{AccountId, BlockHeight, BlockId};
+use std::ops::RangeInclusive;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+pub const ALL: &str = "all";
+pub const AUTO_ERA: &str = "auto";
+pub const LATEST_ERA: &str = "latest";
+/// Set by interfaces which own the terminal, scans then show no progress bar.
+static HIDE_SCAN_PROGRESS: AtomicBool = AtomicBool::new(false);
+/// Heights tried after a height without a block, before the anchor is taken as not deployed yet.
+const SKIPPED_BLOCKS_TO_TRY: u64 = 8;
+/// Validators with at most this many unprofitable eras left are at risk, the eras left are red
+/// or yellow in the risk table.
+pub const AT_RISK_ERAS_LEFT: u64 = 1;
+
+/// The validator set of an era as seen by the unprofitable check.
+#[derive(Debug, Clone)]
+pub struct EraValidators {
+    pub era: u64,
+    pub unprofitable_validator_ids: Vec<AccountId>,
+}
+
+/// Eras read of an anchor, `None` for an era without validator set info.
+type EraReads = Vec<(u64, Option<EraValidators>)>;
+
+/// The scanned eras of an anchor, by default those within
+/// `maximum_allowed_unprofitable_era_count` of the current era.
+#[derive(Debug, Clone)]
+pub struct AnchorScan {
+    pub appchain_id: String,
+    pub anchor_account_id: AccountId,
+    pub current_era: u64,
+    pub maximum_allowed_unprofitable_era_count: u64,
+    pub eras: Vec<EraValidators>,
+    /// Eras in the range without validator set info, they are left out of `eras`.
+    pub missing_eras: Vec<u64>,
+}
+
+/// What a scan covers, every active appchain and validator in the unprofitable window if unset.
+#[derive(Debug, Clone, Default)]
+pub struct ScanFilter {
+    pub appchain_ids: Vec<String>,
+    pub from_era: Option<u64>,
+    pub to_era: Option<u64>,
+    pub validator_ids: Vec<AccountId>,
+}
+
+impl ScanFilter {
+    /// The eras to scan of an anchor, eras after the current era are left out.
+    pub fn era_range_of(&self, anchor: &ActiveAnchor) -> RangeInclusive<u64> {
+        let start_era = self
+            .from_era
+            .unwrap_or_else(|| anchor.unprofitable_window_start());
+        let end_era = self
+            .to_era
+            .unwrap_or(anchor.current_era)
+            .min(anchor.current_era);
+        start_era..=end_era
+    }
+
+    fn keeps_validator(&self, validator_id: &AccountId) -> bool {
+        self.validator_ids.is_empty() || self.validator_ids.contains(validator_id)
+    }
+}
+
+/// Parses an era option, `default` (eg: `auto` or `latest`) stands for no era.
+pub fn parse_era(era: &str, default: &str) -> color_eyre::eyre::Result<Option<u64>> {
+    if era.trim().eq_ignore_ascii_case(default) {
+        return Ok(None);
+    }
+    era.trim().parse().map(Some).map_err(|_| {
+        color_eyre::Report::msg(format!(
+            "Invalid era '{}', expected a number or '{}'",
+            era, default
+        ))
+    })
+}
+
+/// Parses a comma separated list, `all` stands for an empty list.
+pub fn parse_list(list: &str) -> Vec<String> {
+    if list.trim().eq_ignore_ascii_case(ALL) {
+        return vec![];
+    }
+    list.split(',')
+        .map(|e| e.trim().to_string())
+        .filter(|e| !e.is_empty())
+        .collect()
+}
+
+/// Parses a comma separated list of validator account ids, `all` stands for an empty list.
+pub fn parse_validator_ids(list: &str) -> color_eyre::eyre::Result<Vec<AccountId>> {
+    parse_list(list)
+        .iter()
+        .map(|e| {
+            e.parse::<AccountId>().map_err(|err| {
+                color_eyre::Report::msg(format!(
+                    "Invalid validator account id {}, error: {}",
+                    e, err
+                ))
+            })
+        })
+        .collect()
+}
+
+/// How close a validator is to being auto-unbonded for being unprofitable.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnbondRisk {
+    pub validator_id: AccountId,
+    /// Consecutive unprofitable eras up to the last scanned era.
+    pub streak: u64,
+    /// More unprofitable eras before the validator is unbonded, 0 if it is due.
+    pub eras_left: u64,
+    pub predicted_unbond_era: u64,
+}
+
+impl UnbondRisk {
+    /// Whether the validator is unbonded in at most `AT_RISK_ERAS_LEFT` more unprofitable eras.
+    pub fn is_at_risk(&self) -> bool {
+        self.eras_left <= AT_RISK_ERAS_LEFT
+    }
+}
+
+/// Every validator unprofitable in any scanned era of an anchor against the scanned eras.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnprofitableMatrix {
+    pub eras: Vec<u64>,
+    /// Validators sorted by account id, whether they were unprofitable in each of `eras`.
+    pub rows: Vec<(AccountId, Vec<bool>)>,
+}
+
+impl AnchorScan {
+    pub fn unprofitable_matrix(&self) -> UnprofitableMatrix {
+        let mut validator_ids = self
+            .eras
+            .iter()
+            .flat_map(|e| e.unprofitable_validator_ids.iter())
+            .collect::<Vec<_>>();
+        validator_ids.sort();
+        validator_ids.dedup();
+        UnprofitableMatrix {
+            eras: self.eras.iter().map(|e| e.era).collect(),
+            rows: validator_ids
+                .into_iter()
+                .map(|validator_id| {
+                    (
+                        validator_id.clone(),
+                        self.eras
+                            .iter()
+                            .map(|e| e.unprofitable_validator_ids.contains(validator_id))
+                            .collect(),
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    /// The validators unprofitable in the last scanned era, the most urgent first.
+    ///
+    /// A validator is auto-unbonded once it has been unprofitable for
+    /// `maximum_allowed_unprofitable_era_count` eras in a row, so if it stays unprofitable that
+    /// happens `eras_left` eras after the last scanned era.
+    pub fn unbond_risks(&self) -> Vec<UnbondRisk> {
+        let last_era = match self.eras.last() {
+            Some(era) => era,
+            None => return vec![],
+        };
+        let mut risks = last_era
+            .unprofitable_validator_ids
+            .iter()
+            .map(|validator_id| {
+                // A missing era breaks the streak, it is not known to be unprofitable.
+                let streak = self
+                    .eras
+                    .iter()
+                    .rev()
+                    .zip((0..=last_era.era).rev())
+                    .take_while(|(e, era)| {
+                        e.era == *era && e.unprofitable_validator_ids.contains(validator_id)
+                    })
+                    .count() as u64;
+                let eras_left = self
+                    .maximum_allowed_unprofitable_era_count
+                    .saturating_sub(streak);
+                UnbondRisk {
+                    validator_id: validator_id.clone(),
+                    streak,
+                    eras_left,
+                    predicted_unbond_era: last_era.era + eras_left,
+                }
+            })
+            .collect::<Vec<_>>();
+        risks.sort_by(|a, b| {
+            a.eras_left
+                .cmp(&b.eras_left)
+                .then(b.streak.cmp(&a.streak))
+                .then(a.validator_id.cmp(&b.validator_id))
+        });
+        risks
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum AppchainScanResult {
+    Scanned(AnchorScan),
+    /// The appchain is not active, there is nothing to check.
+    Skipped {
+        appchain_id: String,
+        anchor_account_id: AccountId,
+        appchain_state: AppchainState,
+    },
+    Failed {
+        appchain_id: String,
+        error: String,
+    },
+}
+
+impl AppchainScanResult {
+    pub fn appchain_id(&self) -> &str {
+        match self {
+            AppchainScanResult::Scanned(scan) => &scan.appchain_id,
+            AppchainScanResult::Skipped { appchain_id, .. }
+            | AppchainScanResult::Failed { appchain_id, .. } => appchain_id,
+        }
+    }
+}
+
+/// An active appchain with what its anchor reports at the moment.
+#[derive(Debug, Clone)]
+pub struct ActiveAnchor {
+    pub appchain_id: String,
+    pub anchor_account_id: AccountId,
+    pub current_era: u64,
+    /// The oldest era still kept by the anchor, older eras are only on archival nodes.
+    pub oldest_era: u64,
+    pub settings: ProtocolSettings,
+}
+
+impl ActiveAnchor {
+    /// The first era within `maximum_allowed_unprofitable_era_count` of the current era.
+    pub fn unprofitable_window_start(&self) -> u64 {
+        self.current_era
+            .saturating_sub(self.settings.maximum_allowed_unprofitable_era_count as u64)
+    }
+}
+
+/// A progress bar for a scan, `{msg}` names what is counted.
+pub fn scan_progress_bar(len: usize, message: &'static str) -> anyhow::Result<ProgressBar> {
+    if HIDE_SCAN_PROGRESS.load(Ordering::Relaxed) {
+        return Ok(ProgressBar::hidden());
+    }
+    let progress = ProgressBar::new(len as u64);
+    progress.set_style(
+        ProgressStyle::with_template("{msg} [{bar:40}] {pos}/{len}")?.progress_chars("=> "),
+    );
+    progress.set_message(message);
+    Ok(progress)
+}
+
+pub fn hide_scan_progress() {
+    HIDE_SCAN_PROGRESS.store(true, Ordering::Relaxed);
+}
+
+/// Reads the appchains of a registry with at most `concurrency` requests in flight, only those
+/// in `appchain_ids` unless it is empty.
+///
+/// Appchains which are not active are returned as `Skipped`, errors of an appchain as
+/// `Failed`, in the order of the registry.
+pub async fn active_anchors_of_registry(
+    client: &Client,
+    registry_account_id: AccountId,
+    appchain_ids: &[String],
+    concurrency: usize,
+) -> anyhow::Result<Vec<Result<ActiveAnchor, AppchainScanResult>>> {
+    let registry = RegistryContract::new(registry_account_id.clone(), client);
+    let mut registered_ids = registry.get_appchain_ids().await.map_err(|err| {
+        anyhow::anyhow!(
+            "Failed to get appchain ids of {}, error: {}",
+            registry_account_id,
+            err
+        )
+    })?;
+    if !appchain_ids.is_empty() {
+        if let Some(unknown) = appchain_ids.iter().find(|e| !registered_ids.contains(e)) {
+            anyhow::bail!(
+                "Appchain {} is not registered in {}",
+                unknown,
+                registry_account_id
+            );
+        }
+        registered_ids.retain(|e| appchain_ids.contains(e));
+    }
+    let appchain_ids = registered_ids;
+
+    let progress = scan_progress_bar(appchain_ids.len(), "appchains")?;
+    let registry = &registry;
+    let progress_ref = &progress;
+    let anchors = stream::iter(appchain_ids.into_iter().map(|appchain_id| async move {
+        let anchor = active_anchor_of(client, registry, &appchain_id).await;
+        progress_ref.inc(1);
+        match anchor {
+            Ok(anchor) => anchor,
+            Err(err) => Err(AppchainScanResult::Failed {
+                appchain_id,
+                error: err.to_string(),
+            }),
+        }
+    }))
+    .buffered(concurrency.max(1))
+    .collect()
+    .await;
+    progress.finish_and_clear();
+    Ok(anchors)
+}
+
+/// Scans the appchains of a registry in `filter` with at most `concurrency` requests in flight.
+///
+/// Appchains are first checked for their state and era range, then the validator set info of
+/// every era of every active appchain is fetched. Eras the anchor no longer keeps are read from
+/// `archival_client` if there is one. Results are in the order of the registry and an error of
+/// an appchain is reported as `Failed` without stopping the others.
+pub async fn scan_registry(
+    client: &Client,
+    archival_client: Option<&Client>,
+    registry_account_id: AccountId,
+    filter: &ScanFilter,
+    concurrency: usize,
+) -> anyhow::Result<Vec<AppchainScanResult>> {
+    let anchors = active_anchors_of_registry(
+        client,
+        registry_account_id,
+        &filter.appchain_ids,
+        concurrency,
+    )
+    .await?;
+
+    // Archived eras of an anchor are read one after another as they share the searched blocks,
+    // the other eras one by one.
+    let mut era_count = 0;
+    let mut tasks = vec![];
+    for anchor in anchors.iter().filter_map(|e| e.as_ref().ok()) {
+        let range = filter.era_range_of(anchor);
+        era_count += range.clone().count();
+        match archival_client {
+            Some(archival_client) if *range.start() < anchor.oldest_era => {
+                let archived = *range.start()..=(*range.end()).min(anchor.oldest_era - 1);
+                tasks.push((anchor, Some(archival_client), archived));
+                for era in anchor.oldest_era..=*range.end() {
+                    tasks.push((anchor, None, era..=era));
+                }
+            }
+            _ => tasks.extend(range.map(|era| (anchor, None, era..=era))),
+        }
+    }
+    let progress = scan_progress_bar(era_count, "eras")?;
+    let progress_ref = &progress;
+    let era_results: Vec<(String, anyhow::Result<EraReads>)> = stream::iter(tasks.into_iter().map(
+        |(anchor, archival_client, eras)| async move {
+            let result = match archival_client {
+                Some(archival_client) => {
+                    fetch_archived_era_validators(
+                        archival_client,
+                        &anchor.anchor_account_id,
+                        eras,
+                        progress_ref,
+                    )
+                    .await
+                }
+                None => {
+                    let era = *eras.start();
+                    let result = fetch_era_validators(client, &anchor.anchor_account_id, era, None)
+                        .await
+                        .map(|e| vec![(era, e)]);
+                    progress_ref.inc(1);
+                    result
+                }
+            };
+            (anchor.appchain_id.clone(), result)
+        },
+    ))
+    .buffered(concurrency.max(1))
+    .collect()
+    .await;
+    progress.finish_and_clear();
+
+    let mut era_results = era_results.into_iter().peekable();
+    let mut results = vec![];
+    for anchor in anchors {
+        let anchor = match anchor {
+            Ok(anchor) => anchor,
+            Err(result) => {
+                results.push(result);
+                continue;
+            }
+        };
+        let mut eras = vec![];
+        let mut missing_eras = vec![];
+        let mut error = None;
+        while let Some((_, result)) =
+            era_results.next_if(|(appchain_id, _)| appchain_id == &anchor.appchain_id)
+        {
+            match result {
+                Ok(results) => {
+                    for (era, validators) in results {
+                        match validators {
+                            Some(mut validators) => {
+                                validators
+                                    .unprofitable_validator_ids
+                                    .retain(|e| filter.keeps_validator(e));
+                                eras.push(validators);
+                            }
+                            None => missing_eras.push(era),
+                        }
+                    }
+                }
+                Err(err) => {
+                    error.get_or_insert(err.to_string());
+                }
+            }
+        }
+        results.push(match error {
+            Some(error) => AppchainScanResult::Failed {
+                appchain_id: anchor.appchain_id,
+                error,
+            },
+            None => AppchainScanResult::Scanned(AnchorScan {
+                appchain_id: anchor.appchain_id,
+                anchor_account_id: anchor.anchor_account_id,
+                current_era: anchor.current_era,
+                maximum_allowed_unprofitable_era_count: anchor
+                    .settings
+                    .maximum_allowed_unprofitable_era_count
+                    as u64,
+                eras,
+                missing_eras,
+            }),
+        });
+    }
+    Ok(results)
+}
+
+/// Reads the era range to check of an active appchain, or the result for a skipped one.
+async fn active_anchor_of(
+    client: &Client,
+    registry: &RegistryContract<'_>,
+    appchain_id: &str,
+) -> anyhow::Result<Result<ActiveAnchor, AppchainScanResult>> {
+    let anchor_account_id = registry.anchor_account_id_of(appchain_id)?;
+    let appchain_state = registry
+        .get_appchain_status_of(appchain_id.to_string())
+        .await
+        .map_err(|err| anyhow::anyhow!("Failed to get appchain status, error: {}", err))?
+        .appchain_state;
+    if !matches!(appchain_state, AppchainState::Active) {
+        return Ok(Err(AppchainScanResult::Skipped {
+            appchain_id: appchain_id.to_string(),
+            anchor_account_id,
+            appchain_state,
+        }));
+    }
+
+    let anchor = AnchorContract::new(anchor_account_id.clone(), client);
+    let anchor_status = anchor.get_anchor_status().await.map_err(|err| {
+        anyhow::anyhow!(
+            "Failed to get anchor({}) status, error: {}",
+            anchor_account_id,
+            err
+        )
+    })?;
+    let settings = anchor.get_protocol_settings().await.map_err(|err| {
+        anyhow::anyhow!(
+            "Failed to get protocol settings of anchor({}), error: {}",
+            anchor_account_id,
+            err
+        )
+    })?;
+    Ok(Ok(ActiveAnchor {
+        appchain_id: appchain_id.to_string(),
+        anchor_account_id,
+        current_era: anchor_status
+            .index_range_of_validator_set_history
+            .end_index
+            .0,
+        oldest_era: anchor_status
+            .index_range_of_validator_set_history
+            .start_index
+            .0,
+        settings,
+    }))
+}
+
+/// Reads the unprofitable validators of an era at `block_id`, `None` if the anchor has no
+/// validator set info of the era.
+async fn fetch_era_validators(
+    client: &Client,
+    anchor_account_id: &AccountId,
+    era: u64,
+    block_id: Option<BlockId>,
+) -> anyhow::Result<Option<EraValidators>> {
+    let info = AnchorContract::new(anchor_account_id.clone(), client)
+        .get_validator_set_info_of_at(era, block_id)
+        .await
+        .map_err(|err| {
+            anyhow::anyhow!(
+                "Failed to get validator set info of era({}), error: {}",
+                era,
+                err
+            )
+        })?;
+    let info = match info {
+        Some(info) => info,
+        None => return Ok(None),
+    };
+    let unprofitable_validator_ids = info
+        .unprofitable_validator_ids
+        .iter()
+        .map(|id| AccountId::try_from(id.to_string()))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(Some(EraValidators {
+        era,
+        unprofitable_validator_ids,
+    }))
+}
+
+/// Reads eras the anchor no longer keeps from an archival node.
+///
+/// An era is read at the last block before the anchor removed it, where the unprofitable
+/// validators of every era but the newest one are final. That block also keeps the following
+/// eras, so one block search usually covers many eras.
+async fn fetch_archived_era_validators(
+    archival_client: &Client,
+    anchor_account_id: &AccountId,
+    eras: RangeInclusive<u64>,
+    progress: &ProgressBar,
+) -> anyhow::Result<EraReads> {
+    let latest_height = archival_client
+        .view_block(None)
+        .await
+        .map_err(|err| anyhow::anyhow!("Failed to get the latest block, error: {}", err))?
+        .header
+        .height;
+    let mut results = vec![];
+    let mut era = *eras.start();
+    while era <= *eras.end() {
+        let last_era =
+            match last_block_keeping_era(archival_client, anchor_account_id, era, latest_height)
+                .await?
+            {
+                Some((height, newest_era)) => {
+                    let last_era = newest_era.saturating_sub(1).min(*eras.end()).max(era);
+                    for era in era..=last_era {
+                        results.push((
+                            era,
+                            fetch_era_validators(
+                                archival_client,
+                                anchor_account_id,
+                                era,
+                                Some(BlockId::Height(height)),
+                            )
+                            .await?,
+                        ));
+                        progress.inc(1);
+                    }
+                    last_era
+                }
+                // The anchor never had the era.
+                None => {
+                    results.push((era, None));
+                    progress.inc(1);
+                    era
+                }
+            };
+        era = last_era + 1;
+    }
+    Ok(results)
+}
+
+/// Searches the last block up to `latest_height` at which the anchor still keeps `era`,
+/// returns its height and the newest era of the anchor at that block.
+async fn last_block_keeping_era(
+    archival_client: &Client,
+    anchor_account_id: &AccountId,
+    era: u64,
+    latest_height: BlockHeight,
+) -> anyhow::Result<Option<(BlockHeight, u64)>> {
+    let anchor = AnchorContract::new(anchor_account_id.clone(), archival_client);
+    // The anchor keeps the era at `low` (or is not deployed yet there) but not at `high`.
+    let mut low = 0;
+    let mut high = latest_height;
+    let mut found = None;
+    while high - low > 1 {
+        let middle = low + (high - low) / 2;
+        let mut status = None;
+        for height in middle..(middle + SKIPPED_BLOCKS_TO_TRY).min(high) {
+            match anchor
+                .get_anchor_status_at(Some(BlockId::Height(height)))
+                .await
+            {
+                Ok(anchor_status) => {
+                    status = Some((height, anchor_status.index_range_of_validator_set_history));
+                    break;
+                }
+                // A skipped block, or the anchor is not deployed yet at it.
+                Err(err) if is_missing_at_block_error(&err) => {}
+                Err(err) => return Err(err),
+            }
+        }
+        match status {
+            Some((height, range)) if range.start_index.0 <= era => {
+                low = height;
+                found = Some((height, range.end_index.0));
+            }
+            Some(_) => high = middle,
+            // No block with the anchor, either skipped blocks or before the anchor was deployed.
+            None if found.is_none() => low = middle,
+            None => high = middle,
+        }
+    }
+    Ok(found.filter(|(_, newest_era)| *newest_era >= era))
+}
+
+#[test]
+fn test_unbond_risks() {
+    let era = |era, validators: &[&str]| EraValidators {
+        era,
+        unprofitable_validator_ids: validators.iter().map(|e| e.parse().unwrap()).collect(),
+    };
+    let scan = AnchorScan {
+        appchain_id: "fusotao".to_string(),
+        anchor_account_id: "fusotao.octopus-registry.near".parse().unwrap(),
+        current_era: 53,
+        maximum_allowed_unprofitable_era_count: 3,
+        eras: vec![
+            era(50, &["alice.near", "bob.near"]),
+            era(51, &["bob.near", "carol.near"]),
+            era(52, &["bob.near", "carol.near", "dave.near"]),
+            era(53, &["alice.near", "bob.near", "carol.near"]),
+        ],
+        missing_eras: vec![],
+    };
+    assert_eq!(
+        scan.unbond_risks()
+            .iter()
+            .filter(|e| e.is_at_risk())
+            .map(|e| e.validator_id.as_str())
+            .collect::<Vec<_>>(),
+        vec!["bob.near", "carol.near"]
+    );
+    let risks = scan
+        .unbond_risks()
+        .into_iter()
+        .map(|e| {
+            (
+                e.validator_id.to_string(),
+                e.streak,
+                e.eras_left,
+                e.predicted_unbond_era,
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        risks,
+        vec![
+            ("bob.near".to_string(), 4, 0, 53),
+            ("carol.near".to_string(), 3, 0, 53),
+            ("alice.near".to_string(), 1, 2, 55),
+        ]
+    );
+
+    let scan = AnchorScan {
+        eras: vec![
+            era(50, &["bob.near"]),
+            era(52, &["bob.near"]),
+            era(53, &["bob.near"]),
+        ],
+        missing_eras: vec![51],
+        ..scan
+    };
+    let risks = scan.unbond_risks();
+    assert_eq!(risks.len(), 1);
+    assert_eq!((risks[0].streak, risks[0].eras_left), (2, 1));
+    assert!(risks[0].is_at_risk());
+}